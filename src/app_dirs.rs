@@ -0,0 +1,113 @@
+// 应用目录 - 配置、数据、缓存文件的存放位置
+//
+// 设置环境变量 WECOM_MULTI_HOME 后,所有文件都存放在该目录下 (便携模式/测试)
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// 应用目录名
+pub const APP_DIR_NAME: &str = "wecom-multi-open";
+
+/// 覆盖所有目录的环境变量
+pub const HOME_ENV: &str = "WECOM_MULTI_HOME";
+
+fn home_dir() -> PathBuf {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+}
+
+fn env_dir(name: &str) -> Option<PathBuf> {
+    std::env::var_os(name).filter(|v| !v.is_empty()).map(PathBuf::from)
+}
+
+/// 配置目录
+/// - Windows: %APPDATA%\wecom-multi-open
+/// - macOS: ~/Library/Application Support/wecom-multi-open
+/// - Linux: $XDG_CONFIG_HOME/wecom-multi-open (默认 ~/.config)
+pub fn config_dir() -> PathBuf {
+    if let Some(dir) = env_dir(HOME_ENV) {
+        return dir;
+    }
+
+    let base = if cfg!(target_os = "windows") {
+        env_dir("APPDATA").unwrap_or_else(|| home_dir().join("AppData").join("Roaming"))
+    } else if cfg!(target_os = "macos") {
+        home_dir().join("Library/Application Support")
+    } else {
+        env_dir("XDG_CONFIG_HOME").unwrap_or_else(|| home_dir().join(".config"))
+    };
+
+    base.join(APP_DIR_NAME)
+}
+
+/// 数据目录 (实例注册表、实例数据等)
+/// - Windows: %LOCALAPPDATA%\wecom-multi-open
+/// - macOS: ~/Library/Application Support/wecom-multi-open
+/// - Linux: $XDG_DATA_HOME/wecom-multi-open (默认 ~/.local/share)
+pub fn data_dir() -> PathBuf {
+    if let Some(dir) = env_dir(HOME_ENV) {
+        return dir;
+    }
+
+    let base = if cfg!(target_os = "windows") {
+        env_dir("LOCALAPPDATA").unwrap_or_else(|| home_dir().join("AppData").join("Local"))
+    } else if cfg!(target_os = "macos") {
+        home_dir().join("Library/Application Support")
+    } else {
+        env_dir("XDG_DATA_HOME").unwrap_or_else(|| home_dir().join(".local/share"))
+    };
+
+    base.join(APP_DIR_NAME)
+}
+
+/// 缓存目录 (可随时删除)
+/// - Windows: %LOCALAPPDATA%\wecom-multi-open\cache
+/// - macOS: ~/Library/Caches/wecom-multi-open
+/// - Linux: $XDG_CACHE_HOME/wecom-multi-open (默认 ~/.cache)
+pub fn cache_dir() -> PathBuf {
+    if let Some(dir) = env_dir(HOME_ENV) {
+        return dir.join("cache");
+    }
+
+    if cfg!(target_os = "windows") {
+        data_dir().join("cache")
+    } else if cfg!(target_os = "macos") {
+        home_dir().join("Library/Caches").join(APP_DIR_NAME)
+    } else {
+        env_dir("XDG_CACHE_HOME")
+            .unwrap_or_else(|| home_dir().join(".cache"))
+            .join(APP_DIR_NAME)
+    }
+}
+
+/// 原子写入文件: 先写临时文件再重命名,避免进程中断导致文件损坏
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("创建目录失败 {}: {}", parent.display(), e))?;
+    }
+
+    let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+    let result = fs::File::create(&tmp)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&tmp, path));
+
+    result.map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        format!("写入文件失败 {}: {}", path.display(), e)
+    })
+}
+
+/// 为单元测试创建独立的临时目录
+#[cfg(test)]
+pub(crate) fn temp_test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("wecom-multi-test-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,412 @@
+// 命令行参数解析
+//
+// 与配置项同名的选项 (--app / --count / --isolation 等) 统一作为配置覆盖,
+// 其余选项按子命令解析。不带子命令或只给数量时保持旧版行为: 启动实例并等待回车。
+
+use serde::Serialize;
+use std::path::PathBuf;
+use wecom_multi_open::config;
+
+pub const USAGE: &str = "用法: wecom-multi-open-cli <命令> [选项]
+
+命令:
+  spawn [数量]              启动实例
+  list [--all]              列出实例 (--all 包含非本工具启动的实例)
+  kill <ID|PID>...          结束实例
+  kill-all [--all]          结束全部实例 (--all 包含非本工具启动的实例)
+  status                    查看运行状态
+  profile <操作> ...        管理账号配置 (profile help 查看详情)
+  paths [--clear-cache]     查看检测到的应用路径
+  doctor [paths]            环境自检 (paths: 只看路径检测详情)
+  clean [--sandboxes]       清理失效记录、路径缓存和未使用的副本
+  top [--interval 秒] [--once]  资源监控
+  daemon [start|stop|status]  后台服务 (运行时 CLI 和 GUI 共享实例列表)
+  config                    查看生效的配置
+
+spawn 选项:
+  --app-path <路径>         应用路径 (默认自动检测)
+  --data-dir <目录>         数据目录 (多个实例时为每个实例创建子目录)
+  --proxy <scheme://host:port>  代理
+  --arg <参数>              额外启动参数 (可重复)
+  --wait                    启动后等待回车再退出
+  --dry-run                 只显示启动计划,不启动实例
+
+通用选项 (覆盖配置文件):
+  --app <wecom|wechat>      应用类型
+  --count <数量>            实例数量
+  --isolation <simple|sandboxie>  隔离方式
+  --log-level <error|warn|info|debug>
+  其他配置项同样可用 --<键名> <值> 覆盖,运行 config 查看全部键名
+
+全局选项:
+  --json                    以 JSON 格式输出结果和错误
+  --non-interactive         不等待回车 (标准输入不是终端时自动启用)
+
+退出码:
+  0 成功  1 失败  2 参数或配置有误  3 部分失败  4 未找到 (应用、实例或账号配置)  5 当前平台不支持";
+
+/// 错误类别 (决定退出码)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    Failed,
+    /// 参数或配置有误
+    Usage,
+    /// 部分成功
+    Partial,
+    /// 未找到应用、实例或账号配置
+    NotFound,
+    /// 当前平台不支持
+    Unsupported,
+}
+
+impl ErrorKind {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Failed => 1,
+            ErrorKind::Usage => 2,
+            ErrorKind::Partial => 3,
+            ErrorKind::NotFound => 4,
+            ErrorKind::Unsupported => 5,
+        }
+    }
+}
+
+/// 命令执行错误
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CliError {
+    pub kind: ErrorKind,
+    pub message: String,
+    /// 结果已经输出 (JSON 模式下不再单独输出错误)
+    #[serde(skip)]
+    pub reported: bool,
+}
+
+impl CliError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            reported: false,
+        }
+    }
+
+    /// 标记结果已输出
+    pub fn reported(mut self) -> Self {
+        self.reported = true;
+        self
+    }
+}
+
+impl From<String> for CliError {
+    fn from(message: String) -> Self {
+        Self::new(ErrorKind::Failed, message)
+    }
+}
+
+/// 子命令
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Spawn(SpawnArgs),
+    List { all: bool },
+    Kill { targets: Vec<String> },
+    KillAll { all: bool },
+    Status,
+    Profile(Vec<String>),
+    Paths { clear_cache: bool },
+    Doctor { paths: bool },
+    Clean { sandboxes: bool },
+    Top { interval: u64, once: bool },
+    Daemon(DaemonAction),
+    Config,
+    Help,
+    Version,
+}
+
+/// daemon 的操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DaemonAction {
+    /// 在前台运行后台服务
+    Start,
+    Stop,
+    Status,
+}
+
+/// spawn 的参数
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpawnArgs {
+    pub app_path: Option<PathBuf>,
+    pub data_dir: Option<PathBuf>,
+    pub proxy: Option<String>,
+    pub args: Vec<String>,
+    /// 启动后等待回车 (双击运行时窗口不会立即关闭)
+    pub wait: bool,
+    /// 只生成启动计划
+    pub dry_run: bool,
+}
+
+/// 解析结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cli {
+    pub command: Command,
+    /// 配置覆盖 (键名, 值)
+    pub overrides: Vec<(String, String)>,
+    /// JSON 输出
+    pub json: bool,
+}
+
+/// 解析命令行参数 (不含程序名)
+pub fn parse(args: &[String]) -> Result<Cli, String> {
+    // 全局选项可以出现在任意位置 (--arg 的参数值除外)
+    let mut json = false;
+    let mut interactive = true;
+    let mut rest = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--non-interactive" => interactive = false,
+            "--arg" => {
+                rest.push(arg.clone());
+                rest.extend(iter.next().cloned());
+            }
+            _ => rest.push(arg.clone()),
+        }
+    }
+
+    let mut cli = match rest.split_first() {
+        Some((first, args)) if first == "profile" => Cli {
+            command: Command::Profile(args.to_vec()),
+            overrides: Vec::new(),
+            json,
+        },
+        Some((first, args)) if !first.starts_with('-') && first.parse::<u8>().is_err() => parse_command(first, args)?,
+        // 不带子命令: 旧版用法,交互运行时启动后等待回车
+        _ => {
+            let mut cli = parse_command("spawn", &rest)?;
+            if let Command::Spawn(spawn) = &mut cli.command {
+                spawn.wait = interactive && !json;
+            }
+            cli
+        }
+    };
+
+    cli.json = json;
+    if !interactive {
+        if let Command::Spawn(spawn) = &mut cli.command {
+            spawn.wait = false;
+        }
+    }
+    Ok(cli)
+}
+
+fn parse_command(name: &str, args: &[String]) -> Result<Cli, String> {
+    let mut command = match name {
+        "spawn" => Command::Spawn(SpawnArgs::default()),
+        "list" | "ls" => Command::List { all: false },
+        "kill" => Command::Kill { targets: Vec::new() },
+        "kill-all" => Command::KillAll { all: false },
+        "status" => Command::Status,
+        "paths" => Command::Paths { clear_cache: false },
+        "doctor" => Command::Doctor { paths: false },
+        "clean" => Command::Clean { sandboxes: false },
+        "top" => Command::Top {
+            interval: 2,
+            once: false,
+        },
+        "daemon" => Command::Daemon(DaemonAction::Start),
+        "config" => Command::Config,
+        "help" => Command::Help,
+        "version" => Command::Version,
+        _ => return Err(format!("未知命令: {} (运行 help 查看用法)", name)),
+    };
+    let mut overrides = Vec::new();
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                return Ok(Cli {
+                    command: Command::Help,
+                    overrides,
+                    json: false,
+                })
+            }
+            "-V" | "--version" => {
+                return Ok(Cli {
+                    command: Command::Version,
+                    overrides,
+                    json: false,
+                })
+            }
+            _ => {}
+        }
+
+        let mut value = |flag: &str| {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| format!("选项 {} 缺少参数值", flag))
+        };
+
+        match (&mut command, arg.as_str()) {
+            (Command::Spawn(spawn), "--app-path") => spawn.app_path = Some(PathBuf::from(value(arg)?)),
+            (Command::Spawn(spawn), "--data-dir") => spawn.data_dir = Some(PathBuf::from(value(arg)?)),
+            (Command::Spawn(spawn), "--proxy") => spawn.proxy = Some(value(arg)?),
+            (Command::Spawn(spawn), "--arg") => spawn.args.push(value(arg)?),
+            (Command::Spawn(spawn), "--wait") => spawn.wait = true,
+            (Command::Spawn(spawn), "--dry-run") => spawn.dry_run = true,
+            (Command::Spawn(_), count) if count.parse::<u8>().is_ok() => {
+                overrides.push(("count".to_string(), count.to_string()))
+            }
+            (Command::List { all } | Command::KillAll { all }, "--all") => *all = true,
+            (Command::Kill { targets }, target) if !target.starts_with('-') => targets.push(target.to_string()),
+            (Command::Paths { clear_cache }, "--clear-cache") => *clear_cache = true,
+            (Command::Doctor { paths }, "paths") => *paths = true,
+            (Command::Clean { sandboxes }, "--sandboxes") => *sandboxes = true,
+            (Command::Top { interval, .. }, "--interval") => {
+                let v = value(arg)?;
+                *interval = v.parse::<u64>().map_err(|_| format!("无效的刷新间隔: {}", v))?.max(1);
+            }
+            (Command::Top { once, .. }, "--once") => *once = true,
+            (Command::Daemon(action), "start") => *action = DaemonAction::Start,
+            (Command::Daemon(action), "stop") => *action = DaemonAction::Stop,
+            (Command::Daemon(action), "status") => *action = DaemonAction::Status,
+            (_, flag) if flag.starts_with("--") => {
+                let key = flag[2..].replace('-', "_");
+                if !config::KEYS.contains(&key.as_str()) {
+                    return Err(format!("未知选项: {} (运行 help 查看用法)", flag));
+                }
+                overrides.push((key, value(arg)?));
+            }
+            (_, other) => return Err(format!("无法识别的参数: {}", other)),
+        }
+    }
+
+    if matches!(&command, Command::Kill { targets } if targets.is_empty()) {
+        return Err("用法: kill <ID|PID>...".to_string());
+    }
+
+    Ok(Cli {
+        command,
+        overrides,
+        json: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(line: &str) -> Result<Cli, String> {
+        let args: Vec<String> = line.split_whitespace().map(String::from).collect();
+        parse(&args)
+    }
+
+    #[test]
+    fn test_legacy_invocation() {
+        let cli = parse_str("").unwrap();
+        assert_eq!(
+            cli.command,
+            Command::Spawn(SpawnArgs {
+                wait: true,
+                ..Default::default()
+            })
+        );
+
+        let cli = parse_str("5 --app wechat").unwrap();
+        assert!(matches!(cli.command, Command::Spawn(SpawnArgs { wait: true, .. })));
+        assert_eq!(
+            cli.overrides,
+            vec![("count".to_string(), "5".to_string()), ("app".to_string(), "wechat".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_spawn_options() {
+        let cli = parse_str("spawn 2 --isolation sandboxie --data-dir /d --proxy socks5://h:1 --arg -a --arg -b --dry-run").unwrap();
+        assert_eq!(
+            cli.command,
+            Command::Spawn(SpawnArgs {
+                app_path: None,
+                data_dir: Some(PathBuf::from("/d")),
+                proxy: Some("socks5://h:1".to_string()),
+                args: vec!["-a".to_string(), "-b".to_string()],
+                wait: false,
+                dry_run: true,
+            })
+        );
+        assert_eq!(
+            cli.overrides,
+            vec![
+                ("count".to_string(), "2".to_string()),
+                ("isolation".to_string(), "sandboxie".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_subcommands() {
+        assert_eq!(parse_str("list --all").unwrap().command, Command::List { all: true });
+        assert_eq!(
+            parse_str("kill 1234 abc-42").unwrap().command,
+            Command::Kill {
+                targets: vec!["1234".to_string(), "abc-42".to_string()]
+            }
+        );
+        assert_eq!(parse_str("paths --clear-cache").unwrap().command, Command::Paths { clear_cache: true });
+        assert_eq!(parse_str("doctor paths").unwrap().command, Command::Doctor { paths: true });
+        assert_eq!(
+            parse_str("top --interval 0 --once").unwrap().command,
+            Command::Top { interval: 1, once: true }
+        );
+        assert_eq!(parse_str("status --help").unwrap().command, Command::Help);
+        assert_eq!(parse_str("daemon").unwrap().command, Command::Daemon(DaemonAction::Start));
+        assert_eq!(parse_str("daemon stop").unwrap().command, Command::Daemon(DaemonAction::Stop));
+        assert_eq!(
+            parse_str("profile add Sales --app wechat").unwrap().command,
+            Command::Profile(vec!["add".into(), "Sales".into(), "--app".into(), "wechat".into()])
+        );
+    }
+
+    #[test]
+    fn test_global_flags() {
+        let cli = parse_str("--json list").unwrap();
+        assert!(cli.json);
+        assert_eq!(cli.command, Command::List { all: false });
+
+        let cli = parse_str("3 --non-interactive").unwrap();
+        assert!(matches!(cli.command, Command::Spawn(SpawnArgs { wait: false, .. })));
+        assert!(matches!(parse_str("--json").unwrap().command, Command::Spawn(SpawnArgs { wait: false, .. })));
+        assert!(matches!(
+            parse_str("spawn --wait --non-interactive").unwrap().command,
+            Command::Spawn(SpawnArgs { wait: false, .. })
+        ));
+
+        // --arg 的参数值原样传给应用
+        let cli = parse_str("spawn --arg --json").unwrap();
+        assert!(!cli.json);
+        assert!(matches!(&cli.command, Command::Spawn(spawn) if spawn.args == ["--json"]));
+
+        assert!(parse_str("profile list --json").unwrap().json);
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(CliError::from("x".to_string()).kind.exit_code(), 1);
+        assert_eq!(ErrorKind::Partial.exit_code(), 3);
+        assert_eq!(ErrorKind::Unsupported.exit_code(), 5);
+        assert_eq!(
+            serde_json::to_string(&CliError::new(ErrorKind::NotFound, "未找到实例: 1")).unwrap(),
+            r#"{"kind":"not_found","message":"未找到实例: 1"}"#
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(parse_str("frobnicate").unwrap_err().contains("未知命令"));
+        assert!(parse_str("kill").is_err());
+        assert!(parse_str("list --data-dir /d").unwrap_err().contains("未知选项"));
+        assert!(parse_str("spawn --proxy").unwrap_err().contains("缺少参数值"));
+        assert!(parse_str("status extra").is_err());
+    }
+}
@@ -0,0 +1,400 @@
+// 配置文件 - 启动数量、应用路径、隔离方式等默认值
+//
+// 优先级 (后者覆盖前者):
+//   1. 内置默认值
+//   2. 配置文件 (<配置目录>/config.json,可用 WECOM_MULTI_CONFIG 指定其他路径)
+//   3. 环境变量 (WECOM_MULTI_<键名大写>,如 WECOM_MULTI_COUNT=5)
+//   4. 命令行参数
+
+use crate::scanner::ScanOptions;
+use crate::{app_dirs, migrations, AppType, IsolationMode};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// 配置文件名 (位于配置目录)
+pub const CONFIG_FILE: &str = "config.json";
+
+/// 指定配置文件路径的环境变量
+pub const CONFIG_ENV: &str = "WECOM_MULTI_CONFIG";
+
+/// 环境变量前缀
+pub const ENV_PREFIX: &str = "WECOM_MULTI_";
+
+/// 可通过环境变量和命令行覆盖的键
+pub const KEYS: &[&str] = &[
+    "app",
+    "count",
+    "isolation",
+    "log_level",
+    "keep_on_exit",
+    "wecom_path",
+    "wechat_path",
+    "launch_interval_ms",
+    "mutex_delay_ms",
+    "max_count",
+    "api_enabled",
+    "api_port",
+    "api_token",
+];
+
+/// 日志级别
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+}
+
+/// 应用路径 (为空时自动检测)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppPaths {
+    pub wecom: Option<PathBuf>,
+    pub wechat: Option<PathBuf>,
+    /// 自动检测时额外搜索的目录
+    pub search_roots: Vec<PathBuf>,
+    /// 在搜索目录中匹配的通配符 (不区分大小写,为空时使用内置的文件名)
+    pub search_patterns: Vec<String>,
+}
+
+impl AppPaths {
+    pub fn get(&self, app_type: &AppType) -> Option<&PathBuf> {
+        match app_type {
+            AppType::WeCom => self.wecom.as_ref(),
+            AppType::WeChat => self.wechat.as_ref(),
+        }
+    }
+}
+
+/// 启动策略
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpawnPolicy {
+    /// 两个实例之间的启动间隔 (为空时使用平台默认值: Windows 800ms, macOS 1000ms)
+    pub launch_interval_ms: Option<u64>,
+    /// 关闭 Mutex 后等待多久再启动 (Windows)
+    pub mutex_delay_ms: u64,
+    /// 单次最多启动的实例数
+    pub max_count: u8,
+}
+
+impl Default for SpawnPolicy {
+    fn default() -> Self {
+        Self {
+            launch_interval_ms: None,
+            mutex_delay_ms: 100,
+            max_count: 10,
+        }
+    }
+}
+
+/// 本地 REST API (随后台服务启动,仅监听 127.0.0.1)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiOptions {
+    pub enabled: bool,
+    pub port: u16,
+    /// 访问令牌 (请求头 Authorization: Bearer <令牌>),启用时必须设置
+    pub token: Option<String>,
+}
+
+impl Default for ApiOptions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 17890,
+            token: None,
+        }
+    }
+}
+
+/// 配置
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// 格式版本
+    pub schema_version: u32,
+    /// 默认应用类型
+    pub app: AppType,
+    /// 默认启动数量
+    pub count: u8,
+    /// 默认隔离方式
+    pub isolation: IsolationMode,
+    pub log_level: LogLevel,
+    /// 退出 GUI 时是否保留实例
+    pub keep_on_exit: bool,
+    pub app_paths: AppPaths,
+    pub spawn: SpawnPolicy,
+    /// 目录扫描参数
+    pub scan: ScanOptions,
+    pub api: ApiOptions,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            schema_version: migrations::CONFIG.current,
+            app: AppType::WeCom,
+            count: 3,
+            isolation: IsolationMode::Simple,
+            log_level: LogLevel::Info,
+            keep_on_exit: true,
+            app_paths: AppPaths::default(),
+            spawn: SpawnPolicy::default(),
+            scan: ScanOptions::default(),
+            api: ApiOptions::default(),
+        }
+    }
+}
+
+impl Config {
+    /// 配置文件路径
+    pub fn default_path() -> PathBuf {
+        std::env::var_os(CONFIG_ENV)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| app_dirs::config_dir().join(CONFIG_FILE))
+    }
+
+    /// 解析配置文件内容,出错时给出行号和列号
+    pub fn parse(text: &str, origin: &Path) -> Result<Self, String> {
+        let config: Self = serde_json::from_str(text).map_err(|e| {
+            format!(
+                "配置文件 {} 第 {} 行第 {} 列: {}",
+                origin.display(),
+                e.line(),
+                e.column(),
+                strip_position(&e.to_string())
+            )
+        })?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// 加载配置文件,文件不存在时使用默认值
+    pub fn load_file(path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&migrations::upgrade_file(&migrations::CONFIG, path, text)?, path),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("读取配置文件失败 {}: {}", path.display(), e)),
+        }
+    }
+
+    /// 按优先级合并: 配置文件 < 环境变量 < 命令行参数
+    pub fn load_layered<E>(path: &Path, env: E, overrides: &[(String, String)]) -> Result<Self, String>
+    where
+        E: Fn(&str) -> Option<String>,
+    {
+        let mut config = Self::load_file(path)?;
+
+        for key in KEYS {
+            let name = format!("{}{}", ENV_PREFIX, key.to_uppercase());
+            if let Some(value) = env(&name) {
+                config
+                    .set(key, &value)
+                    .map_err(|e| format!("环境变量 {}: {}", name, e))?;
+            }
+        }
+
+        for (key, value) in overrides {
+            config.set(key, value).map_err(|e| format!("命令行参数 --{}: {}", key.replace('_', "-"), e))?;
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// 从默认位置加载 (配置文件 + 当前进程环境变量 + 命令行参数)
+    pub fn load(overrides: &[(String, String)]) -> Result<Self, String> {
+        Self::load_layered(&Self::default_path(), |name| std::env::var(name).ok(), overrides)
+    }
+
+    /// 设置单个键
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        match key {
+            "app" => self.app = parse_app_type(value)?,
+            "count" => self.count = parse_number(value)?,
+            "isolation" => {
+                self.isolation = match value.to_lowercase().as_str() {
+                    "simple" => IsolationMode::Simple,
+                    "sandboxie" => IsolationMode::Sandboxie,
+                    _ => return Err(format!("未知隔离方式 '{}' (可选: simple, sandboxie)", value)),
+                }
+            }
+            "log_level" => {
+                self.log_level = match value.to_lowercase().as_str() {
+                    "error" => LogLevel::Error,
+                    "warn" => LogLevel::Warn,
+                    "info" => LogLevel::Info,
+                    "debug" => LogLevel::Debug,
+                    _ => return Err(format!("未知日志级别 '{}' (可选: error, warn, info, debug)", value)),
+                }
+            }
+            "keep_on_exit" => self.keep_on_exit = parse_bool(value)?,
+            "wecom_path" => self.app_paths.wecom = (!value.is_empty()).then(|| PathBuf::from(value)),
+            "wechat_path" => self.app_paths.wechat = (!value.is_empty()).then(|| PathBuf::from(value)),
+            "launch_interval_ms" => self.spawn.launch_interval_ms = Some(parse_number(value)?),
+            "mutex_delay_ms" => self.spawn.mutex_delay_ms = parse_number(value)?,
+            "max_count" => self.spawn.max_count = parse_number(value)?,
+            "api_enabled" => self.api.enabled = parse_bool(value)?,
+            "api_port" => self.api.port = parse_number(value)?,
+            "api_token" => self.api.token = (!value.is_empty()).then(|| value.to_string()),
+            _ => return Err(format!("未知配置项 '{}'", key)),
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.spawn.max_count == 0 {
+            return Err("spawn.max_count 必须大于 0".to_string());
+        }
+        if self.count == 0 || self.count > self.spawn.max_count {
+            return Err(format!("count 应在 1-{} 之间: {}", self.spawn.max_count, self.count));
+        }
+        if self.api.enabled && self.api.token.as_deref().unwrap_or_default().len() < 16 {
+            return Err("启用 api 时必须设置至少 16 个字符的 api.token".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn log_enabled(&self, level: LogLevel) -> bool {
+        level <= self.log_level
+    }
+
+    /// 实例启动间隔
+    pub fn launch_interval(&self, platform_default_ms: u64) -> std::time::Duration {
+        std::time::Duration::from_millis(self.spawn.launch_interval_ms.unwrap_or(platform_default_ms))
+    }
+}
+
+fn parse_app_type(value: &str) -> Result<AppType, String> {
+    match value.to_lowercase().as_str() {
+        "wecom" => Ok(AppType::WeCom),
+        "wechat" => Ok(AppType::WeChat),
+        _ => Err(format!("未知应用类型 '{}' (可选: wecom, wechat)", value)),
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(format!("应为 true 或 false: '{}'", value)),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("无效的数字: '{}'", value))
+}
+
+/// serde_json 的错误信息末尾带有 " at line X column Y",已单独输出
+fn strip_position(message: &str) -> &str {
+    message.rfind(" at line ").map(|i| &message[..i]).unwrap_or(message)
+}
+
+static CURRENT: OnceLock<RwLock<Arc<Config>>> = OnceLock::new();
+
+fn current_slot() -> &'static RwLock<Arc<Config>> {
+    CURRENT.get_or_init(|| RwLock::new(Arc::new(Config::default())))
+}
+
+/// 当前生效的配置 (未调用 install 时为默认值)
+pub fn current() -> Arc<Config> {
+    current_slot().read().map(|c| c.clone()).unwrap_or_default()
+}
+
+/// 设置当前生效的配置
+pub fn install(config: Config) {
+    if let Ok(mut slot) = current_slot().write() {
+        *slot = Arc::new(config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    #[test]
+    fn test_missing_file_uses_defaults() {
+        let dir = app_dirs::temp_test_dir("config-missing");
+        let config = Config::load_layered(&dir.join(CONFIG_FILE), no_env, &[]).unwrap();
+        assert_eq!(config, Config::default());
+        assert_eq!(config.count, 3);
+        assert!(config.keep_on_exit);
+    }
+
+    #[test]
+    fn test_partial_file_keeps_other_defaults() {
+        let config = Config::parse(
+            r#"{ "count": 5, "spawn": { "launch_interval_ms": 1500 } }"#,
+            Path::new("config.json"),
+        )
+        .unwrap();
+        assert_eq!(config.count, 5);
+        assert_eq!(config.spawn.launch_interval_ms, Some(1500));
+        assert_eq!(config.spawn.mutex_delay_ms, 100);
+        assert_eq!(config.isolation, IsolationMode::Simple);
+    }
+
+    #[test]
+    fn test_errors_report_line_and_column() {
+        let text = "{\n  \"count\": 2,\n  \"isolaton\": \"sandboxie\"\n}";
+        let err = Config::parse(text, Path::new("config.json")).unwrap_err();
+        assert!(err.contains("第 3 行"), "{}", err);
+        assert!(err.contains("isolaton"), "{}", err);
+
+        let err = Config::parse("{\n  \"count\": \"many\"\n}", Path::new("config.json")).unwrap_err();
+        assert!(err.contains("第 2 行"), "{}", err);
+    }
+
+    #[test]
+    fn test_precedence_file_env_cli() {
+        let dir = app_dirs::temp_test_dir("config-precedence");
+        let path = dir.join(CONFIG_FILE);
+        fs::write(&path, r#"{ "count": 4, "log_level": "warn", "keep_on_exit": false }"#).unwrap();
+
+        let env: HashMap<&str, &str> = [("WECOM_MULTI_COUNT", "6"), ("WECOM_MULTI_ISOLATION", "sandboxie")].into();
+        let env = |name: &str| env.get(name).map(|v| v.to_string());
+        let cli = vec![("count".to_string(), "8".to_string())];
+
+        let config = Config::load_layered(&path, env, &cli).unwrap();
+        assert_eq!(config.count, 8); // 命令行覆盖环境变量
+        assert_eq!(config.isolation, IsolationMode::Sandboxie); // 环境变量覆盖默认值
+        assert_eq!(config.log_level, LogLevel::Warn); // 配置文件覆盖默认值
+        assert!(!config.keep_on_exit);
+    }
+
+    #[test]
+    fn test_api_requires_token() {
+        let mut config = Config::default();
+        config.set("api_enabled", "true").unwrap();
+        assert!(config.validate().unwrap_err().contains("api.token"));
+
+        config.set("api_token", "0123456789abcdef").unwrap();
+        config.set("api_port", "8080").unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.api.port, 8080);
+    }
+
+    #[test]
+    fn test_invalid_override_names_source() {
+        let dir = app_dirs::temp_test_dir("config-invalid");
+        let env = |name: &str| (name == "WECOM_MULTI_KEEP_ON_EXIT").then(|| "maybe".to_string());
+        let err = Config::load_layered(&dir.join(CONFIG_FILE), env, &[]).unwrap_err();
+        assert!(err.contains("WECOM_MULTI_KEEP_ON_EXIT"), "{}", err);
+
+        let cli = vec![("count".to_string(), "50".to_string())];
+        assert!(Config::load_layered(&dir.join(CONFIG_FILE), no_env, &cli).is_err());
+    }
+}
@@ -0,0 +1,254 @@
+// 配置热加载 - 监视配置文件和账号配置,修改后无需重启即可生效
+//
+// 使用轮询 (比较修改时间和文件大小) 而非系统通知,各平台行为一致。
+// 修改后的文件校验失败时保留原配置,并通过 ReloadFailed 事件报告错误。
+
+use crate::config::{self, Config};
+use crate::profiles::{AccountProfile, ProfileStore};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// 默认轮询间隔
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 文件状态 (用于判断是否被修改)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Option<Self> {
+        let meta = fs::metadata(path).ok()?;
+        Some(Self {
+            modified: meta.modified().ok(),
+            len: meta.len(),
+        })
+    }
+}
+
+/// 轮询式文件监视器
+#[derive(Debug)]
+pub struct FileWatcher {
+    files: Vec<(PathBuf, Option<FileStamp>)>,
+}
+
+impl FileWatcher {
+    /// 监视指定文件 (文件可以暂不存在)
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let files = paths
+            .into_iter()
+            .map(|path| {
+                let stamp = FileStamp::of(&path);
+                (path, stamp)
+            })
+            .collect();
+        Self { files }
+    }
+
+    /// 返回自上次检查以来被创建、修改或删除的文件
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        for (path, stamp) in &mut self.files {
+            let current = FileStamp::of(path);
+            if current != *stamp {
+                *stamp = current;
+                changed.push(path.clone());
+            }
+        }
+        changed
+    }
+}
+
+/// 配置变更事件 (以 "config-changed" 事件发送给前端)
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind")]
+pub enum ConfigEvent {
+    /// 配置已重新加载并生效
+    ConfigChanged { config: Config },
+    /// 账号配置已重新加载
+    ProfilesChanged { profiles: Vec<AccountProfile> },
+    /// 文件有误,继续使用原配置
+    ReloadFailed { path: PathBuf, error: String },
+}
+
+/// 配置热加载器
+pub struct ConfigReloader {
+    config_path: PathBuf,
+    profiles_path: PathBuf,
+    overrides: Vec<(String, String)>,
+    watcher: FileWatcher,
+    current: Config,
+}
+
+impl ConfigReloader {
+    /// `current` 为当前生效的配置,`overrides` 为启动时的命令行参数 (重新加载时继续生效)
+    pub fn new(
+        config_path: PathBuf,
+        profiles_path: PathBuf,
+        current: Config,
+        overrides: Vec<(String, String)>,
+    ) -> Self {
+        let watcher = FileWatcher::new(vec![config_path.clone(), profiles_path.clone()]);
+        Self {
+            config_path,
+            profiles_path,
+            overrides,
+            watcher,
+            current,
+        }
+    }
+
+    /// 监视默认位置的配置文件和账号配置
+    pub fn with_defaults(current: Config, overrides: Vec<(String, String)>) -> Self {
+        Self::new(Config::default_path(), ProfileStore::default_path(), current, overrides)
+    }
+
+    pub fn current(&self) -> &Config {
+        &self.current
+    }
+
+    /// 检查文件变化,返回产生的事件
+    pub fn check(&mut self) -> Vec<ConfigEvent> {
+        let mut events = Vec::new();
+
+        for path in self.watcher.poll() {
+            if path == self.config_path {
+                events.push(self.reload_config());
+            } else if path == self.profiles_path {
+                events.push(self.reload_profiles());
+            }
+        }
+
+        events
+    }
+
+    fn reload_config(&mut self) -> ConfigEvent {
+        let loaded = Config::load_layered(&self.config_path, |name| std::env::var(name).ok(), &self.overrides);
+        match loaded {
+            Ok(config) => {
+                self.current = config.clone();
+                ConfigEvent::ConfigChanged { config }
+            }
+            Err(error) => ConfigEvent::ReloadFailed {
+                path: self.config_path.clone(),
+                error,
+            },
+        }
+    }
+
+    fn reload_profiles(&self) -> ConfigEvent {
+        let loaded = ProfileStore::load(&self.profiles_path).and_then(|store| {
+            for profile in store.list() {
+                profile.validate()?;
+            }
+            Ok(store.profiles)
+        });
+
+        match loaded {
+            Ok(profiles) => ConfigEvent::ProfilesChanged { profiles },
+            Err(error) => ConfigEvent::ReloadFailed {
+                path: self.profiles_path.clone(),
+                error,
+            },
+        }
+    }
+
+    /// 持续监视,配置变化时替换全局配置并回调
+    pub async fn run<F>(mut self, interval: Duration, mut on_event: F)
+    where
+        F: FnMut(&ConfigEvent) + Send + 'static,
+    {
+        loop {
+            tokio::time::sleep(interval).await;
+
+            for event in self.check() {
+                match &event {
+                    ConfigEvent::ConfigChanged { config } => {
+                        config::install(config.clone());
+                        eprintln!("✓ 配置已重新加载");
+                    }
+                    ConfigEvent::ProfilesChanged { profiles } => {
+                        eprintln!("✓ 账号配置已重新加载 ({} 个)", profiles.len());
+                    }
+                    ConfigEvent::ReloadFailed { error, .. } => {
+                        eprintln!("⚠ 配置未生效,继续使用原配置: {}", error);
+                    }
+                }
+                on_event(&event);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_dirs;
+    use crate::config::CONFIG_FILE;
+    use crate::profiles::PROFILES_FILE;
+
+    fn reloader(dir: &Path) -> ConfigReloader {
+        ConfigReloader::new(dir.join(CONFIG_FILE), dir.join(PROFILES_FILE), Config::default(), Vec::new())
+    }
+
+    #[test]
+    fn test_watcher_detects_create_modify_delete() {
+        let dir = app_dirs::temp_test_dir("watch-file");
+        let path = dir.join("a.json");
+        let mut watcher = FileWatcher::new(vec![path.clone()]);
+        assert!(watcher.poll().is_empty());
+
+        fs::write(&path, "{}").unwrap();
+        assert_eq!(watcher.poll(), vec![path.clone()]);
+        assert!(watcher.poll().is_empty());
+
+        fs::write(&path, "{ \"count\": 4 }").unwrap();
+        assert_eq!(watcher.poll(), vec![path.clone()]);
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(watcher.poll(), vec![path]);
+    }
+
+    #[test]
+    fn test_valid_edit_is_applied() {
+        let dir = app_dirs::temp_test_dir("watch-valid");
+        let mut reloader = reloader(&dir);
+
+        fs::write(dir.join(CONFIG_FILE), r#"{ "count": 5 }"#).unwrap();
+        let events = reloader.check();
+        assert!(matches!(&events[..], [ConfigEvent::ConfigChanged { config }] if config.count == 5));
+        assert_eq!(reloader.current().count, 5);
+    }
+
+    #[test]
+    fn test_bad_edit_keeps_previous_config() {
+        let dir = app_dirs::temp_test_dir("watch-invalid");
+        let mut reloader = reloader(&dir);
+
+        fs::write(dir.join(CONFIG_FILE), r#"{ "count": 5 }"#).unwrap();
+        reloader.check();
+
+        fs::write(dir.join(CONFIG_FILE), "{ \"count\": 5, }").unwrap();
+        let events = reloader.check();
+        assert!(matches!(&events[..], [ConfigEvent::ReloadFailed { error, .. }] if error.contains("第 1 行")));
+        assert_eq!(reloader.current().count, 5);
+    }
+
+    #[test]
+    fn test_profiles_change_is_reported() {
+        let dir = app_dirs::temp_test_dir("watch-profiles");
+        let mut reloader = reloader(&dir);
+
+        fs::write(dir.join(PROFILES_FILE), r#"{ "profiles": [ { "name": "Sales" } ] }"#).unwrap();
+        let events = reloader.check();
+        assert!(matches!(&events[..], [ConfigEvent::ProfilesChanged { profiles }] if profiles[0].name == "Sales"));
+
+        fs::write(dir.join(PROFILES_FILE), r#"{ "profiles": [ { "name": "a/b" } ] }"#).unwrap();
+        let events = reloader.check();
+        assert!(matches!(&events[..], [ConfigEvent::ReloadFailed { .. }]));
+    }
+}
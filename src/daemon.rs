@@ -0,0 +1,1105 @@
+// 后台服务 - 统一持有实例状态,通过本地 IPC 提供 JSON-RPC 2.0 接口
+//
+// CLI 和 GUI 在后台服务运行时作为客户端读写实例列表,否则直接读写注册表文件。
+// 传输层: Unix 上为数据目录下的 Unix socket (仅当前用户可访问),
+// Windows 上为按用户名区分的命名管道。每行一个 JSON 请求 / 响应。
+
+use crate::hooks::{self, HookEvent};
+use crate::metrics;
+use crate::monitor::{InstanceStats, ResourceMonitor};
+use crate::profiles::{self, AccountProfile, ProfileStore};
+use crate::registry::{self, InstanceRecord, InstanceRegistry, ReconcileReport};
+use crate::scheduler::{self, Clock, ScheduleAction, ScheduledAction, Scheduler};
+use crate::{platform, SpawnRequest, SpawnResponse};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{watch, Mutex};
+
+/// Unix socket 文件名 (位于数据目录)
+pub const SOCKET_FILE: &str = "daemon.sock";
+
+/// JSON-RPC 错误码
+pub const PARSE_ERROR: i64 = -32700;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// 操作失败
+pub const FAILED: i64 = -32000;
+/// 未找到实例
+pub const NOT_FOUND: i64 = -32001;
+
+/// 默认通信地址
+#[cfg(unix)]
+pub fn default_endpoint() -> PathBuf {
+    crate::app_dirs::data_dir().join(SOCKET_FILE)
+}
+
+/// 默认通信地址
+///
+/// 命名管道默认的安全描述符只允许创建者、管理员和 SYSTEM 写入,其他用户无法发送请求。
+#[cfg(windows)]
+pub fn default_endpoint() -> PathBuf {
+    let user = std::env::var("USERNAME").unwrap_or_default();
+    PathBuf::from(format!(r"\\.\pipe\wecom-multi-open-{}", user))
+}
+
+/// JSON-RPC 请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcRequest {
+    pub jsonrpc: String,
+    /// 为空时是通知,不返回响应
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub params: Value,
+}
+
+/// JSON-RPC 响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcResponse {
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+/// JSON-RPC 错误
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl From<String> for RpcError {
+    fn from(message: String) -> Self {
+        Self::new(FAILED, message)
+    }
+}
+
+/// ping 的返回
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DaemonInfo {
+    pub version: String,
+    pub pid: u32,
+    pub instances: usize,
+}
+
+/// kill_all 中结束失败的实例
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct KillError {
+    pub pid: u32,
+    pub error: String,
+}
+
+/// kill_all 的返回
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct KillAllResult {
+    pub killed: Vec<u32>,
+    pub failed: Vec<KillError>,
+}
+
+/// 实际启动 / 结束进程的后端 (测试中可替换)
+pub trait Backend: Send + Sync + 'static {
+    fn spawn(&self, req: SpawnRequest) -> impl Future<Output = Result<SpawnResponse, String>> + Send;
+    /// 启动账号配置,返回应记录的实例
+    fn launch_profile(&self, profile: AccountProfile) -> impl Future<Output = Result<InstanceRecord, String>> + Send;
+    /// 在各自的 Sandboxie 沙盒中启动企业微信,返回应记录的实例 (默认不支持)
+    fn spawn_sandboxie(&self, _count: u8) -> impl Future<Output = Result<Vec<InstanceRecord>, String>> + Send {
+        async { Err("Sandboxie 仅支持 Windows 平台".to_string()) }
+    }
+    fn kill(&self, pid: u32) -> Result<(), String>;
+    /// 正常结束实例 (定时停止使用),默认直接结束
+    fn stop(&self, pid: u32) -> impl Future<Output = Result<(), String>> + Send {
+        let result = self.kill(pid);
+        async move { result }
+    }
+    /// 移除已退出的实例
+    fn reconcile(&self, registry: &mut InstanceRegistry) -> ReconcileReport;
+    /// 采样实例资源占用
+    fn sample(&self, pids: &[u32]) -> Vec<InstanceStats> {
+        ResourceMonitor::new().sample(pids)
+    }
+}
+
+/// 使用当前平台实现的后端
+pub struct PlatformBackend;
+
+impl Backend for PlatformBackend {
+    fn spawn(&self, req: SpawnRequest) -> impl Future<Output = Result<SpawnResponse, String>> + Send {
+        platform::spawn_multiple(req)
+    }
+
+    async fn launch_profile(&self, profile: AccountProfile) -> Result<InstanceRecord, String> {
+        profiles::launch(&profile).await
+    }
+
+    #[cfg(target_os = "windows")]
+    fn spawn_sandboxie(&self, count: u8) -> impl Future<Output = Result<Vec<InstanceRecord>, String>> + Send {
+        crate::wecom_manager::spawn_sandboxie(count)
+    }
+
+    fn kill(&self, pid: u32) -> Result<(), String> {
+        platform::kill_process(pid)
+    }
+
+    fn stop(&self, pid: u32) -> impl Future<Output = Result<(), String>> + Send {
+        crate::stop_process(pid, scheduler::STOP_GRACE)
+    }
+
+    fn reconcile(&self, registry: &mut InstanceRegistry) -> ReconcileReport {
+        registry.reconcile()
+    }
+}
+
+/// 后台服务
+pub struct Daemon<B> {
+    backend: Arc<B>,
+    registry: Mutex<InstanceRegistry>,
+    /// 账号配置文件路径
+    profiles: PathBuf,
+    shutdown: watch::Sender<bool>,
+}
+
+impl<B: Backend> Daemon<B> {
+    pub fn new(backend: B, registry: InstanceRegistry) -> Arc<Self> {
+        Self::with_profiles(backend, registry, ProfileStore::default_path())
+    }
+
+    /// 使用指定的账号配置文件
+    pub fn with_profiles(backend: B, registry: InstanceRegistry, profiles: PathBuf) -> Arc<Self> {
+        Arc::new(Self {
+            backend: Arc::new(backend),
+            registry: Mutex::new(registry),
+            profiles,
+            shutdown: watch::channel(false).0,
+        })
+    }
+
+    /// 等待 shutdown 请求
+    pub async fn stopped(&self) {
+        let mut receiver = self.shutdown.subscribe();
+        let _ = receiver.wait_for(|stopped| *stopped).await;
+    }
+
+    /// 处理一行请求,通知 (无 id) 不返回响应
+    pub async fn handle_line(&self, line: &str) -> Option<RpcResponse> {
+        let request: RpcRequest = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(e) => {
+                return Some(RpcResponse::failure(Value::Null, RpcError::new(PARSE_ERROR, format!("无效的请求: {}", e))))
+            }
+        };
+
+        let result = self.call(&request.method, request.params).await;
+        let id = request.id?;
+        Some(match result {
+            Ok(value) => RpcResponse::success(id, value),
+            Err(e) => RpcResponse::failure(id, e),
+        })
+    }
+
+    /// 执行一个方法 (JSON-RPC 和 REST API 共用)
+    pub async fn call(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "ping" => to_value(DaemonInfo {
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                pid: std::process::id(),
+                instances: self.registry.lock().await.instances.len(),
+            }),
+            "list" => {
+                self.reconcile().await;
+                to_value(&self.registry.lock().await.instances)
+            }
+            "reconcile" => to_value(self.reconcile().await),
+            "spawn" => {
+                let req: SpawnRequest = from_params(params)?;
+                // 启动过程较慢,不持有注册表锁
+                let started = Instant::now();
+                let result = self.backend.spawn(req.clone()).await;
+                if !req.dry_run {
+                    match &result {
+                        Ok(response) => metrics::counters().record_spawn(started.elapsed(), response),
+                        Err(_) => metrics::counters().record_spawn_error(),
+                    }
+                }
+                let response = result?;
+                if !req.dry_run {
+                    let records = registry::records_for_spawn(&req, &response);
+                    logged(self.update(move |r| records.into_iter().for_each(|record| r.add(record))).await);
+                }
+                to_value(response)
+            }
+            "spawn_sandboxie" => {
+                #[derive(Deserialize)]
+                struct Params {
+                    count: u8,
+                }
+                let Params { count } = from_params(params)?;
+
+                let records = self.backend.spawn_sandboxie(count).await?;
+                let added = records.clone();
+                logged(self.update(move |r| added.into_iter().for_each(|record| r.add(record))).await);
+                to_value(records)
+            }
+            "register" => {
+                let records: Vec<InstanceRecord> = from_params(params)?;
+                let count = records.len();
+                self.update(move |r| records.into_iter().for_each(|record| r.add(record))).await?;
+                to_value(count)
+            }
+            "forget" => {
+                #[derive(Deserialize)]
+                struct Params {
+                    pids: Vec<u32>,
+                }
+                let Params { pids } = from_params(params)?;
+
+                let removed: Vec<InstanceRecord> =
+                    self.update(move |r| pids.iter().filter_map(|&pid| r.remove(pid)).collect()).await?;
+                to_value(removed)
+            }
+            "kill" => {
+                #[derive(Deserialize)]
+                struct Params {
+                    pid: u32,
+                }
+                let Params { pid } = from_params(params)?;
+
+                let record = self.update(move |r| r.get(pid).cloned()).await?;
+                let record = record.ok_or_else(|| RpcError::new(NOT_FOUND, format!("未找到实例: {}", pid)))?;
+                // 钩子可能较慢,不持有注册表锁
+                hooks::run_for_record(&self.profiles, HookEvent::PreKill, &record, None).await;
+
+                self.backend.kill(pid)?;
+                to_value(logged(self.update(move |r| r.remove(pid)).await).flatten())
+            }
+            "kill_all" => {
+                let records = self.update(|r| r.instances.clone()).await?;
+                for record in &records {
+                    hooks::run_for_record(&self.profiles, HookEvent::PreKill, record, None).await;
+                }
+
+                let mut result = KillAllResult::default();
+                for pid in records.iter().map(|r| r.pid) {
+                    match self.backend.kill(pid) {
+                        Ok(()) => result.killed.push(pid),
+                        Err(error) => result.failed.push(KillError { pid, error }),
+                    }
+                }
+                let killed = result.killed.clone();
+                logged(
+                    self.update(move |r| {
+                        for pid in killed {
+                            r.remove(pid);
+                        }
+                    })
+                    .await,
+                );
+                to_value(result)
+            }
+            "restarted" => {
+                #[derive(Deserialize)]
+                struct Params {
+                    old_pid: u32,
+                    new_pid: u32,
+                }
+                let Params { old_pid, new_pid } = from_params(params)?;
+
+                let record = self
+                    .update(move |r| {
+                        r.get(old_pid)?;
+                        r.update_pid(old_pid, new_pid);
+                        r.get(new_pid).cloned()
+                    })
+                    .await?;
+                let record = record.ok_or_else(|| RpcError::new(NOT_FOUND, format!("未找到实例: {}", old_pid)))?;
+                metrics::counters().record_restart();
+                to_value(record)
+            }
+            "metrics" => {
+                self.reconcile().await;
+                let registry = self.registry.lock().await;
+                let stats = self.backend.sample(&registry.pids());
+                to_value(metrics::render(metrics::counters(), &registry.instances, &stats))
+            }
+            "profiles" => {
+                let store = ProfileStore::load(&self.profiles)?;
+                to_value(store.list())
+            }
+            "launch_profile" => {
+                #[derive(Deserialize)]
+                struct Params {
+                    name: String,
+                }
+                let Params { name } = from_params(params)?;
+
+                let store = ProfileStore::load(&self.profiles)?;
+                let profile = store
+                    .get(&name)
+                    .cloned()
+                    .ok_or_else(|| RpcError::new(NOT_FOUND, format!("账号配置不存在: {}", name)))?;
+                let record = self.backend.launch_profile(profile).await?;
+                let added = record.clone();
+                logged(self.update(move |r| r.add(added)).await);
+                to_value(record)
+            }
+            "shutdown" => {
+                self.shutdown.send_replace(true);
+                Ok(Value::Null)
+            }
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("未知方法: {}", method))),
+        }
+    }
+
+    /// 在文件锁内重新加载、修改并保存注册表
+    ///
+    /// 后台服务启动前或连接失败时,GUI / CLI 会直接写入注册表文件;先重新加载才不会覆盖这些实例。
+    async fn update<T, F>(&self, f: F) -> Result<T, String>
+    where
+        F: FnOnce(&mut InstanceRegistry) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        self.registry.lock().await.update_async(f).await
+    }
+
+    /// 移除已退出的实例
+    async fn reconcile(&self) -> ReconcileReport {
+        // 获取进程快照较慢,在阻塞线程池中对账,不持有注册表锁
+        let path = self.registry.lock().await.path().to_path_buf();
+        let backend = Arc::clone(&self.backend);
+        let checked = tokio::task::spawn_blocking(move || {
+            let mut registry = InstanceRegistry::load(&path)?;
+            Ok(backend.reconcile(&mut registry))
+        })
+        .await
+        .unwrap_or_else(|e| Err(format!("实例对账失败: {}", e)));
+        let report = match checked {
+            Ok(report) => report,
+            Err(e) => {
+                eprintln!("⚠ {}", e);
+                return ReconcileReport::default();
+            }
+        };
+
+        // 只移除对账时已退出的记录,对账期间新记录的实例保留
+        let removed: Vec<String> = report.removed.iter().map(|r| r.id.clone()).collect();
+        if logged(self.update(move |r| r.instances.retain(|record| !removed.contains(&record.id))).await).is_none() {
+            return ReconcileReport::default();
+        }
+
+        // 被移除的实例是自行退出的 (由本服务结束的实例已先从注册表移除)
+        for record in report.removed.iter().filter(|r| r.profile.is_some()) {
+            let (profiles, record) = (self.profiles.clone(), record.clone());
+            tokio::spawn(async move { hooks::run_for_record(&profiles, HookEvent::OnExit, &record, None).await });
+        }
+        report
+    }
+
+    /// 定期移除已退出的实例,使 on_exit 钩子和指标及时更新
+    pub async fn reconcile_every(self: Arc<Self>, interval: Duration) {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {
+                    self.reconcile().await;
+                }
+                _ = self.stopped() => break,
+            }
+        }
+    }
+
+    /// 按账号配置的定时规则启动 / 停止实例 (启动时先补执行一次)
+    pub async fn run_scheduler<C: Clock + Send>(self: Arc<Self>, mut scheduler: Scheduler<C>, interval: Duration) {
+        loop {
+            self.run_due(&mut scheduler).await;
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {}
+                _ = self.stopped() => break,
+            }
+        }
+    }
+
+    /// 执行一次到期的定时动作,返回各动作的结果
+    pub async fn run_due<C: Clock>(&self, scheduler: &mut Scheduler<C>) -> Vec<(ScheduledAction, Result<String, String>)> {
+        let store = match ProfileStore::load(&self.profiles) {
+            Ok(store) => store,
+            Err(e) => {
+                eprintln!("⚠ 读取账号配置失败,跳过定时任务: {}", e);
+                return Vec::new();
+            }
+        };
+
+        let mut results = Vec::new();
+        for due in scheduler.due(store.list()) {
+            let result = match due.action {
+                ScheduleAction::Start => self.start_scheduled(&due.profile).await,
+                ScheduleAction::Stop => self.stop_scheduled(&due.profile).await,
+            };
+            match &result {
+                Ok(message) => eprintln!("✓ 定时任务 ({}): {}", due.profile, message),
+                Err(e) => eprintln!("⚠ 定时任务 ({}) 失败: {}", due.profile, e),
+            }
+            results.push((due, result));
+        }
+        results
+    }
+
+    async fn start_scheduled(&self, name: &str) -> Result<String, String> {
+        self.reconcile().await;
+        if let Some(record) = self.registry.lock().await.instances.iter().find(|r| r.profile.as_deref() == Some(name)) {
+            return Ok(format!("已在运行 (PID {})", record.pid));
+        }
+        let record: InstanceRecord = self
+            .call("launch_profile", json!({ "name": name }))
+            .await
+            .and_then(from_params)
+            .map_err(|e| e.message)?;
+        Ok(format!("已启动 (PID {})", record.pid))
+    }
+
+    async fn stop_scheduled(&self, name: &str) -> Result<String, String> {
+        self.reconcile().await;
+        let records: Vec<InstanceRecord> = {
+            let registry = self.registry.lock().await;
+            registry.instances.iter().filter(|r| r.profile.as_deref() == Some(name)).cloned().collect()
+        };
+        if records.is_empty() {
+            return Ok("未在运行".to_string());
+        }
+
+        // 等待实例退出可能较久,不持有注册表锁
+        let mut stopped = Vec::new();
+        for record in &records {
+            hooks::run_for_record(&self.profiles, HookEvent::PreKill, record, None).await;
+            self.backend.stop(record.pid).await?;
+            stopped.push(record.pid);
+        }
+
+        let count = stopped.len();
+        logged(
+            self.update(move |r| {
+                for pid in stopped {
+                    r.remove(pid);
+                }
+            })
+            .await,
+        );
+        Ok(format!("已停止 {} 个实例", count))
+    }
+
+    /// 处理一个连接,直到对方断开
+    async fn serve_connection<S>(self: Arc<Self>, stream: S)
+    where
+        S: AsyncRead + AsyncWrite + Send,
+    {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut lines = BufReader::new(reader).lines();
+
+        while let Ok(Some(line)) = lines.next_line().await {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let Some(response) = self.handle_line(&line).await else {
+                continue;
+            };
+            let mut text = serde_json::to_string(&response).unwrap_or_default();
+            text.push('\n');
+            if writer.write_all(text.as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    }
+
+    /// 在 Unix socket 上提供服务,收到 shutdown 请求后返回
+    #[cfg(unix)]
+    pub async fn serve(self: Arc<Self>, endpoint: &Path) -> Result<(), String> {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+        use tokio::net::{UnixListener, UnixStream};
+
+        if endpoint.exists() {
+            if UnixStream::connect(endpoint).await.is_ok() {
+                return Err(format!("后台服务已在运行: {}", endpoint.display()));
+            }
+            // 上次异常退出留下的 socket 文件
+            std::fs::remove_file(endpoint).map_err(|e| format!("删除失效的 socket 失败 {}: {}", endpoint.display(), e))?;
+        }
+        if let Some(parent) = endpoint.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("创建目录失败 {}: {}", parent.display(), e))?;
+        }
+
+        let listener = UnixListener::bind(endpoint).map_err(|e| format!("监听 {} 失败: {}", endpoint.display(), e))?;
+        std::fs::set_permissions(endpoint, std::fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("设置 socket 权限失败: {}", e))?;
+        let owner = std::fs::metadata(endpoint).map(|m| m.uid()).map_err(|e| format!("读取 socket 信息失败: {}", e))?;
+        eprintln!("✓ 后台服务已启动: {}", endpoint.display());
+
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let (stream, _) = match accepted {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            eprintln!("⚠ 接受连接失败: {}", e);
+                            continue;
+                        }
+                    };
+                    // 只接受当前用户的连接
+                    match stream.peer_cred() {
+                        Ok(cred) if cred.uid() == owner => {
+                            tokio::spawn(self.clone().serve_connection(stream));
+                        }
+                        _ => eprintln!("⚠ 拒绝其他用户的连接"),
+                    }
+                }
+                _ = self.stopped() => break,
+            }
+        }
+
+        let _ = std::fs::remove_file(endpoint);
+        Ok(())
+    }
+
+    /// 在命名管道上提供服务,收到 shutdown 请求后返回
+    #[cfg(windows)]
+    pub async fn serve(self: Arc<Self>, endpoint: &Path) -> Result<(), String> {
+        use tokio::net::windows::named_pipe::ServerOptions;
+
+        let create = |first: bool| {
+            ServerOptions::new()
+                .first_pipe_instance(first)
+                .reject_remote_clients(true)
+                .create(endpoint)
+        };
+        let mut server = create(true).map_err(|e| format!("创建命名管道失败 (后台服务可能已在运行): {}", e))?;
+        eprintln!("✓ 后台服务已启动: {}", endpoint.display());
+
+        loop {
+            tokio::select! {
+                connected = server.connect() => {
+                    if let Err(e) = connected {
+                        eprintln!("⚠ 接受连接失败: {}", e);
+                        continue;
+                    }
+                    let next = create(false).map_err(|e| format!("创建命名管道失败: {}", e))?;
+                    let stream = std::mem::replace(&mut server, next);
+                    tokio::spawn(self.clone().serve_connection(stream));
+                }
+                _ = self.stopped() => break,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RpcResponse {
+    fn success(id: Value, result: Value) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    fn failure(id: Value, error: RpcError) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: None,
+            error: Some(error),
+        }
+    }
+}
+
+fn to_value<T: Serialize>(value: T) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| RpcError::new(FAILED, format!("序列化结果失败: {}", e)))
+}
+
+fn from_params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, format!("参数无效: {}", e)))
+}
+
+/// 保存注册表失败时仅记录日志 (进程已启动或结束,不因此让请求失败)
+fn logged<T>(result: Result<T, String>) -> Option<T> {
+    result.map_err(|e| eprintln!("⚠ 保存实例注册表失败: {}", e)).ok()
+}
+
+#[cfg(unix)]
+type Stream = tokio::net::UnixStream;
+#[cfg(windows)]
+type Stream = tokio::net::windows::named_pipe::NamedPipeClient;
+
+/// 后台服务客户端
+pub struct Client {
+    reader: BufReader<tokio::io::ReadHalf<Stream>>,
+    writer: tokio::io::WriteHalf<Stream>,
+    next_id: u64,
+}
+
+impl Client {
+    /// 连接后台服务 (未运行时返回错误)
+    pub async fn connect(endpoint: &Path) -> Result<Self, String> {
+        let stream = open_stream(endpoint)
+            .await
+            .map_err(|e| format!("无法连接后台服务 {}: {}", endpoint.display(), e))?;
+        let (reader, writer) = tokio::io::split(stream);
+        Ok(Self {
+            reader: BufReader::new(reader),
+            writer,
+            next_id: 1,
+        })
+    }
+
+    /// 连接默认地址的后台服务
+    pub async fn connect_default() -> Result<Self, String> {
+        Self::connect(&default_endpoint()).await
+    }
+
+    /// 调用方法
+    pub async fn call<T: DeserializeOwned>(&mut self, method: &str, params: Value) -> Result<T, String> {
+        let request = RpcRequest {
+            jsonrpc: "2.0".to_string(),
+            id: Some(json!(self.next_id)),
+            method: method.to_string(),
+            params,
+        };
+        self.next_id += 1;
+
+        let mut text = serde_json::to_string(&request).map_err(|e| format!("序列化请求失败: {}", e))?;
+        text.push('\n');
+        self.writer
+            .write_all(text.as_bytes())
+            .await
+            .map_err(|e| format!("发送请求失败: {}", e))?;
+
+        let mut line = String::new();
+        let read = self
+            .reader
+            .read_line(&mut line)
+            .await
+            .map_err(|e| format!("读取响应失败: {}", e))?;
+        if read == 0 {
+            return Err("后台服务已断开连接".to_string());
+        }
+
+        let response: RpcResponse = serde_json::from_str(&line).map_err(|e| format!("解析响应失败: {}", e))?;
+        if let Some(error) = response.error {
+            return Err(error.message);
+        }
+        serde_json::from_value(response.result.unwrap_or(Value::Null)).map_err(|e| format!("解析响应失败: {}", e))
+    }
+
+    pub async fn ping(&mut self) -> Result<DaemonInfo, String> {
+        self.call("ping", Value::Null).await
+    }
+
+    /// 实例列表 (已移除退出的实例)
+    pub async fn list(&mut self) -> Result<Vec<InstanceRecord>, String> {
+        self.call("list", Value::Null).await
+    }
+
+    pub async fn reconcile(&mut self) -> Result<ReconcileReport, String> {
+        self.call("reconcile", Value::Null).await
+    }
+
+    /// 由后台服务启动实例并记录
+    pub async fn spawn(&mut self, req: &SpawnRequest) -> Result<SpawnResponse, String> {
+        self.call("spawn", json!(req)).await
+    }
+
+    /// 记录由客户端自行启动的实例 (如 Sandboxie 实例)
+    pub async fn register(&mut self, records: &[InstanceRecord]) -> Result<usize, String> {
+        self.call("register", json!(records)).await
+    }
+
+    /// 由后台服务在 Sandboxie 沙盒中启动企业微信并记录
+    pub async fn spawn_sandboxie(&mut self, count: u8) -> Result<Vec<InstanceRecord>, String> {
+        self.call("spawn_sandboxie", json!({ "count": count })).await
+    }
+
+    /// 移除已由客户端结束 (或已退出) 的实例记录,不结束进程
+    pub async fn forget(&mut self, pids: &[u32]) -> Result<Vec<InstanceRecord>, String> {
+        self.call("forget", json!({ "pids": pids })).await
+    }
+
+    /// 结束由后台服务管理的实例
+    pub async fn kill(&mut self, pid: u32) -> Result<Option<InstanceRecord>, String> {
+        self.call("kill", json!({ "pid": pid })).await
+    }
+
+    pub async fn kill_all(&mut self) -> Result<KillAllResult, String> {
+        self.call("kill_all", Value::Null).await
+    }
+
+    /// 报告实例已被守护进程重启
+    pub async fn restarted(&mut self, old_pid: u32, new_pid: u32) -> Result<Option<InstanceRecord>, String> {
+        self.call("restarted", json!({ "old_pid": old_pid, "new_pid": new_pid })).await
+    }
+
+    /// Prometheus 文本格式的运行指标
+    pub async fn metrics(&mut self) -> Result<String, String> {
+        self.call("metrics", Value::Null).await
+    }
+
+    pub async fn profiles(&mut self) -> Result<Vec<AccountProfile>, String> {
+        self.call("profiles", Value::Null).await
+    }
+
+    /// 由后台服务启动账号配置并记录
+    pub async fn launch_profile(&mut self, name: &str) -> Result<InstanceRecord, String> {
+        self.call("launch_profile", json!({ "name": name })).await
+    }
+
+    /// 停止后台服务
+    pub async fn shutdown(&mut self) -> Result<(), String> {
+        self.call("shutdown", Value::Null).await
+    }
+}
+
+#[cfg(unix)]
+async fn open_stream(endpoint: &Path) -> std::io::Result<Stream> {
+    tokio::net::UnixStream::connect(endpoint).await
+}
+
+#[cfg(windows)]
+async fn open_stream(endpoint: &Path) -> std::io::Result<Stream> {
+    use tokio::net::windows::named_pipe::ClientOptions;
+
+    const ERROR_PIPE_BUSY: i32 = 231;
+    let mut attempts = 0;
+    loop {
+        match ClientOptions::new().open(endpoint) {
+            // 所有管道实例都在使用中,稍后重试
+            Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY) && attempts < 20 => {
+                attempts += 1;
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// 实例状态: 后台服务运行时通过 IPC 读写,否则直接读写注册表文件
+pub enum InstanceStore {
+    Daemon {
+        client: Client,
+        instances: Vec<InstanceRecord>,
+    },
+    Local(InstanceRegistry),
+}
+
+impl InstanceStore {
+    /// 连接后台服务,未运行时加载注册表 (均已移除退出的实例)
+    pub async fn open() -> Result<Self, String> {
+        if let Ok(mut client) = Client::connect_default().await {
+            let instances = client.list().await?;
+            return Ok(Self::Daemon { client, instances });
+        }
+
+        let mut registry = InstanceRegistry::new(InstanceRegistry::default_path());
+        registry.update_async(InstanceRegistry::reconcile).await?;
+        Ok(Self::Local(registry))
+    }
+
+    pub fn is_daemon(&self) -> bool {
+        matches!(self, Self::Daemon { .. })
+    }
+
+    pub fn instances(&self) -> &[InstanceRecord] {
+        match self {
+            Self::Daemon { instances, .. } => instances,
+            Self::Local(registry) => &registry.instances,
+        }
+    }
+
+    /// 启动实例并记录
+    pub async fn spawn(&mut self, req: SpawnRequest) -> Result<SpawnResponse, String> {
+        match self {
+            Self::Daemon { client, instances } => {
+                let response = client.spawn(&req).await?;
+                *instances = client.list().await?;
+                Ok(response)
+            }
+            Self::Local(registry) => {
+                let response = platform::spawn_multiple(req.clone()).await?;
+                if !req.dry_run {
+                    let records = registry::records_for_spawn(&req, &response);
+                    if let Err(e) = registry.update_async(|r| records.into_iter().for_each(|record| r.add(record))).await {
+                        eprintln!("⚠ 保存实例注册表失败: {}", e);
+                    }
+                }
+                Ok(response)
+            }
+        }
+    }
+
+    /// 记录已启动的实例
+    pub async fn add(&mut self, records: Vec<InstanceRecord>) -> Result<(), String> {
+        match self {
+            Self::Daemon { client, instances } => {
+                client.register(&records).await?;
+                instances.extend(records);
+                Ok(())
+            }
+            Self::Local(registry) => registry.update_async(|r| records.into_iter().for_each(|record| r.add(record))).await,
+        }
+    }
+
+    /// 结束已记录的实例
+    pub async fn kill(&mut self, pid: u32) -> Result<(), String> {
+        match self {
+            Self::Daemon { client, instances } => {
+                client.kill(pid).await?;
+                instances.retain(|r| r.pid != pid);
+                Ok(())
+            }
+            Self::Local(registry) => {
+                if let Some(record) = registry.get(pid) {
+                    hooks::run_for_record(&ProfileStore::default_path(), HookEvent::PreKill, record, None).await;
+                }
+                platform::kill_process(pid)?;
+                registry
+                    .update_async(move |r| {
+                        r.remove(pid);
+                    })
+                    .await
+            }
+        }
+    }
+
+    /// 移除已退出的实例
+    pub async fn reconcile(&mut self) -> Result<ReconcileReport, String> {
+        match self {
+            Self::Daemon { client, instances } => {
+                let report = client.reconcile().await?;
+                *instances = client.list().await?;
+                Ok(report)
+            }
+            Self::Local(registry) => registry.update_async(InstanceRegistry::reconcile).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AppType, InstanceConfig};
+    use std::sync::Mutex as StdMutex;
+
+    /// 记录调用、不启动真实进程的后端
+    #[derive(Default)]
+    struct FakeBackend {
+        next_pid: StdMutex<u32>,
+        killed: StdMutex<Vec<u32>>,
+    }
+
+    impl Backend for FakeBackend {
+        async fn spawn(&self, req: SpawnRequest) -> Result<SpawnResponse, String> {
+            let mut next_pid = self.next_pid.lock().unwrap();
+            let pids: Vec<u32> = (0..req.count as u32).map(|i| 1000 + *next_pid + i).collect();
+            *next_pid += req.count as u32;
+            Ok(SpawnResponse {
+                success: pids.len(),
+                failed: 0,
+                pids,
+                plan: None,
+            })
+        }
+
+        async fn launch_profile(&self, profile: AccountProfile) -> Result<InstanceRecord, String> {
+            let mut record = InstanceRecord::new(2000, profile.app_type, crate::IsolationMode::Simple);
+            record.profile = Some(profile.name);
+            Ok(record)
+        }
+
+        async fn spawn_sandboxie(&self, count: u8) -> Result<Vec<InstanceRecord>, String> {
+            Ok((1..=count)
+                .map(|id| {
+                    let mut record = InstanceRecord::new(3000 + id as u32, AppType::WeCom, crate::IsolationMode::Sandboxie);
+                    record.sandbox_name = Some(format!("WeCom_{}", id));
+                    record
+                })
+                .collect())
+        }
+
+        fn kill(&self, pid: u32) -> Result<(), String> {
+            self.killed.lock().unwrap().push(pid);
+            Ok(())
+        }
+
+        fn reconcile(&self, registry: &mut InstanceRegistry) -> ReconcileReport {
+            let killed = self.killed.lock().unwrap().clone();
+            registry.reconcile_with(|r| !killed.contains(&r.pid))
+        }
+    }
+
+    fn daemon(name: &str) -> Arc<Daemon<FakeBackend>> {
+        let dir = crate::app_dirs::temp_test_dir(name);
+        Daemon::new(FakeBackend::default(), InstanceRegistry::new(dir.join(registry::REGISTRY_FILE)))
+    }
+
+    async fn call(daemon: &Daemon<FakeBackend>, method: &str, params: Value) -> RpcResponse {
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        daemon.handle_line(&request.to_string()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_spawn_records_instances() {
+        let daemon = daemon("daemon-spawn");
+        let req = SpawnRequest {
+            count: 2,
+            app_path: Some(PathBuf::from("/Applications/企业微信.app")),
+            app_type: Some(AppType::WeChat),
+            instance_configs: Some(vec![
+                InstanceConfig {
+                    data_dir: Some(PathBuf::from("/data/1")),
+                    ..Default::default()
+                },
+                InstanceConfig::default(),
+            ]),
+            dry_run: false,
+        };
+
+        let response = call(&daemon, "spawn", json!(req)).await;
+        assert_eq!(response.result.unwrap()["pids"], json!([1000, 1001]));
+
+        let list: Vec<InstanceRecord> = serde_json::from_value(call(&daemon, "list", Value::Null).await.result.unwrap()).unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].app_type, AppType::WeChat);
+        assert_eq!(list[0].data_dir, Some(PathBuf::from("/data/1")));
+        assert_eq!(list[1].data_dir, None);
+    }
+
+    #[tokio::test]
+    async fn test_keeps_instances_written_by_local_clients() {
+        let daemon = daemon("daemon-local-writes");
+        call(&daemon, "spawn", json!({ "count": 1, "app_path": null, "app_type": null, "instance_configs": null })).await;
+
+        // 后台服务运行期间 GUI / CLI 回退为直接写入注册表文件
+        let path = daemon.registry.lock().await.path().to_path_buf();
+        let mut local = InstanceRegistry::load(&path).unwrap();
+        local.update(|r| r.add(InstanceRecord::new(4000, AppType::WeCom, crate::IsolationMode::Simple))).unwrap();
+
+        call(&daemon, "spawn", json!({ "count": 1, "app_path": null, "app_type": null, "instance_configs": null })).await;
+        assert_eq!(InstanceRegistry::load(&path).unwrap().pids(), vec![1000, 4000, 1001]);
+
+        let list: Vec<InstanceRecord> = serde_json::from_value(call(&daemon, "list", Value::Null).await.result.unwrap()).unwrap();
+        assert_eq!(list.iter().map(|r| r.pid).collect::<Vec<_>>(), vec![1000, 4000, 1001]);
+    }
+
+    #[tokio::test]
+    async fn test_kill_and_errors() {
+        let daemon = daemon("daemon-kill");
+        call(&daemon, "spawn", json!({ "count": 1, "app_path": null, "app_type": null, "instance_configs": null })).await;
+
+        let missing = call(&daemon, "kill", json!({ "pid": 42 })).await;
+        assert_eq!(missing.error.unwrap().code, NOT_FOUND);
+        let invalid = call(&daemon, "kill", json!({ "id": 1 })).await;
+        assert_eq!(invalid.error.unwrap().code, INVALID_PARAMS);
+        let unknown = call(&daemon, "restart", Value::Null).await;
+        assert_eq!(unknown.error.unwrap().code, METHOD_NOT_FOUND);
+        let garbage = daemon.handle_line("{not json").await.unwrap();
+        assert_eq!(garbage.error.unwrap().code, PARSE_ERROR);
+
+        assert!(call(&daemon, "kill", json!({ "pid": 1000 })).await.error.is_none());
+        assert_eq!(daemon.registry.lock().await.instances.len(), 0);
+
+        // 通知不返回响应
+        assert!(daemon.handle_line(r#"{"jsonrpc":"2.0","method":"ping"}"#).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sandboxie_spawn_and_forget() {
+        let daemon = daemon("daemon-forget");
+
+        let spawned = call(&daemon, "spawn_sandboxie", json!({ "count": 2 })).await;
+        let records: Vec<InstanceRecord> = serde_json::from_value(spawned.result.unwrap()).unwrap();
+        assert_eq!(records[1].sandbox_name.as_deref(), Some("WeCom_2"));
+        assert_eq!(daemon.registry.lock().await.pids(), [3001, 3002]);
+
+        // forget 只移除记录,不结束进程
+        let forgot = call(&daemon, "forget", json!({ "pids": [3001, 42] })).await;
+        let removed: Vec<InstanceRecord> = serde_json::from_value(forgot.result.unwrap()).unwrap();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(daemon.registry.lock().await.pids(), [3002]);
+        assert!(daemon.backend.killed.lock().unwrap().is_empty());
+        let saved = InstanceRegistry::load(daemon.registry.lock().await.path()).unwrap();
+        assert_eq!(saved.pids(), [3002]);
+    }
+
+    /// 可拨动的时钟 (UTC)
+    #[derive(Clone, Default)]
+    struct TestClock(Arc<std::sync::atomic::AtomicI64>);
+
+    impl TestClock {
+        fn set(&self, minute: i64) {
+            self.0.store(minute * 60, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    impl Clock for TestClock {
+        fn now(&self) -> scheduler::LocalTime {
+            scheduler::LocalTime {
+                unix: self.0.load(std::sync::atomic::Ordering::SeqCst),
+                offset: 0,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_start_and_stop() {
+        let dir = crate::app_dirs::temp_test_dir("daemon-schedule");
+        let mut profile = AccountProfile::new("客服", AppType::WeCom);
+        profile.schedule = vec![scheduler::ScheduleRule::parse_window("daily 09:00-18:00").unwrap()];
+        let mut store = ProfileStore::load(&dir.join("profiles.json")).unwrap();
+        store.create(profile).unwrap();
+        store.save().unwrap();
+        let daemon = Daemon::with_profiles(
+            FakeBackend::default(),
+            InstanceRegistry::new(dir.join(registry::REGISTRY_FILE)),
+            dir.join("profiles.json"),
+        );
+
+        let clock = TestClock::default();
+        let mut scheduler = Scheduler::new(clock.clone());
+        clock.set(10 * 60);
+        let results = daemon.run_due(&mut scheduler).await;
+        assert_eq!(results[0].0.action, ScheduleAction::Start);
+        assert_eq!(results[0].1, Ok("已启动 (PID 2000)".to_string()));
+        assert_eq!(daemon.registry.lock().await.pids(), [2000]);
+
+        // 重置后再次补执行启动时不重复启动
+        scheduler.reset();
+        assert_eq!(daemon.run_due(&mut scheduler).await[0].1, Ok("已在运行 (PID 2000)".to_string()));
+
+        clock.set(18 * 60);
+        let results = daemon.run_due(&mut scheduler).await;
+        assert_eq!(results[0].0.action, ScheduleAction::Stop);
+        assert_eq!(*daemon.backend.killed.lock().unwrap(), [2000]);
+        assert!(daemon.registry.lock().await.instances.is_empty());
+        assert!(daemon.run_due(&mut scheduler).await.is_empty());
+    }
+}
@@ -0,0 +1,445 @@
+// 进程发现 - 识别工具之外启动的实例,以便纳入管理
+// 支持 Windows / macOS / Linux
+
+use crate::AppType;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// macOS 克隆实例所在目录 (相对 HOME)
+pub const MACOS_CLONE_DIR: &str = "Applications/WeComMulti";
+
+/// macOS 实例数据目录前缀 (相对 HOME)
+pub const MACOS_DATA_DIR_PREFIX: &str = "Library/Containers/WeComInstance";
+
+/// 应用特征 - 用于从进程列表中识别目标应用
+#[derive(Debug, Clone)]
+pub struct AppProfile {
+    pub app_type: AppType,
+    /// 可执行文件名 (小写,不含 .exe 后缀)
+    pub exe_names: &'static [&'static str],
+    /// macOS 应用包名 (不含 .app 后缀)
+    pub bundle_names: &'static [&'static str],
+    /// 命令行中出现即视为匹配的片段 (小写,如 Wine 下的 Windows 路径)
+    pub cmdline_markers: &'static [&'static str],
+    /// Windows 单实例 Mutex 名称 (关闭后才能启动新实例)
+    pub mutex_name: &'static str,
+}
+
+impl AppProfile {
+    pub fn for_type(app_type: &AppType) -> Self {
+        match app_type {
+            AppType::WeCom => Self {
+                app_type: AppType::WeCom,
+                exe_names: &["wxwork", "wecom", "wework", "企业微信"],
+                bundle_names: &["企业微信", "WeCom", "WeWork"],
+                cmdline_markers: &["wxwork.exe", "wecom.exe", "wework.exe", "企业微信.exe"],
+                mutex_name: "Tencent.WeWork.Exclusive",
+            },
+            AppType::WeChat => Self {
+                app_type: AppType::WeChat,
+                exe_names: &["wechat", "weixin", "微信"],
+                bundle_names: &["WeChat", "微信"],
+                cmdline_markers: &["wechat.exe", "weixin.exe", "微信.exe"],
+                mutex_name: "_WeChat_App_Instance_Identity_Mutex_Name",
+            },
+        }
+    }
+
+    /// 判断可执行文件名是否匹配 (忽略大小写和 .exe 后缀)
+    pub fn matches_exe_name(&self, file_name: &str) -> bool {
+        let lower = file_name.to_lowercase();
+        let stem = lower.strip_suffix(".exe").unwrap_or(&lower);
+        self.exe_names.contains(&stem)
+    }
+
+    /// 识别进程,返回匹配方式
+    pub fn classify(&self, entry: &ProcessEntry) -> Option<MatchKind> {
+        // 克隆包路径优先: 能够推导出实例编号和数据目录
+        let path_text = entry
+            .exe
+            .as_ref()
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or_else(|| entry.command_line.clone());
+        if self.clone_index(&path_text).is_some() {
+            return Some(MatchKind::BundleClone);
+        }
+
+        if let Some(exe) = &entry.exe {
+            // 同时兼容 Windows 和 Unix 路径分隔符
+            let exe = exe.to_string_lossy();
+            let name = exe.rsplit(['/', '\\']).next().unwrap_or(&exe);
+            if self.matches_exe_name(name) {
+                return Some(MatchKind::ExecutableName);
+            }
+        }
+
+        let cmdline = entry.command_line.to_lowercase();
+        if self.cmdline_markers.iter().any(|m| cmdline.contains(m)) {
+            return Some(MatchKind::CommandLine);
+        }
+
+        // macOS 原版应用包: /Applications/企业微信.app/Contents/MacOS/企业微信
+        if self
+            .bundle_names
+            .iter()
+            .any(|b| path_text.contains(&format!("/{}.app/Contents/MacOS/", b)))
+        {
+            return Some(MatchKind::ExecutableName);
+        }
+
+        None
+    }
+
+    /// 从克隆包路径中解析实例编号
+    /// 例如 `~/Applications/WeComMulti/企业微信2.app/Contents/MacOS/企业微信` -> 2
+    pub fn clone_index(&self, path: &str) -> Option<u8> {
+        let rest = &path[path.find(MACOS_CLONE_DIR)? + MACOS_CLONE_DIR.len()..];
+        let bundle = rest.trim_start_matches('/').split('/').next()?;
+        let stem = bundle.strip_suffix(".app")?;
+
+        self.bundle_names.iter().find_map(|name| {
+            stem.strip_prefix(name)
+                .filter(|n| !n.is_empty())
+                .and_then(|n| n.parse::<u8>().ok())
+        })
+    }
+}
+
+/// 进程匹配方式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchKind {
+    ExecutableName, // 可执行文件名
+    BundleClone,    // 本工具创建的克隆包
+    CommandLine,    // 命令行 (Wine 等)
+}
+
+/// 进程快照条目
+#[derive(Debug, Clone, Default)]
+pub struct ProcessEntry {
+    pub pid: u32,
+    pub ppid: Option<u32>,
+    pub exe: Option<PathBuf>,
+    pub command_line: String,
+    /// 进程环境中的 HOME (仅在可读取时)
+    pub env_home: Option<PathBuf>,
+    /// 进程环境中的 WINEPREFIX (Linux)
+    pub wine_prefix: Option<PathBuf>,
+}
+
+/// 可纳入管理的已运行实例
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DiscoveredInstance {
+    pub pid: u32,
+    pub app_type: AppType,
+    pub exe_path: Option<PathBuf>,
+    pub command_line: Option<String>,
+    /// 数据目录 (能推断时)
+    pub data_dir: Option<PathBuf>,
+    /// 克隆实例编号 (macOS)
+    pub clone_id: Option<u8>,
+    pub matched_by: MatchKind,
+}
+
+/// 发现正在运行的实例
+pub fn discover(app_type: &AppType) -> Vec<DiscoveredInstance> {
+    let home = std::env::var("HOME").ok().map(PathBuf::from);
+    classify_processes(&AppProfile::for_type(app_type), &snapshot(), home.as_deref())
+}
+
+/// 从进程快照中识别实例
+///
+/// 子进程 (父进程同样匹配的) 会被过滤,只返回每个实例的主进程。
+pub fn classify_processes(
+    profile: &AppProfile,
+    entries: &[ProcessEntry],
+    own_home: Option<&Path>,
+) -> Vec<DiscoveredInstance> {
+    let matched: Vec<(&ProcessEntry, MatchKind)> = entries
+        .iter()
+        .filter_map(|e| profile.classify(e).map(|kind| (e, kind)))
+        .collect();
+
+    let matched_pids: HashSet<u32> = matched.iter().map(|(e, _)| e.pid).collect();
+
+    matched
+        .into_iter()
+        .filter(|(e, _)| !e.ppid.is_some_and(|ppid| matched_pids.contains(&ppid)))
+        .map(|(e, kind)| {
+            let path_text = e
+                .exe
+                .as_ref()
+                .map(|p| p.to_string_lossy().into_owned())
+                .unwrap_or_else(|| e.command_line.clone());
+            let clone_id = profile.clone_index(&path_text);
+
+            DiscoveredInstance {
+                pid: e.pid,
+                app_type: profile.app_type.clone(),
+                exe_path: e.exe.clone(),
+                command_line: (!e.command_line.is_empty()).then(|| e.command_line.clone()),
+                data_dir: infer_data_dir(e, clone_id, own_home),
+                clone_id,
+                matched_by: kind,
+            }
+        })
+        .collect()
+}
+
+/// 推断实例数据目录
+fn infer_data_dir(entry: &ProcessEntry, clone_id: Option<u8>, own_home: Option<&Path>) -> Option<PathBuf> {
+    // 优先级1: 进程被重定向的 HOME (本工具启动的实例都会设置)
+    if let Some(home) = &entry.env_home {
+        if own_home != Some(home.as_path()) {
+            return Some(home.clone());
+        }
+    }
+
+    // 优先级2: Wine 前缀
+    if let Some(prefix) = &entry.wine_prefix {
+        return Some(prefix.clone());
+    }
+
+    // 优先级3: 根据克隆编号推导
+    let id = clone_id?;
+    own_home.map(|home| macos_data_dir(home, id))
+}
+
+/// macOS 克隆实例的数据目录
+pub fn macos_data_dir(home: &Path, instance_id: u8) -> PathBuf {
+    home.join(format!("{}{}", MACOS_DATA_DIR_PREFIX, instance_id))
+}
+
+/// macOS 克隆应用包路径,如 `~/Applications/WeComMulti/企业微信2.app`
+pub fn macos_clone_path(home: &Path, source_app: &Path, instance_id: u8) -> PathBuf {
+    let app_name = source_app.file_stem().map(|s| s.to_string_lossy()).unwrap_or_default();
+    home.join(MACOS_CLONE_DIR).join(format!("{}{}.app", app_name, instance_id))
+}
+
+/// macOS 克隆应用的 Bundle ID
+pub fn macos_bundle_id(instance_id: u8) -> String {
+    format!("com.tencent.WeWorkMac.instance{}", instance_id)
+}
+
+/// macOS 实例的环境变量,让实例把数据写到独立目录
+pub fn macos_instance_env(instance_home: &Path) -> Vec<(String, String)> {
+    let home = instance_home.display();
+    vec![
+        ("HOME".to_string(), home.to_string()),
+        ("TMPDIR".to_string(), format!("{}/tmp", home)),
+        ("XDG_CONFIG_HOME".to_string(), format!("{}/config", home)),
+        ("XDG_DATA_HOME".to_string(), format!("{}/data", home)),
+        ("XDG_CACHE_HOME".to_string(), format!("{}/cache", home)),
+    ]
+}
+
+/// 获取当前进程快照
+#[cfg(target_os = "linux")]
+pub fn snapshot() -> Vec<ProcessEntry> {
+    use std::fs;
+
+    let Ok(dir) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+
+    dir.flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .filter_map(|pid| {
+            let base = PathBuf::from(format!("/proc/{}", pid));
+
+            // cmdline 以 \0 分隔
+            let cmdline = fs::read(base.join("cmdline")).ok()?;
+            let command_line = String::from_utf8_lossy(&cmdline)
+                .split('\0')
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            let ppid = fs::read_to_string(base.join("stat"))
+                .ok()
+                .and_then(|stat| parse_stat_ppid(&stat));
+
+            let mut entry = ProcessEntry {
+                pid,
+                ppid,
+                exe: fs::read_link(base.join("exe")).ok(),
+                command_line,
+                ..Default::default()
+            };
+
+            // 其他用户的进程无法读取环境变量,忽略即可
+            if let Ok(environ) = fs::read(base.join("environ")) {
+                for var in environ.split(|b| *b == 0) {
+                    let var = String::from_utf8_lossy(var);
+                    if let Some(v) = var.strip_prefix("HOME=") {
+                        entry.env_home = Some(PathBuf::from(v));
+                    } else if let Some(v) = var.strip_prefix("WINEPREFIX=") {
+                        entry.wine_prefix = Some(PathBuf::from(v));
+                    }
+                }
+            }
+
+            Some(entry)
+        })
+        .collect()
+}
+
+/// 解析 /proc/<pid>/stat 中的父进程 ID
+///
+/// 进程名 (第2字段) 可能包含空格和括号,因此从最后一个 ')' 之后开始解析。
+#[cfg(target_os = "linux")]
+pub(crate) fn parse_stat_ppid(stat: &str) -> Option<u32> {
+    let rest = &stat[stat.rfind(')')? + 1..];
+    rest.split_whitespace().nth(1)?.parse().ok()
+}
+
+/// 获取当前进程快照
+#[cfg(target_os = "macos")]
+pub fn snapshot() -> Vec<ProcessEntry> {
+    use std::process::Command;
+
+    let output = match Command::new("ps").args(["-axww", "-o", "pid=,ppid=,args="]).output() {
+        Ok(output) if output.status.success() => output,
+        _ => return Vec::new(),
+    };
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.trim_start().splitn(2, char::is_whitespace);
+            let pid = parts.next()?.parse().ok()?;
+            let mut rest = parts.next()?.trim_start().splitn(2, char::is_whitespace);
+            let ppid = rest.next()?.parse().ok();
+            let command_line = rest.next().unwrap_or("").trim().to_string();
+
+            Some(ProcessEntry {
+                pid,
+                ppid,
+                command_line,
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// 获取当前进程快照
+#[cfg(target_os = "windows")]
+pub fn snapshot() -> Vec<ProcessEntry> {
+    crate::platform::list_process_images()
+        .into_iter()
+        .map(|(pid, exe)| ProcessEntry {
+            pid,
+            exe: Some(exe),
+            ..Default::default()
+        })
+        .collect()
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+pub fn snapshot() -> Vec<ProcessEntry> {
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(pid: u32, ppid: u32, exe: &str, cmdline: &str) -> ProcessEntry {
+        ProcessEntry {
+            pid,
+            ppid: Some(ppid),
+            exe: (!exe.is_empty()).then(|| PathBuf::from(exe)),
+            command_line: cmdline.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_match_windows_exe_case_insensitive() {
+        let profile = AppProfile::for_type(&AppType::WeCom);
+        assert!(profile.matches_exe_name("WXWork.exe"));
+        assert!(profile.matches_exe_name("WXWORK.EXE"));
+        assert!(profile.matches_exe_name("企业微信.exe"));
+        assert!(!profile.matches_exe_name("WXWorkWeb.exe"));
+        assert!(!profile.matches_exe_name("WeChat.exe"));
+    }
+
+    #[test]
+    fn test_clone_index() {
+        let profile = AppProfile::for_type(&AppType::WeCom);
+        let path = "/Users/a/Applications/WeComMulti/企业微信3.app/Contents/MacOS/企业微信";
+        assert_eq!(profile.clone_index(path), Some(3));
+        assert_eq!(profile.clone_index("/Applications/企业微信.app/Contents/MacOS/企业微信"), None);
+        assert_eq!(profile.clone_index("/x/Applications/WeComMulti/Other2.app/Contents/MacOS/Other"), None);
+    }
+
+    #[test]
+    fn test_classify_filters_child_processes() {
+        let profile = AppProfile::for_type(&AppType::WeCom);
+        let entries = vec![
+            entry(100, 1, r"C:\Program Files (x86)\WXWork\WXWork.exe", ""),
+            entry(101, 100, r"C:\Program Files (x86)\WXWork\WXWork.exe", ""),
+            entry(200, 1, r"C:\Program Files\Tencent\WeChat\WeChat.exe", ""),
+        ];
+
+        let found = classify_processes(&profile, &entries, None);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].pid, 100);
+        assert_eq!(found[0].matched_by, MatchKind::ExecutableName);
+    }
+
+    #[test]
+    fn test_classify_bundle_clone_infers_data_dir() {
+        let profile = AppProfile::for_type(&AppType::WeCom);
+        let home = PathBuf::from("/Users/a");
+        let entries = vec![entry(
+            300,
+            1,
+            "",
+            "/Users/a/Applications/WeComMulti/企业微信2.app/Contents/MacOS/企业微信",
+        )];
+
+        let found = classify_processes(&profile, &entries, Some(&home));
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].matched_by, MatchKind::BundleClone);
+        assert_eq!(found[0].clone_id, Some(2));
+        assert_eq!(
+            found[0].data_dir,
+            Some(PathBuf::from("/Users/a/Library/Containers/WeComInstance2"))
+        );
+    }
+
+    #[test]
+    fn test_classify_wine_command_line() {
+        let profile = AppProfile::for_type(&AppType::WeCom);
+        let mut wine = entry(400, 1, "/usr/bin/wine64-preloader", r"C:\Program Files\WXWork\WXWork.exe");
+        wine.wine_prefix = Some(PathBuf::from("/home/a/.wine-wecom"));
+        wine.env_home = Some(PathBuf::from("/home/a"));
+
+        let found = classify_processes(&profile, &[wine], Some(Path::new("/home/a")));
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].matched_by, MatchKind::CommandLine);
+        assert_eq!(found[0].data_dir, Some(PathBuf::from("/home/a/.wine-wecom")));
+    }
+
+    #[test]
+    fn test_redirected_home_is_data_dir() {
+        let profile = AppProfile::for_type(&AppType::WeCom);
+        let mut e = entry(500, 1, "/Applications/企业微信.app/Contents/MacOS/企业微信", "");
+        e.env_home = Some(PathBuf::from("/Users/a/Library/Containers/WeComInstance1"));
+
+        let found = classify_processes(&profile, &[e], Some(Path::new("/Users/a")));
+        assert_eq!(
+            found[0].data_dir,
+            Some(PathBuf::from("/Users/a/Library/Containers/WeComInstance1"))
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_stat_ppid() {
+        assert_eq!(parse_stat_ppid("42 (a b) c) S 7 42 42 0"), Some(7));
+        assert_eq!(parse_stat_ppid("garbage"), None);
+    }
+}
@@ -0,0 +1,402 @@
+// 环境自检 - 检查应用安装、Sandboxie、权限和数据目录
+//
+// 每项检查给出 通过 / 警告 / 失败 和修复建议,CLI `doctor` 和 GUI 共用同一份报告。
+
+use crate::config::Config;
+use crate::path_resolver::PathResolver;
+use crate::profiles::ProfileStore;
+use crate::{app_dirs, AppType, IsolationMode};
+use serde::Serialize;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+/// 检查结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// 单项检查
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Check {
+    /// 标识 (如 "app.wecom"),便于脚本判断
+    pub id: String,
+    pub title: String,
+    pub status: Status,
+    pub detail: String,
+    /// 修复建议
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn new(id: &str, title: &str, status: Status, detail: impl Into<String>) -> Self {
+        Self {
+            id: id.to_string(),
+            title: title.to_string(),
+            status,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn hint(mut self, hint: &str) -> Self {
+        self.hint = Some(hint.to_string());
+        self
+    }
+}
+
+/// 自检报告
+#[derive(Debug, Clone, Serialize)]
+pub struct DoctorReport {
+    pub version: String,
+    pub platform: String,
+    pub status: Status,
+    pub checks: Vec<Check>,
+}
+
+impl DoctorReport {
+    pub fn new(checks: Vec<Check>) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            platform: std::env::consts::OS.to_string(),
+            status: checks.iter().map(|c| c.status).max().unwrap_or(Status::Pass),
+            checks,
+        }
+    }
+
+    /// 文本格式
+    pub fn render(&self) -> String {
+        let mut out = format!("wecom-multi-open {} ({})\n", self.version, self.platform);
+        for check in &self.checks {
+            let mark = match check.status {
+                Status::Pass => "✓",
+                Status::Warn => "⚠",
+                Status::Fail => "✗",
+            };
+            out.push_str(&format!("{} {}: {}\n", mark, check.title, check.detail));
+            if let Some(hint) = &check.hint {
+                out.push_str(&format!("    → {}\n", hint));
+            }
+        }
+
+        let (warn, fail) = self.checks.iter().fold((0, 0), |(w, f), c| match c.status {
+            Status::Pass => (w, f),
+            Status::Warn => (w + 1, f),
+            Status::Fail => (w, f + 1),
+        });
+        out.push_str(&format!("\n共 {} 项检查, {} 项警告, {} 项失败", self.checks.len(), warn, fail));
+        out
+    }
+}
+
+/// 执行全部检查
+pub fn run(config: &Config) -> DoctorReport {
+    let mut checks = Vec::new();
+
+    // 诊断不应有副作用: 不写路径缓存,不创建目录
+    let mut resolver = PathResolver::standard(config).read_only();
+    for app_type in [AppType::WeCom, AppType::WeChat] {
+        checks.push(check_app(&mut resolver, &app_type, app_type == config.app));
+    }
+
+    checks.extend(platform_checks(config));
+
+    let mut dirs = vec![
+        ("配置目录", app_dirs::config_dir()),
+        ("数据目录", app_dirs::data_dir()),
+        ("缓存目录", app_dirs::cache_dir()),
+    ];
+    if let Ok(store) = ProfileStore::load_default() {
+        dirs.extend(store.list().iter().map(|p| ("账号数据目录", p.resolved_data_dir())));
+    }
+    checks.extend(dirs.iter().map(|(title, dir)| check_writable(title, dir)));
+
+    DoctorReport::new(checks)
+}
+
+fn check_app(resolver: &mut PathResolver, app_type: &AppType, required: bool) -> Check {
+    let (id, title) = match app_type {
+        AppType::WeCom => ("app.wecom", "企业微信"),
+        AppType::WeChat => ("app.wechat", "微信"),
+    };
+
+    match resolver.resolve(app_type) {
+        Some(resolution) => {
+            let version = crate::path_resolver::app_version(&resolution.path)
+                .map(|v| format!(" (版本 {})", v))
+                .unwrap_or_default();
+            Check::new(
+                id,
+                title,
+                Status::Pass,
+                format!("{}{}", resolution.path.display(), version),
+            )
+        }
+        None => Check::new(
+            id,
+            title,
+            if required { Status::Fail } else { Status::Warn },
+            "未找到",
+        )
+        .hint("安装后重试,或在配置文件的 app_paths 中指定路径;运行 doctor paths 查看检测详情"),
+    }
+}
+
+/// 检查目录可写 (不创建也不写入;不存在时检查最近的已存在上级目录能否创建它)
+pub fn check_writable(title: &str, dir: &Path) -> Check {
+    let result = match dir.ancestors().find(|p| p.exists()) {
+        None => Err("找不到已存在的上级目录".to_string()),
+        Some(existing) if !existing.is_dir() => Err(format!("{} 不是目录", existing.display())),
+        Some(existing) if !is_writable(existing) => Err(format!("{} 没有写入权限", existing.display())),
+        Some(existing) => Ok(existing == dir),
+    };
+
+    match result {
+        Ok(true) => Check::new("dir.writable", title, Status::Pass, dir.display().to_string()),
+        Ok(false) => Check::new("dir.writable", title, Status::Pass, format!("{} (尚未创建)", dir.display())),
+        Err(e) => Check::new(
+            "dir.writable",
+            title,
+            Status::Fail,
+            format!("{} 不可写: {}", dir.display(), e),
+        )
+        .hint("检查目录权限,或通过 WECOM_MULTI_HOME 环境变量改用其他目录"),
+    }
+}
+
+#[cfg(unix)]
+fn is_writable(path: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    let Ok(path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // SAFETY: path 是以 NUL 结尾的有效字符串
+    unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 }
+}
+
+#[cfg(not(unix))]
+fn is_writable(path: &Path) -> bool {
+    std::fs::metadata(path).map(|m| !m.permissions().readonly()).unwrap_or(false)
+}
+
+/// 在 PATH 中查找程序
+pub fn find_in_path(name: &str, path_var: Option<&OsStr>) -> Option<PathBuf> {
+    std::env::split_paths(path_var?)
+        .map(|dir| dir.join(name))
+        .find(|p| p.is_file())
+}
+
+#[cfg_attr(not(any(target_os = "macos", target_os = "linux")), allow(dead_code))]
+fn check_program(id: &str, name: &str, fallback: Option<&str>, missing: Status, hint: &str) -> Check {
+    let found = find_in_path(name, std::env::var_os("PATH").as_deref())
+        .or_else(|| fallback.map(PathBuf::from).filter(|p| p.is_file()));
+
+    match found {
+        Some(path) => Check::new(id, name, Status::Pass, path.display().to_string()),
+        None => Check::new(id, name, missing, "未找到").hint(hint),
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn platform_checks(config: &Config) -> Vec<Check> {
+    use crate::windows_sandbox::SANDBOXIE_DIRS;
+
+    let mut checks = Vec::new();
+
+    // Sandboxie-Plus: SbieIni.exe 用于管理沙盒,Start.exe 用于在沙盒中启动程序
+    let required = config.isolation == IsolationMode::Sandboxie;
+    let dir = SANDBOXIE_DIRS.iter().map(PathBuf::from).find(|d| d.join("SbieIni.exe").exists());
+    checks.push(match dir {
+        Some(dir) if dir.join("Start.exe").exists() => {
+            Check::new("sandboxie", "Sandboxie-Plus", Status::Pass, dir.display().to_string())
+        }
+        Some(dir) => Check::new(
+            "sandboxie",
+            "Sandboxie-Plus",
+            Status::Fail,
+            format!("{} 中缺少 Start.exe", dir.display()),
+        )
+        .hint("重新安装 Sandboxie-Plus"),
+        None => Check::new(
+            "sandboxie",
+            "Sandboxie-Plus",
+            if required { Status::Fail } else { Status::Warn },
+            "未安装 (沙盒隔离模式不可用)",
+        )
+        .hint("从 https://github.com/sandboxie-plus/Sandboxie/releases 下载安装"),
+    });
+
+    checks.push(match is_elevated() {
+        Some(true) => Check::new("elevation", "管理员权限", Status::Pass, "已以管理员身份运行"),
+        Some(false) => Check::new("elevation", "管理员权限", Status::Warn, "未以管理员身份运行")
+            .hint("关闭已运行实例的 Mutex 失败时,右键选择 \"以管理员身份运行\""),
+        None => Check::new("elevation", "管理员权限", Status::Warn, "无法读取进程权限"),
+    });
+
+    checks
+}
+
+/// 当前进程是否已提升权限
+#[cfg(target_os = "windows")]
+fn is_elevated() -> Option<bool> {
+    use windows::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows::Win32::Security::{GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY};
+    use windows::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+    unsafe {
+        let mut token = HANDLE::default();
+        OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token).ok()?;
+
+        let mut elevation = TOKEN_ELEVATION::default();
+        let mut len = 0u32;
+        let result = GetTokenInformation(
+            token,
+            TokenElevation,
+            Some(&mut elevation as *mut TOKEN_ELEVATION as *mut std::ffi::c_void),
+            std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut len,
+        );
+        let _ = CloseHandle(token);
+
+        result.ok()?;
+        Some(elevation.TokenIsElevated != 0)
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn platform_checks(config: &Config) -> Vec<Check> {
+    let mut checks = vec![
+        check_program(
+            "tool.codesign",
+            "codesign",
+            Some("/usr/bin/codesign"),
+            Status::Fail,
+            "运行 xcode-select --install 安装命令行工具 (克隆的应用需要重新签名)",
+        ),
+        check_program(
+            "tool.xattr",
+            "xattr",
+            Some("/usr/bin/xattr"),
+            Status::Warn,
+            "无法清除隔离属性,首次打开克隆的应用时可能被系统拦截",
+        ),
+        check_program(
+            "tool.plistbuddy",
+            "PlistBuddy",
+            Some("/usr/libexec/PlistBuddy"),
+            Status::Fail,
+            "系统文件缺失,无法修改克隆应用的 Bundle ID",
+        ),
+        check_root(),
+    ];
+    checks.extend(isolation_unsupported(config));
+    checks
+}
+
+#[cfg(target_os = "linux")]
+fn platform_checks(config: &Config) -> Vec<Check> {
+    let mut checks = vec![
+        check_program(
+            "tool.wine",
+            "wine",
+            None,
+            Status::Warn,
+            "Linux 上需要通过 Wine 运行企业微信/微信的 Windows 版本",
+        ),
+        check_program(
+            "tool.bwrap",
+            "bwrap",
+            None,
+            Status::Warn,
+            "安装 bubblewrap 后可为实例隔离数据目录",
+        ),
+        check_root(),
+    ];
+    checks.extend(isolation_unsupported(config));
+    checks
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+fn platform_checks(config: &Config) -> Vec<Check> {
+    isolation_unsupported(config).into_iter().collect()
+}
+
+/// 以 root 运行时克隆的应用和数据目录归 root 所有,普通用户之后无法使用
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn check_root() -> Check {
+    // SAFETY: geteuid 总是成功且没有副作用
+    match unsafe { libc::geteuid() } {
+        0 => Check::new("elevation", "运行用户", Status::Warn, "正在以 root 运行")
+            .hint("使用普通用户运行,避免创建的文件归 root 所有"),
+        uid => Check::new("elevation", "运行用户", Status::Pass, format!("uid {}", uid)),
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn isolation_unsupported(config: &Config) -> Option<Check> {
+    (config.isolation == IsolationMode::Sandboxie).then(|| {
+        Check::new("sandboxie", "Sandboxie-Plus", Status::Fail, "沙盒隔离仅支持 Windows")
+            .hint("将配置中的 isolation 改为 simple")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_overall_status_is_worst() {
+        let report = DoctorReport::new(vec![
+            Check::new("a", "A", Status::Pass, ""),
+            Check::new("b", "B", Status::Warn, ""),
+        ]);
+        assert_eq!(report.status, Status::Warn);
+        assert!(report.render().contains("1 项警告, 0 项失败"));
+
+        assert_eq!(DoctorReport::new(Vec::new()).status, Status::Pass);
+        let report = DoctorReport::new(vec![
+            Check::new("a", "A", Status::Fail, "x").hint("修复"),
+            Check::new("b", "B", Status::Warn, ""),
+        ]);
+        assert_eq!(report.status, Status::Fail);
+        assert!(report.render().contains("✗ A: x\n    → 修复"));
+    }
+
+    #[test]
+    fn test_check_writable() {
+        let dir = app_dirs::temp_test_dir("doctor-writable");
+        let check = check_writable("数据目录", &dir);
+        assert_eq!(check.status, Status::Pass);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+        // 不存在的目录只检查上级,不创建
+        let check = check_writable("数据目录", &dir.join("new/sub"));
+        assert_eq!(check.status, Status::Pass);
+        assert!(check.detail.contains("尚未创建"));
+        assert!(!dir.join("new").exists());
+
+        // 路径的上级是文件,无法创建
+        fs::write(dir.join("file"), "").unwrap();
+        let check = check_writable("数据目录", &dir.join("file/sub"));
+        assert_eq!(check.status, Status::Fail);
+        assert!(check.hint.is_some());
+    }
+
+    #[test]
+    fn test_find_in_path() {
+        let dir = app_dirs::temp_test_dir("doctor-path");
+        let bin = dir.join("bin");
+        fs::create_dir_all(&bin).unwrap();
+        fs::write(bin.join("wine"), "").unwrap();
+
+        let path_var = std::env::join_paths([dir.join("missing"), bin.clone()]).unwrap();
+        assert_eq!(find_in_path("wine", Some(&path_var)), Some(bin.join("wine")));
+        assert_eq!(find_in_path("bwrap", Some(&path_var)), None);
+        assert_eq!(find_in_path("wine", None), None);
+    }
+}
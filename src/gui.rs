@@ -5,19 +5,24 @@
 
 use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
-use tauri::{Manager, WindowEvent};
-use wecom_multi_open::{platform, SpawnRequest, AppType};
+use tauri::{Emitter, Manager, WindowEvent};
+use wecom_multi_open::{discovery, platform, InstanceConfig, IsolationMode, SpawnRequest, AppType};
+use wecom_multi_open::config::{self, Config};
+use wecom_multi_open::config_watch::{self, ConfigEvent, ConfigReloader};
+use wecom_multi_open::daemon;
+use wecom_multi_open::doctor;
+use wecom_multi_open::hooks::{self, HookEvent};
+use wecom_multi_open::path_resolver;
+use wecom_multi_open::plan::SpawnPlan;
+use wecom_multi_open::monitor::{InstanceStats, ResourceMonitor};
+use wecom_multi_open::profiles::{self, AccountProfile, ProfileStore};
+use wecom_multi_open::registry::{InstanceRecord, InstanceRegistry};
+use wecom_multi_open::single_instance::{self, Activation, LockFile};
+use wecom_multi_open::scheduler::{self, Clock, NextFire, ScheduleAction, ScheduledAction, Scheduler, SystemClock};
+use wecom_multi_open::watchdog::{WatchedInstance, Watchdog, WatchdogEvent, WatchdogPolicy};
 
 #[cfg(target_os = "windows")]
-use wecom_multi_open::wecom_manager::{WeComManager, WeComInstance};
-
-/// 隔离模式
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
-enum IsolationMode {
-    Simple,    // 简单模式 (无隔离)
-    Sandboxie, // Sandboxie沙盒模式 (Windows)
-}
+use wecom_multi_open::wecom_manager::{self, WeComManager};
 
 /// 应用状态
 #[derive(Clone)]
@@ -26,22 +31,139 @@ struct AppState {
     pids: Arc<Mutex<Vec<u32>>>,
     /// 退出时是否保留实例 (true = 保留,false = 关闭)
     keep_on_exit: Arc<Mutex<bool>>,
-    /// Sandboxie 实例列表 (仅Windows)
-    #[cfg(target_os = "windows")]
-    sandboxie_instances: Arc<Mutex<Vec<WeComInstance>>>,
+    /// 资源监控器 (保存上次采样用于计算 CPU 占用)
+    monitor: Arc<Mutex<ResourceMonitor>>,
+    /// 实例守护 (默认关闭)
+    watchdog: Watchdog,
+    /// 持久化的实例注册表
+    registry: Arc<Mutex<InstanceRegistry>>,
 }
 
 impl Default for AppState {
     fn default() -> Self {
         Self {
             pids: Arc::new(Mutex::new(Vec::new())),
-            keep_on_exit: Arc::new(Mutex::new(true)), // 默认保留实例
-            #[cfg(target_os = "windows")]
-            sandboxie_instances: Arc::new(Mutex::new(Vec::new())),
+            keep_on_exit: Arc::new(Mutex::new(config::current().keep_on_exit)), // 默认保留实例
+            monitor: Arc::new(Mutex::new(ResourceMonitor::new())),
+            watchdog: Watchdog::new(WatchdogPolicy::default()),
+            registry: Arc::new(Mutex::new(InstanceRegistry::default())),
+        }
+    }
+}
+
+impl AppState {
+    /// 从实例注册表恢复上次运行时管理的实例
+    fn restore() -> Self {
+        let state = Self::default();
+
+        // 后台服务运行时以其实例列表为准 (由后台服务对账并写入注册表文件)
+        let from_daemon = tauri::async_runtime::block_on(async { daemon_client().await?.list().await.ok() });
+
+        let mut registry = InstanceRegistry::new(InstanceRegistry::default_path());
+        match from_daemon {
+            Some(instances) => registry.instances = instances,
+            None => match registry.update(InstanceRegistry::reconcile) {
+                Ok(report) if !report.removed.is_empty() => {
+                    println!("实例注册表: 移除 {} 个已退出的实例", report.removed.len());
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("⚠ 加载实例注册表失败: {}", e),
+            },
+        }
+        println!("实例注册表: 恢复 {} 个运行中的实例", registry.instances.len());
+
+        // 交给守护进程监视 (Sandboxie 实例不支持自动重启)
+        for record in registry.instances.iter().filter(|r| r.backend == IsolationMode::Simple) {
+            state.watchdog.watch(WatchedInstance {
+                pid: record.pid,
+                app_type: record.app_type.clone(),
+                app_path: record.exe_path.clone(),
+                config: InstanceConfig {
+                    data_dir: record.data_dir.clone(),
+                    ..Default::default()
+                },
+            });
+        }
+
+        *state.pids.lock().unwrap() = registry.pids();
+        *state.registry.lock().unwrap() = registry;
+        state
+    }
+}
+
+/// 重新加载、修改并保存实例注册表,不覆盖 CLI 同时写入的实例 (失败时仅记录日志)
+async fn update_registry<T, F>(registry: &Mutex<InstanceRegistry>, f: F) -> Option<T>
+where
+    F: FnOnce(&mut InstanceRegistry) -> T + Send + 'static,
+    T: Send + 'static,
+{
+    // 等待文件锁期间不持有内存中注册表的锁
+    let mut updated = InstanceRegistry::new(registry.lock().unwrap().path().to_path_buf());
+    match updated.update_async(f).await {
+        Ok(result) => {
+            *registry.lock().unwrap() = updated;
+            Some(result)
+        }
+        Err(e) => {
+            eprintln!("⚠ 保存实例注册表失败: {}", e);
+            None
         }
     }
 }
 
+/// 后台服务运行时返回其客户端
+async fn daemon_client() -> Option<daemon::Client> {
+    daemon::Client::connect_default().await.ok()
+}
+
+/// 实例注册表的修改
+enum RegistryChange {
+    /// 记录由 GUI 启动或纳入管理的实例
+    Add(Vec<InstanceRecord>),
+    /// 移除已结束的实例 (不结束进程)
+    Forget(Vec<u32>),
+}
+
+impl RegistryChange {
+    fn apply(&self, registry: &mut InstanceRegistry) {
+        match self {
+            Self::Add(records) => records.iter().for_each(|record| registry.add(record.clone())),
+            Self::Forget(pids) => {
+                for &pid in pids {
+                    registry.remove(pid);
+                }
+            }
+        }
+    }
+}
+
+/// 记录实例变化
+///
+/// 后台服务运行时由其持有并写入注册表文件,通过 IPC 交给后台服务 (CLI 可见);
+/// 否则在文件锁内直接修改注册表。GUI 内存中的注册表同步更新。
+async fn record_change(registry: &Mutex<InstanceRegistry>, change: RegistryChange) {
+    let Some(mut client) = daemon_client().await else {
+        update_registry(registry, move |r| change.apply(r)).await;
+        return;
+    };
+
+    let result = match &change {
+        RegistryChange::Add(records) => client.register(records).await.map(|_| ()),
+        RegistryChange::Forget(pids) => client.forget(pids).await.map(|_| ()),
+    };
+    if let Err(e) = result {
+        eprintln!("⚠ 同步实例到后台服务失败: {}", e);
+    }
+    change.apply(&mut registry.lock().unwrap());
+}
+
+/// 运行 GUI 记录的实例的钩子 (未使用账号配置的实例没有钩子)
+async fn run_hook(state: &AppState, event: HookEvent, pid: u32) {
+    let record = state.registry.lock().unwrap().get(pid).cloned();
+    if let Some(record) = record {
+        hooks::run_for_record(&ProfileStore::default_path(), event, &record, None).await;
+    }
+}
 
 /// GUI 响应
 #[derive(Debug, Serialize, Deserialize)]
@@ -59,10 +181,16 @@ async fn spawn_instances(
     isolation_mode: Option<String>,
     state: tauri::State<'_, AppState>,
 ) -> Result<GuiResponse, String> {
-    // 解析应用类型
+    let config = config::current();
+    if count > config.spawn.max_count {
+        return Err(format!("单次最多启动 {} 个实例", config.spawn.max_count));
+    }
+
+    // 解析应用类型 (未指定时使用配置中的默认值)
     let app_type_enum = match app_type.as_deref() {
         Some("wechat") | Some("WeChat") => AppType::WeChat,
-        _ => AppType::WeCom, // 默认企业微信
+        Some(_) => AppType::WeCom,
+        None => config.app.clone(),
     };
 
     let app_name = match app_type_enum {
@@ -73,87 +201,121 @@ async fn spawn_instances(
     // 解析隔离模式
     let isolation = match isolation_mode.as_deref() {
         Some("sandboxie") => IsolationMode::Sandboxie,
-        _ => IsolationMode::Simple,
+        Some(_) => IsolationMode::Simple,
+        None => config.isolation.clone(),
     };
 
     println!("收到启动请求: {} {} 个实例 (模式: {:?})", app_name, count, isolation);
 
-    // Windows + Sandboxie 模式
+    // Windows + Sandboxie 模式 (后台服务运行时由其启动并记录实例)
     #[cfg(target_os = "windows")]
     if matches!(isolation, IsolationMode::Sandboxie) {
-        match WeComManager::new() {
-            Ok(manager) => {
-                match manager.spawn_multiple(count) {
-                    Ok(instances) => {
-                        // 保存Sandboxie实例信息
-                        let mut sb_instances = state.sandboxie_instances.lock().unwrap();
-                        let mut pids = state.pids.lock().unwrap();
-
-                        for instance in &instances {
-                            if let Some(pid) = instance.pid {
-                                pids.push(pid);
-                            }
-                        }
-
-                        sb_instances.extend(instances.clone());
-
-                        return Ok(GuiResponse {
-                            success: true,
-                            message: format!("✅ Sandboxie模式: 成功启动 {} 个隔离实例!", instances.len()),
-                            pids: pids.clone(),
-                        });
-                    }
-                    Err(e) => {
-                        return Ok(GuiResponse {
-                            success: false,
-                            message: format!("Sandboxie启动失败: {}. 请检查是否已安装Sandboxie-Plus", e),
-                            pids: vec![],
-                        });
-                    }
+        let spawned = match daemon_client().await {
+            Some(mut client) => client.spawn_sandboxie(count).await.inspect(|records| {
+                RegistryChange::Add(records.clone()).apply(&mut state.registry.lock().unwrap());
+            }),
+            None => {
+                let spawned = wecom_manager::spawn_sandboxie(count).await;
+                if let Ok(records) = &spawned {
+                    let change = RegistryChange::Add(records.clone());
+                    update_registry(&state.registry, move |r| change.apply(r)).await;
                 }
+                spawned
             }
-            Err(e) => {
-                return Ok(GuiResponse {
-                    success: false,
-                    message: format!("无法初始化Sandboxie: {}. 请先安装Sandboxie-Plus: https://github.com/sandboxie-plus/Sandboxie/releases", e),
-                    pids: vec![],
-                });
+        };
+
+        return Ok(match spawned {
+            Ok(records) => {
+                let mut pids = state.pids.lock().unwrap();
+                pids.extend(records.iter().map(|record| record.pid));
+                GuiResponse {
+                    success: true,
+                    message: format!("✅ Sandboxie模式: 成功启动 {} 个隔离实例!", records.len()),
+                    pids: pids.clone(),
+                }
             }
-        }
+            Err(e) => GuiResponse {
+                success: false,
+                message: format!("Sandboxie启动失败: {}. 请先安装Sandboxie-Plus: https://github.com/sandboxie-plus/Sandboxie/releases", e),
+                pids: vec![],
+            },
+        });
     }
 
     // 简单模式 (所有平台)
     // 检测已存在的进程
-    #[cfg(target_os = "windows")]
     let existing_pids = platform::find_processes_by_type(app_type_enum.clone());
-    #[cfg(not(target_os = "windows"))]
-    let existing_pids = Vec::new();
 
     if !existing_pids.is_empty() {
         println!("⚠ 检测到 {} 个已运行的{}实例: {:?}", existing_pids.len(), app_name, existing_pids);
 
         // 将已存在的进程添加到管理列表
-        let mut pids = state.pids.lock().unwrap();
-        for &pid in &existing_pids {
-            if !pids.contains(&pid) {
-                pids.push(pid);
-            }
-        }
+        let new_pids: Vec<u32> = {
+            let mut pids = state.pids.lock().unwrap();
+            let new_pids: Vec<u32> = existing_pids.iter().copied().filter(|pid| !pids.contains(pid)).collect();
+            pids.extend_from_slice(&new_pids);
+            new_pids
+        };
+        let records = new_pids
+            .iter()
+            .map(|&pid| InstanceRecord::new(pid, app_type_enum.clone(), IsolationMode::Simple))
+            .collect();
+        record_change(&state.registry, RegistryChange::Add(records)).await;
     }
 
     let req = SpawnRequest {
         count,
         app_path: None,
-        app_type: Some(app_type_enum),
+        app_type: Some(app_type_enum.clone()),
         instance_configs: None,
+        dry_run: false,
     };
 
+    // 后台服务运行时由其启动并记录实例
+    if let Some(mut client) = daemon_client().await {
+        return Ok(match client.spawn(&req).await {
+            Ok(response) => {
+                let mut pids = state.pids.lock().unwrap();
+                pids.extend_from_slice(&response.pids);
+                GuiResponse {
+                    success: true,
+                    message: format!("✅ 后台服务: 成功启动 {} 个新实例! 当前共 {} 个实例运行", response.success, pids.len()),
+                    pids: pids.clone(),
+                }
+            }
+            Err(e) => GuiResponse {
+                success: false,
+                message: format!("启动失败: {}", e),
+                pids: vec![],
+            },
+        });
+    }
+
     match platform::spawn_multiple(req).await {
         Ok(response) => {
-            // 保存新启动的 PID 到状态
+            // 交给守护进程监视 (Sandboxie 实例不支持自动重启)
+            for (i, &pid) in response.pids.iter().enumerate() {
+                state.watchdog.watch(WatchedInstance {
+                    pid,
+                    app_type: app_type_enum.clone(),
+                    app_path: None,
+                    config: InstanceConfig {
+                        instance_id: Some(i as u8 + 1),
+                        ..Default::default()
+                    },
+                });
+            }
+
+            // 保存新启动的 PID 到注册表和状态
+            let records: Vec<InstanceRecord> = response
+                .pids
+                .iter()
+                .map(|&pid| InstanceRecord::new(pid, app_type_enum.clone(), IsolationMode::Simple))
+                .collect();
+            update_registry(&state.registry, move |r| RegistryChange::Add(records).apply(r)).await;
+
             let mut pids = state.pids.lock().unwrap();
             pids.extend_from_slice(&response.pids);
-
             let total_instances = pids.len();
 
             let mode_desc = if matches!(isolation, IsolationMode::Sandboxie) {
@@ -182,12 +344,37 @@ async fn kill_instance(
     pid: u32,
     state: tauri::State<'_, AppState>,
 ) -> Result<GuiResponse, String> {
+    // 用户主动关闭,守护进程不应重启
+    state.watchdog.expect_exit(pid);
+
+    // 由后台服务管理的实例交给后台服务结束
+    if let Some(mut client) = daemon_client().await {
+        if client.list().await?.iter().any(|r| r.pid == pid) {
+            return Ok(match client.kill(pid).await {
+                Ok(_) => {
+                    forget_instance(&state, pid).await;
+                    GuiResponse {
+                        success: true,
+                        message: format!("已关闭实例 {}", pid),
+                        pids: state.pids.lock().unwrap().clone(),
+                    }
+                }
+                Err(e) => GuiResponse {
+                    success: false,
+                    message: format!("关闭实例失败: {}", e),
+                    pids: vec![],
+                },
+            });
+        }
+    }
+
     if platform::process_exists(pid) {
+        run_hook(&state, HookEvent::PreKill, pid).await;
         match platform::kill_process(pid) {
             Ok(_) => {
                 // 从状态中移除此 PID
-                let mut pids = state.pids.lock().unwrap();
-                pids.retain(|&p| p != pid);
+                forget_instance(&state, pid).await;
+                let pids = state.pids.lock().unwrap();
 
                 Ok(GuiResponse {
                     success: true,
@@ -203,8 +390,8 @@ async fn kill_instance(
         }
     } else {
         // 进程不存在,从列表中移除
-        let mut pids = state.pids.lock().unwrap();
-        pids.retain(|&p| p != pid);
+        forget_instance(&state, pid).await;
+        let pids = state.pids.lock().unwrap();
 
         Ok(GuiResponse {
             success: true,
@@ -214,24 +401,53 @@ async fn kill_instance(
     }
 }
 
+/// 从 PID 列表、注册表和守护列表中移除实例
+async fn forget_instance(state: &AppState, pid: u32) {
+    state.watchdog.unwatch(pid);
+    state.pids.lock().unwrap().retain(|&p| p != pid);
+
+    record_change(&state.registry, RegistryChange::Forget(vec![pid])).await;
+}
+
 /// Tauri 命令: 关闭所有实例
 #[tauri::command]
 async fn kill_all_instances(state: tauri::State<'_, AppState>) -> Result<GuiResponse, String> {
-    let pids = state.pids.lock().unwrap();
+    // 先结束后台服务管理的实例
+    let daemon_result = match daemon_client().await {
+        Some(mut client) => Some(client.kill_all().await?),
+        None => None,
+    };
+
+    let local: Vec<u32> = state
+        .pids
+        .lock()
+        .unwrap()
+        .iter()
+        .copied()
+        .filter(|pid| !daemon_result.as_ref().is_some_and(|r| r.killed.contains(pid)))
+        .collect();
+    for pid in local {
+        run_hook(&state, HookEvent::PreKill, pid).await;
+    }
+
+    // 清空 PID 列表
+    let pids = std::mem::take(&mut *state.pids.lock().unwrap());
     let count = pids.len();
 
     let mut killed = 0;
     for &pid in pids.iter() {
-        if platform::process_exists(pid) {
+        state.watchdog.expect_exit(pid);
+        if daemon_result.as_ref().is_some_and(|r| r.killed.contains(&pid)) {
+            killed += 1;
+        } else if platform::process_exists(pid) {
             if let Ok(_) = platform::kill_process(pid) {
                 killed += 1;
             }
         }
     }
 
-    // 清空 PID 列表
-    drop(pids);
-    state.pids.lock().unwrap().clear();
+    // 注册表中只移除本 GUI 管理的实例 (保留 CLI 同时启动的实例)
+    record_change(&state.registry, RegistryChange::Forget(pids)).await;
 
     Ok(GuiResponse {
         success: true,
@@ -245,17 +461,39 @@ async fn kill_all_instances(state: tauri::State<'_, AppState>) -> Result<GuiResp
 async fn get_running_instances(
     state: tauri::State<'_, AppState>,
 ) -> Result<GuiResponse, String> {
-    let mut pids = state.pids.lock().unwrap();
+    // 后台服务运行时以其实例列表为准 (包含 CLI 启动的实例)
+    if let Some(mut client) = daemon_client().await {
+        let instances = client.list().await?;
+        let running_pids: Vec<u32> = instances.iter().map(|r| r.pid).collect();
+        *state.pids.lock().unwrap() = running_pids.clone();
+        state.registry.lock().unwrap().instances = instances;
 
-    // 过滤出仍在运行的进程
-    let running_pids: Vec<u32> = pids
-        .iter()
-        .copied()
-        .filter(|&pid| platform::process_exists(pid))
-        .collect();
+        return Ok(GuiResponse {
+            success: true,
+            message: format!("当前运行 {} 个实例", running_pids.len()),
+            pids: running_pids,
+        });
+    }
 
-    // 更新 PID 列表,移除已经不存在的进程
-    *pids = running_pids.clone();
+    let (running_pids, exited) = {
+        let mut pids = state.pids.lock().unwrap();
+
+        // 过滤出仍在运行的进程
+        let running_pids: Vec<u32> = pids
+            .iter()
+            .copied()
+            .filter(|&pid| platform::process_exists(pid))
+            .collect();
+
+        // 更新 PID 列表,移除已经不存在的进程
+        let exited: Vec<u32> = pids.iter().copied().filter(|pid| !running_pids.contains(pid)).collect();
+        *pids = running_pids.clone();
+        (running_pids, exited)
+    };
+
+    if !exited.is_empty() {
+        update_registry(&state.registry, move |r| RegistryChange::Forget(exited).apply(r)).await;
+    }
 
     Ok(GuiResponse {
         success: true,
@@ -264,6 +502,251 @@ async fn get_running_instances(
     })
 }
 
+/// Tauri 命令: 采样所有实例的资源占用
+#[tauri::command]
+async fn get_instance_stats(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<InstanceStats>, String> {
+    let pids = state.pids.lock().unwrap().clone();
+    let stats = state.monitor.lock().unwrap().sample(&pids);
+    Ok(stats)
+}
+
+/// Tauri 命令: 发现工具外启动的实例
+#[tauri::command]
+async fn discover_instances(
+    app_type: Option<String>,
+) -> Result<Vec<discovery::DiscoveredInstance>, String> {
+    let app_type_enum = match app_type.as_deref() {
+        Some("wechat") | Some("WeChat") => AppType::WeChat,
+        _ => AppType::WeCom,
+    };
+
+    Ok(discovery::discover(&app_type_enum))
+}
+
+/// Tauri 命令: 将已发现的实例纳入管理
+#[tauri::command]
+async fn adopt_instances(
+    pids: Vec<u32>,
+    state: tauri::State<'_, AppState>,
+) -> Result<GuiResponse, String> {
+    let mut records = Vec::new();
+    let discovered: Vec<discovery::DiscoveredInstance> = [AppType::WeCom, AppType::WeChat]
+        .iter()
+        .flat_map(discovery::discover)
+        .collect();
+
+    let mut managed = state.pids.lock().unwrap().clone();
+    for pid in pids {
+        if platform::process_exists(pid) && !managed.contains(&pid) {
+            managed.push(pid);
+
+            // 记录发现时推断出的应用类型和数据目录
+            let found = discovered.iter().find(|d| d.pid == pid);
+            let app_type = found.map(|d| d.app_type.clone()).unwrap_or_default();
+            let mut record = InstanceRecord::new(pid, app_type, IsolationMode::Simple);
+            record.data_dir = found.and_then(|d| d.data_dir.clone());
+            record.exe_path = found.and_then(|d| d.exe_path.clone());
+            records.push(record);
+        }
+    }
+    *state.pids.lock().unwrap() = managed.clone();
+
+    let adopted = records.len();
+    record_change(&state.registry, RegistryChange::Add(records)).await;
+
+    Ok(GuiResponse {
+        success: true,
+        message: format!("已纳入管理 {} 个实例", adopted),
+        pids: managed,
+    })
+}
+
+/// Tauri 命令: 启用/关闭崩溃自动重启
+#[tauri::command]
+async fn set_watchdog_enabled(
+    enabled: bool,
+    state: tauri::State<'_, AppState>,
+) -> Result<GuiResponse, String> {
+    let mut policy = state.watchdog.policy();
+    policy.enabled = enabled;
+    state.watchdog.set_policy(policy);
+
+    Ok(GuiResponse {
+        success: true,
+        message: format!("已{}崩溃自动重启", if enabled { "启用" } else { "关闭" }),
+        pids: vec![],
+    })
+}
+
+/// Tauri 命令: 获取守护策略
+#[tauri::command]
+async fn get_watchdog_policy(
+    state: tauri::State<'_, AppState>,
+) -> Result<WatchdogPolicy, String> {
+    Ok(state.watchdog.policy())
+}
+
+/// Tauri 命令: 获取当前生效的配置
+#[tauri::command]
+async fn get_config() -> Result<Config, String> {
+    Ok((*config::current()).clone())
+}
+
+/// Tauri 命令: 列出账号配置
+#[tauri::command]
+async fn list_profiles() -> Result<Vec<AccountProfile>, String> {
+    Ok(ProfileStore::load_default()?.profiles)
+}
+
+/// Tauri 命令: 保存账号配置 (original_name 为空时新建,否则更新)
+#[tauri::command]
+async fn save_profile(
+    profile: AccountProfile,
+    original_name: Option<String>,
+) -> Result<AccountProfile, String> {
+    let mut store = ProfileStore::load_default()?;
+    let saved = match original_name {
+        Some(name) => store.update(&name, profile)?.clone(),
+        None => store.create(profile)?.clone(),
+    };
+    store.save()?;
+    Ok(saved)
+}
+
+/// Tauri 命令: 删除账号配置
+#[tauri::command]
+async fn delete_profile(name: String) -> Result<GuiResponse, String> {
+    let mut store = ProfileStore::load_default()?;
+    store.delete(&name)?;
+    store.save()?;
+
+    Ok(GuiResponse {
+        success: true,
+        message: format!("已删除账号配置 {} (数据目录已保留)", name),
+        pids: vec![],
+    })
+}
+
+/// Tauri 命令: 启动账号配置
+#[tauri::command]
+async fn launch_profile(
+    name: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<GuiResponse, String> {
+    let store = ProfileStore::load_default()?;
+    let profile = store
+        .get(&name)
+        .cloned()
+        .ok_or_else(|| format!("账号配置不存在: {}", name))?;
+
+    match start_profile(&state, &profile).await {
+        Ok(record) => Ok(GuiResponse {
+            success: true,
+            message: format!("✅ 已启动账号 {} (PID: {})", name, record.pid),
+            pids: state.pids.lock().unwrap().clone(),
+        }),
+        Err(e) => Ok(GuiResponse {
+            success: false,
+            message: format!("启动账号 {} 失败: {}", name, e),
+            pids: vec![],
+        }),
+    }
+}
+
+/// 启动账号配置并记录实例
+async fn start_profile(state: &AppState, profile: &AccountProfile) -> Result<InstanceRecord, String> {
+    // 后台服务运行时由其启动并记录实例
+    let record = match daemon_client().await {
+        Some(mut client) => {
+            let record = client.launch_profile(&profile.name).await?;
+            state.registry.lock().unwrap().add(record.clone());
+            record
+        }
+        None => {
+            let record = profiles::launch(profile).await?;
+            let added = record.clone();
+            update_registry(&state.registry, move |r| r.add(added)).await;
+            record
+        }
+    };
+
+    if record.backend == IsolationMode::Simple {
+        state.watchdog.watch(WatchedInstance {
+            pid: record.pid,
+            app_type: profile.app_type.clone(),
+            app_path: profile.app_path.clone(),
+            config: profile.instance_config(),
+        });
+    }
+
+    state.pids.lock().unwrap().push(record.pid);
+    Ok(record)
+}
+
+/// 定时任务事件 (发送给前端)
+#[derive(Debug, Clone, Serialize)]
+struct ScheduleEvent {
+    #[serde(flatten)]
+    action: ScheduledAction,
+    success: bool,
+    message: String,
+}
+
+/// 定时启动账号配置 (已在运行时跳过)
+async fn start_scheduled(state: &AppState, name: &str) -> Result<String, String> {
+    let running = state
+        .registry
+        .lock()
+        .unwrap()
+        .instances
+        .iter()
+        .find(|r| r.profile.as_deref() == Some(name) && platform::process_exists(r.pid))
+        .map(|r| r.pid);
+    if let Some(pid) = running {
+        return Ok(format!("已在运行 (PID {})", pid));
+    }
+
+    let store = ProfileStore::load_default()?;
+    let profile = store.get(name).ok_or_else(|| format!("账号配置不存在: {}", name))?;
+    let record = start_profile(state, profile).await?;
+    Ok(format!("已启动 (PID {})", record.pid))
+}
+
+/// 定时停止账号配置的实例 (先请求正常退出)
+async fn stop_scheduled(state: &AppState, name: &str) -> Result<String, String> {
+    let pids: Vec<u32> = state
+        .registry
+        .lock()
+        .unwrap()
+        .instances
+        .iter()
+        .filter(|r| r.profile.as_deref() == Some(name))
+        .map(|r| r.pid)
+        .collect();
+    if pids.is_empty() {
+        return Ok("未在运行".to_string());
+    }
+
+    for &pid in &pids {
+        state.watchdog.expect_exit(pid);
+        run_hook(state, HookEvent::PreKill, pid).await;
+        if platform::process_exists(pid) {
+            wecom_multi_open::stop_process(pid, scheduler::STOP_GRACE).await?;
+        }
+        forget_instance(state, pid).await;
+    }
+    Ok(format!("已停止 {} 个实例", pids.len()))
+}
+
+/// Tauri 命令: 各账号配置的下一次定时动作
+#[tauri::command]
+async fn get_schedules() -> Result<Vec<NextFire>, String> {
+    let store = ProfileStore::load_default()?;
+    Ok(scheduler::next_fires(store.list(), SystemClock.now()))
+}
+
 /// Tauri 命令: 设置退出时是否保留实例
 #[tauri::command]
 async fn set_keep_on_exit(
@@ -303,26 +786,71 @@ async fn check_sandboxie_available() -> Result<bool, String> {
     }
 }
 
+/// Tauri 命令: 路径检测诊断
+#[tauri::command]
+async fn diagnose_paths(app_type: Option<String>) -> Result<path_resolver::PathReport, String> {
+    let app_type = match app_type.as_deref() {
+        Some("wechat") | Some("WeChat") => AppType::WeChat,
+        Some(_) => AppType::WeCom,
+        None => config::current().app.clone(),
+    };
+    Ok(path_resolver::diagnose(&app_type))
+}
+
+/// Tauri 命令: 生成启动计划 (不启动实例)
+#[tauri::command]
+async fn plan_spawn(
+    count: u8,
+    app_type: Option<String>,
+    isolation_mode: Option<String>,
+) -> Result<SpawnPlan, String> {
+    let config = config::current();
+    let app_type = match app_type.as_deref() {
+        Some("wechat") | Some("WeChat") => AppType::WeChat,
+        Some(_) => AppType::WeCom,
+        None => config.app.clone(),
+    };
+    let isolation = match isolation_mode.as_deref() {
+        Some("sandboxie") => IsolationMode::Sandboxie,
+        Some(_) => IsolationMode::Simple,
+        None => config.isolation.clone(),
+    };
+
+    if isolation == IsolationMode::Sandboxie {
+        #[cfg(target_os = "windows")]
+        return WeComManager::new()?.plan(count);
+        #[cfg(not(target_os = "windows"))]
+        return Err("Sandboxie 隔离仅支持 Windows 平台".to_string());
+    }
+
+    let req = SpawnRequest {
+        count,
+        app_path: None,
+        app_type: Some(app_type),
+        instance_configs: None,
+        dry_run: true,
+    };
+    platform::spawn_multiple(req)
+        .await?
+        .plan
+        .ok_or_else(|| "当前平台不支持生成启动计划".to_string())
+}
+
+/// Tauri 命令: 环境自检
+#[tauri::command]
+async fn run_doctor() -> Result<doctor::DoctorReport, String> {
+    Ok(doctor::run(&config::current()))
+}
+
 /// Tauri 命令: 清除路径缓存
 #[tauri::command]
 async fn clear_path_cache() -> Result<GuiResponse, String> {
-    #[cfg(target_os = "windows")]
-    {
-        platform::clear_path_cache();
-        Ok(GuiResponse {
-            success: true,
-            message: "✓ 已清除路径缓存,下次启动将重新检测".to_string(),
-            pids: vec![],
-        })
-    }
-    #[cfg(not(target_os = "windows"))]
-    {
-        Ok(GuiResponse {
-            success: false,
-            message: "此功能仅支持Windows平台".to_string(),
-            pids: vec![],
-        })
-    }
+    path_resolver::clear_cache()?;
+    Ok(GuiResponse {
+        success: true,
+        message: "✓ 已清除路径缓存,下次启动将重新检测".to_string(),
+        pids: vec![],
+    })
 }
 
 /// 清理所有子进程
@@ -355,16 +883,162 @@ fn cleanup_all_processes(state: &AppState) {
     }
 
     println!("清理完成: 已关闭 {} / {} 个进程", killed, count);
+
+    let closed = pids.clone();
+    drop(pids);
+    tauri::async_runtime::block_on(record_change(&state.registry, RegistryChange::Forget(closed)));
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 fn main() {
+    match Config::load(&[]) {
+        Ok(loaded) => config::install(loaded),
+        Err(e) => eprintln!("⚠ 加载配置失败,使用默认配置: {}", e),
+    }
+
+    // 同一用户只运行一个 GUI: 已在运行时转发参数并激活已有窗口
+    let gui_lock = match LockFile::try_acquire(&single_instance::gui_lock_path()) {
+        Ok(Some(lock)) => Some(lock),
+        Ok(None) => {
+            let endpoint = single_instance::gui_endpoint();
+            match tauri::async_runtime::block_on(single_instance::forward(&endpoint, &Activation::current())) {
+                Ok(()) => println!("GUI 已在运行,已激活现有窗口"),
+                Err(e) => {
+                    eprintln!("✗ GUI 已在运行,但无法激活其窗口: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        Err(e) => {
+            eprintln!("⚠ 获取单实例锁失败,继续运行: {}", e);
+            None
+        }
+    };
+
+    let holds_gui_lock = gui_lock.is_some();
+
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
-        .manage(AppState::default())
-        .setup(|app| {
+        .manage(AppState::restore())
+        .setup(move |app| {
             let _window = app.get_webview_window("main").unwrap();
 
+            // 启动守护循环,重启结果同步到 PID 列表并通知前端
+            let state = app.state::<AppState>().inner().clone();
+            let schedule_state = state.clone();
+            let handle = app.handle().clone();
+            let pids = state.pids.clone();
+            let registry = state.registry.clone();
+            tauri::async_runtime::spawn(state.watchdog.run(move |event| {
+                match &event {
+                    WatchdogEvent::Restarted { old_pid, new_pid, .. } => {
+                        let mut pids = pids.lock().unwrap();
+                        pids.retain(|p| p != old_pid);
+                        pids.push(*new_pid);
+
+                        // 后台服务运行时由其记录 (计入重启指标),否则直接修改注册表文件
+                        let (old_pid, new_pid) = (*old_pid, *new_pid);
+                        let registry = registry.clone();
+                        tauri::async_runtime::spawn(async move {
+                            match daemon_client().await {
+                                Some(mut client) => {
+                                    if let Err(e) = client.restarted(old_pid, new_pid).await {
+                                        eprintln!("⚠ 同步重启信息到后台服务失败: {}", e);
+                                    }
+                                    registry.lock().unwrap().update_pid(old_pid, new_pid);
+                                }
+                                None => {
+                                    update_registry(&registry, move |r| r.update_pid(old_pid, new_pid)).await;
+                                }
+                            }
+                        });
+                    }
+                    WatchdogEvent::Crashed { pid, exit_code, .. } => {
+                        if let Some(record) = registry.lock().unwrap().get(*pid).cloned() {
+                            let exit_code = *exit_code;
+                            tauri::async_runtime::spawn(async move {
+                                hooks::run_for_record(&ProfileStore::default_path(), HookEvent::OnCrash, &record, exit_code).await;
+                            });
+                        }
+                    }
+                    WatchdogEvent::Stopped { pid } | WatchdogEvent::GaveUp { pid, .. } => {
+                        let (pid, stopped) = (*pid, matches!(event, WatchdogEvent::Stopped { .. }));
+                        let registry = registry.clone();
+                        tauri::async_runtime::spawn(async move {
+                            // 后台服务运行时由其对账移除并触发 on_exit,避免重复
+                            if daemon_client().await.is_some() {
+                                registry.lock().unwrap().remove(pid);
+                                return;
+                            }
+                            // 仍在注册表中说明是自行退出 (由本工具结束的实例已先移除)
+                            if let Some(Some(record)) = update_registry(&registry, move |r| r.remove(pid)).await {
+                                if stopped {
+                                    hooks::run_for_record(&ProfileStore::default_path(), HookEvent::OnExit, &record, None).await;
+                                }
+                            }
+                        });
+                    }
+                    _ => {}
+                }
+                println!("守护事件: {:?}", event);
+                let _ = handle.emit("watchdog-event", &event);
+            }));
+
+            // 接收再次打开 GUI 时转发的请求: 激活窗口并通知前端
+            if holds_gui_lock {
+                let handle = app.handle().clone();
+                tauri::async_runtime::spawn(async move {
+                    let on_activate = move |activation: Activation| {
+                        if let Some(window) = handle.get_webview_window("main") {
+                            let _ = window.unminimize();
+                            let _ = window.show();
+                            let _ = window.set_focus();
+                        }
+                        let _ = handle.emit("second-instance", &activation);
+                    };
+                    if let Err(e) = single_instance::listen(&single_instance::gui_endpoint(), on_activate).await {
+                        eprintln!("⚠ 无法接收其他 GUI 进程的请求: {}", e);
+                    }
+                });
+            }
+
+            // 定时启动 / 停止账号配置 (后台服务运行时由后台服务执行)
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let mut scheduler = Scheduler::new(SystemClock);
+                loop {
+                    if daemon_client().await.is_some() {
+                        scheduler.reset();
+                    } else if let Ok(store) = ProfileStore::load_default() {
+                        for due in scheduler.due(store.list()) {
+                            let result = match due.action {
+                                ScheduleAction::Start => start_scheduled(&schedule_state, &due.profile).await,
+                                ScheduleAction::Stop => stop_scheduled(&schedule_state, &due.profile).await,
+                            };
+                            println!("定时任务 ({}): {:?}", due.profile, result);
+                            let (success, message) = match result {
+                                Ok(message) => (true, message),
+                                Err(e) => (false, e),
+                            };
+                            let _ = handle.emit("schedule-event", ScheduleEvent { action: due, success, message });
+                        }
+                    }
+                    tokio::time::sleep(scheduler::TICK_INTERVAL).await;
+                }
+            });
+
+            // 监视配置文件,修改后立即生效并通知前端
+            let handle = app.handle().clone();
+            let keep_on_exit = state.keep_on_exit.clone();
+            let reloader = ConfigReloader::with_defaults((*config::current()).clone(), Vec::new());
+            tauri::async_runtime::spawn(reloader.run(config_watch::DEFAULT_POLL_INTERVAL, move |event| {
+                if let ConfigEvent::ConfigChanged { config } = event {
+                    *keep_on_exit.lock().unwrap() = config.keep_on_exit;
+                }
+                let _ = handle.emit("config-changed", event);
+            }));
+
             // 应用原生窗口效果
             // 注意: 在非透明窗口下,vibrancy 效果可能不会生效
             // 目前使用 CSS 样式模拟平台特定的视觉效果
@@ -403,10 +1077,24 @@ fn main() {
             kill_instance,
             kill_all_instances,
             get_running_instances,
+            get_instance_stats,
+            discover_instances,
+            adopt_instances,
+            set_watchdog_enabled,
+            get_watchdog_policy,
+            get_config,
+            list_profiles,
+            save_profile,
+            delete_profile,
+            launch_profile,
+            get_schedules,
             set_keep_on_exit,
             get_keep_on_exit,
             check_sandboxie_available,
             clear_path_cache,
+            diagnose_paths,
+            run_doctor,
+            plan_spawn,
         ])
         .build(tauri::generate_context!())
         .expect("启动 Tauri 应用失败")
@@ -0,0 +1,324 @@
+// 生命周期钩子 - 在实例启动、退出、崩溃、结束前运行用户脚本
+//
+// 钩子按账号配置设置,通过系统 shell 执行 (Unix 为 sh -c,Windows 为 cmd /C)。
+// 实例信息通过 WECOM_HOOK_* 环境变量传入,同时以 JSON 写入标准输入。
+// 钩子超时会被结束;失败只记录日志,不影响实例的启动或结束。
+
+use crate::profiles::{AccountProfile, ProfileStore};
+use crate::registry::InstanceRecord;
+use crate::AppType;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// 默认超时时间 (秒)
+pub const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+/// 生命周期事件
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HookEvent {
+    /// 启动前 (此时还没有 PID)
+    PreSpawn,
+    PostSpawn,
+    /// 实例自行退出 (不含由本工具结束的实例)
+    OnExit,
+    /// 实例异常退出 (需要启用实例守护)
+    OnCrash,
+    /// 由本工具结束实例前
+    PreKill,
+}
+
+impl HookEvent {
+    pub const ALL: [HookEvent; 5] = [
+        HookEvent::PreSpawn,
+        HookEvent::PostSpawn,
+        HookEvent::OnExit,
+        HookEvent::OnCrash,
+        HookEvent::PreKill,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookEvent::PreSpawn => "pre_spawn",
+            HookEvent::PostSpawn => "post_spawn",
+            HookEvent::OnExit => "on_exit",
+            HookEvent::OnCrash => "on_crash",
+            HookEvent::PreKill => "pre_kill",
+        }
+    }
+
+    /// 解析事件名 (`-` 与 `_` 等价)
+    pub fn parse(name: &str) -> Result<Self, String> {
+        let name = name.trim().to_lowercase().replace('-', "_");
+        Self::ALL.into_iter().find(|e| e.name() == name).ok_or_else(|| {
+            let names: Vec<&str> = Self::ALL.iter().map(|e| e.name()).collect();
+            format!("未知钩子事件 '{}' (可选: {})", name, names.join(", "))
+        })
+    }
+}
+
+/// 账号配置的钩子
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Hooks {
+    pub pre_spawn: Option<String>,
+    pub post_spawn: Option<String>,
+    pub on_exit: Option<String>,
+    pub on_crash: Option<String>,
+    pub pre_kill: Option<String>,
+    /// 单个钩子的超时时间 (秒)
+    pub timeout_secs: u64,
+}
+
+impl Default for Hooks {
+    fn default() -> Self {
+        Self {
+            pre_spawn: None,
+            post_spawn: None,
+            on_exit: None,
+            on_crash: None,
+            pre_kill: None,
+            timeout_secs: DEFAULT_TIMEOUT_SECS,
+        }
+    }
+}
+
+impl Hooks {
+    pub fn command(&self, event: HookEvent) -> Option<&str> {
+        let command = match event {
+            HookEvent::PreSpawn => &self.pre_spawn,
+            HookEvent::PostSpawn => &self.post_spawn,
+            HookEvent::OnExit => &self.on_exit,
+            HookEvent::OnCrash => &self.on_crash,
+            HookEvent::PreKill => &self.pre_kill,
+        };
+        command.as_deref().filter(|c| !c.trim().is_empty())
+    }
+
+    /// 设置钩子命令,空字符串表示移除
+    pub fn set(&mut self, event: HookEvent, command: &str) {
+        let command = (!command.trim().is_empty()).then(|| command.to_string());
+        match event {
+            HookEvent::PreSpawn => self.pre_spawn = command,
+            HookEvent::PostSpawn => self.post_spawn = command,
+            HookEvent::OnExit => self.on_exit = command,
+            HookEvent::OnCrash => self.on_crash = command,
+            HookEvent::PreKill => self.pre_kill = command,
+        }
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs.max(1))
+    }
+}
+
+/// 传给钩子的实例信息
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HookContext {
+    pub event: HookEvent,
+    pub profile: String,
+    pub app_type: AppType,
+    /// pre_spawn 时为空
+    pub pid: Option<u32>,
+    pub data_dir: Option<PathBuf>,
+    pub sandbox_name: Option<String>,
+    /// 退出码 (仅 on_crash,且能获取时)
+    pub exit_code: Option<i32>,
+}
+
+impl HookContext {
+    /// 启动前的上下文
+    pub fn for_profile(event: HookEvent, profile: &AccountProfile) -> Self {
+        Self {
+            event,
+            profile: profile.name.clone(),
+            app_type: profile.app_type.clone(),
+            pid: None,
+            data_dir: Some(profile.resolved_data_dir()),
+            sandbox_name: None,
+            exit_code: None,
+        }
+    }
+
+    /// 已启动实例的上下文
+    pub fn for_record(event: HookEvent, record: &InstanceRecord) -> Self {
+        Self {
+            event,
+            profile: record.profile.clone().unwrap_or_default(),
+            app_type: record.app_type.clone(),
+            pid: Some(record.pid),
+            data_dir: record.data_dir.clone(),
+            sandbox_name: record.sandbox_name.clone(),
+            exit_code: None,
+        }
+    }
+
+    /// 环境变量
+    pub fn env(&self) -> Vec<(&'static str, String)> {
+        let mut env = vec![
+            ("WECOM_HOOK_EVENT", self.event.name().to_string()),
+            ("WECOM_HOOK_PROFILE", self.profile.clone()),
+            (
+                "WECOM_HOOK_APP",
+                match self.app_type {
+                    AppType::WeCom => "wecom",
+                    AppType::WeChat => "wechat",
+                }
+                .to_string(),
+            ),
+        ];
+        if let Some(pid) = self.pid {
+            env.push(("WECOM_HOOK_PID", pid.to_string()));
+        }
+        if let Some(dir) = &self.data_dir {
+            env.push(("WECOM_HOOK_DATA_DIR", dir.display().to_string()));
+        }
+        if let Some(sandbox) = &self.sandbox_name {
+            env.push(("WECOM_HOOK_SANDBOX", sandbox.clone()));
+        }
+        if let Some(code) = self.exit_code {
+            env.push(("WECOM_HOOK_EXIT_CODE", code.to_string()));
+        }
+        env
+    }
+}
+
+/// 执行一条钩子命令,超时或退出码非 0 时返回错误
+pub async fn execute(command: &str, context: &HookContext, timeout: Duration) -> Result<(), String> {
+    let mut cmd = shell(command);
+    cmd.envs(context.env())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let mut child = cmd.spawn().map_err(|e| format!("无法执行: {}", e))?;
+    let input = serde_json::to_vec(context).map_err(|e| e.to_string())?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // 脚本不读取标准输入时写入会失败,忽略
+        let _ = stdin.write_all(&input).await;
+    }
+
+    // 超时后丢弃 child 即结束进程
+    let output = tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .map_err(|_| format!("超时 ({} 秒)", timeout.as_secs()))?
+        .map_err(|e| e.to_string())?;
+
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stderr = stderr.trim();
+    let code = output.status.code().map(|c| c.to_string()).unwrap_or_else(|| "无".to_string());
+    if stderr.is_empty() {
+        Err(format!("退出码 {}", code))
+    } else {
+        Err(format!("退出码 {}: {}", code, stderr))
+    }
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").arg(command);
+    cmd
+}
+
+#[cfg(not(windows))]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+/// 运行账号配置中对应事件的钩子,失败只记录日志
+pub async fn run(hooks: &Hooks, context: &HookContext) {
+    let Some(command) = hooks.command(context.event) else {
+        return;
+    };
+    match execute(command, context, hooks.timeout()).await {
+        Ok(()) => eprintln!("✓ 钩子 {} 已执行 ({})", context.event.name(), context.profile),
+        Err(e) => eprintln!("⚠ 钩子 {} 执行失败 ({}): {}", context.event.name(), context.profile, e),
+    }
+}
+
+/// 按实例记录中的账号配置名称查找并运行钩子 (未使用账号配置的实例没有钩子)
+pub async fn run_for_record(profiles: &Path, event: HookEvent, record: &InstanceRecord, exit_code: Option<i32>) {
+    let Some(name) = &record.profile else {
+        return;
+    };
+    let store = match ProfileStore::load(profiles) {
+        Ok(store) => store,
+        Err(e) => {
+            eprintln!("⚠ 读取账号配置失败,跳过钩子 {}: {}", event.name(), e);
+            return;
+        }
+    };
+    let Some(profile) = store.get(name) else {
+        return;
+    };
+
+    let mut context = HookContext::for_record(event, record);
+    context.exit_code = exit_code;
+    run(&profile.hooks, &context).await;
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::IsolationMode;
+
+    fn context() -> HookContext {
+        let mut record = InstanceRecord::new(4321, AppType::WeChat, IsolationMode::Simple);
+        record.profile = Some("Sales-Beijing".to_string());
+        HookContext::for_record(HookEvent::OnCrash, &record)
+    }
+
+    #[tokio::test]
+    async fn test_hook_receives_env_and_stdin() {
+        let dir = crate::app_dirs::temp_test_dir("hooks-env");
+        let out = dir.join("out.txt");
+        let command = format!(
+            "echo \"$WECOM_HOOK_EVENT $WECOM_HOOK_PROFILE $WECOM_HOOK_APP $WECOM_HOOK_PID\" > '{0}'; cat >> '{0}'",
+            out.display()
+        );
+
+        execute(&command, &context(), Duration::from_secs(5)).await.unwrap();
+
+        let text = std::fs::read_to_string(&out).unwrap();
+        let (env_line, stdin) = text.split_once('\n').unwrap();
+        assert_eq!(env_line, "on_crash Sales-Beijing wechat 4321");
+        let received: HookContext = serde_json::from_str(stdin).unwrap();
+        assert_eq!(received, context());
+    }
+
+    #[tokio::test]
+    async fn test_hook_failure_and_timeout() {
+        let error = execute("echo 挂载失败 >&2; exit 3", &context(), Duration::from_secs(5))
+            .await
+            .unwrap_err();
+        assert_eq!(error, "退出码 3: 挂载失败");
+
+        let started = std::time::Instant::now();
+        let error = execute("sleep 5", &context(), Duration::from_millis(200)).await.unwrap_err();
+        assert!(error.contains("超时"));
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_parse_and_set() {
+        assert_eq!(HookEvent::parse("pre-spawn").unwrap(), HookEvent::PreSpawn);
+        assert!(HookEvent::parse("on_start").is_err());
+
+        let mut hooks = Hooks::default();
+        hooks.set(HookEvent::OnCrash, "notify.sh");
+        assert_eq!(hooks.command(HookEvent::OnCrash), Some("notify.sh"));
+        hooks.set(HookEvent::OnCrash, "");
+        assert_eq!(hooks.command(HookEvent::OnCrash), None);
+    }
+}
@@ -0,0 +1,497 @@
+// 本地 REST API - 供运维脚本等外部工具启动 / 结束实例
+//
+// 默认关闭,启用后随后台服务运行,仅监听 127.0.0.1。除 /health 和 /openapi.json 外,
+// 所有请求都需要带上 `Authorization: Bearer <令牌>`。
+// 只实现自动化调用所需的最小 HTTP/1.1 子集: 每个连接处理一个请求后关闭。
+
+use crate::daemon::{self, Backend, Daemon, RpcError};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// 请求头最大长度
+const MAX_HEAD_BYTES: usize = 16 * 1024;
+/// 请求体最大长度
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+/// 读取请求的超时时间
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// HTTP 请求
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// 请求头 (名称不区分大小写)
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// HTTP 响应体
+#[derive(Debug, Clone, PartialEq)]
+pub enum Body {
+    Json(Value),
+    /// Prometheus 文本格式
+    Metrics(String),
+}
+
+/// HTTP 响应
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: Body,
+}
+
+impl Response {
+    fn ok(body: Value) -> Self {
+        Self {
+            status: 200,
+            body: Body::Json(body),
+        }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: Body::Json(json!({ "error": { "code": status, "message": message.into() } })),
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let (content_type, body) = match &self.body {
+            Body::Json(value) => (
+                "application/json; charset=utf-8",
+                serde_json::to_string_pretty(value).unwrap_or_default(),
+            ),
+            Body::Metrics(text) => ("text/plain; version=0.0.4; charset=utf-8", text.clone()),
+        };
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.status,
+            reason(self.status),
+            content_type,
+            body.len()
+        );
+        if self.status == 401 {
+            head.push_str("WWW-Authenticate: Bearer\r\n");
+        }
+        head.push_str("\r\n");
+        [head.into_bytes(), body.into_bytes()].concat()
+    }
+}
+
+/// REST API 服务
+pub struct HttpApi {
+    listener: TcpListener,
+    token: String,
+}
+
+impl HttpApi {
+    /// 在 127.0.0.1 的指定端口监听 (0 表示随机端口)
+    pub async fn bind(port: u16, token: &str) -> Result<Self, String> {
+        if token.is_empty() {
+            return Err("未设置 REST API 访问令牌".to_string());
+        }
+        let addr = SocketAddr::from(([127, 0, 0, 1], port));
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| format!("REST API 监听 {} 失败: {}", addr, e))?;
+        Ok(Self {
+            listener,
+            token: token.to_string(),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, String> {
+        self.listener.local_addr().map_err(|e| e.to_string())
+    }
+
+    /// 处理请求,直到后台服务收到 shutdown
+    pub async fn serve<B: Backend>(self, daemon: Arc<Daemon<B>>) -> Result<(), String> {
+        let api = Arc::new(self);
+        eprintln!("✓ REST API 已启动: http://{}", api.local_addr()?);
+
+        loop {
+            tokio::select! {
+                accepted = api.listener.accept() => {
+                    match accepted {
+                        Ok((stream, _)) => {
+                            tokio::spawn(api.clone().serve_connection(daemon.clone(), stream));
+                        }
+                        Err(e) => eprintln!("⚠ 接受连接失败: {}", e),
+                    }
+                }
+                _ = daemon.stopped() => break,
+            }
+        }
+        Ok(())
+    }
+
+    async fn serve_connection<B: Backend>(self: Arc<Self>, daemon: Arc<Daemon<B>>, mut stream: TcpStream) {
+        let response = match tokio::time::timeout(READ_TIMEOUT, read_request(&mut stream)).await {
+            Ok(Ok(request)) => self.handle(&daemon, request).await,
+            Ok(Err(response)) => response,
+            Err(_) => Response::error(408, "读取请求超时"),
+        };
+        let _ = stream.write_all(&response.to_bytes()).await;
+        let _ = stream.shutdown().await;
+    }
+
+    /// 处理一个请求
+    pub async fn handle<B: Backend>(&self, daemon: &Daemon<B>, request: Request) -> Response {
+        let segments: Vec<&str> = request.path.split('?').next().unwrap_or_default().split('/').filter(|s| !s.is_empty()).collect();
+
+        // 无需认证的接口
+        match (request.method.as_str(), segments.as_slice()) {
+            ("GET", ["health"]) => return Response::ok(json!({ "status": "ok", "version": env!("CARGO_PKG_VERSION") })),
+            ("GET", ["openapi.json"]) => return Response::ok(openapi()),
+            _ => {}
+        }
+
+        if !self.authorized(&request) {
+            return Response::error(401, "缺少或无效的访问令牌");
+        }
+
+        let (method, params) = match (request.method.as_str(), segments.as_slice()) {
+            ("GET", ["instances"]) => ("list", Value::Null),
+            ("POST", ["instances"]) => match serde_json::from_slice::<Value>(&request.body) {
+                Ok(body) => ("spawn", body),
+                Err(e) => return Response::error(400, format!("请求体不是有效的 JSON: {}", e)),
+            },
+            ("DELETE", ["instances"]) => ("kill_all", Value::Null),
+            ("DELETE", ["instances", pid]) => match pid.parse::<u32>() {
+                Ok(pid) => ("kill", json!({ "pid": pid })),
+                Err(_) => return Response::error(400, format!("无效的 PID: {}", pid)),
+            },
+            ("GET", ["profiles"]) => ("profiles", Value::Null),
+            ("GET", ["metrics"]) => ("metrics", Value::Null),
+            ("POST", ["profiles", name, "launch"]) => ("launch_profile", json!({ "name": percent_decode(name) })),
+            (_, ["health" | "openapi.json" | "instances" | "profiles" | "metrics", ..]) => {
+                return Response::error(405, format!("不支持的请求: {} {}", request.method, request.path))
+            }
+            _ => return Response::error(404, format!("未找到: {}", request.path)),
+        };
+
+        match daemon.call(method, params).await {
+            Ok(Value::String(text)) if method == "metrics" => Response {
+                status: 200,
+                body: Body::Metrics(text),
+            },
+            Ok(result) => Response::ok(result),
+            Err(e) => rpc_error_response(e),
+        }
+    }
+
+    fn authorized(&self, request: &Request) -> bool {
+        let Some(token) = request.header("authorization").and_then(|v| v.strip_prefix("Bearer ")) else {
+            return false;
+        };
+        // 逐字节比较全部内容,耗时与令牌内容无关
+        token.len() == self.token.len()
+            && token
+                .bytes()
+                .zip(self.token.bytes())
+                .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+fn rpc_error_response(e: RpcError) -> Response {
+    let status = match e.code {
+        daemon::INVALID_PARAMS | daemon::PARSE_ERROR => 400,
+        daemon::NOT_FOUND | daemon::METHOD_NOT_FOUND => 404,
+        _ => 500,
+    };
+    Response::error(status, e.message)
+}
+
+/// 读取一个请求,格式有误时返回错误响应
+async fn read_request(stream: &mut TcpStream) -> Result<Request, Response> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let head_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+        if buf.len() > MAX_HEAD_BYTES {
+            return Err(Response::error(431, "请求头过大"));
+        }
+        let n = stream.read(&mut chunk).await.map_err(|e| Response::error(400, e.to_string()))?;
+        if n == 0 {
+            return Err(Response::error(400, "请求不完整"));
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let mut request = parse_head(&buf[..head_end]).ok_or_else(|| Response::error(400, "无法解析请求"))?;
+    let length: usize = match request.header("content-length") {
+        Some(value) => value.trim().parse().map_err(|_| Response::error(400, "无效的 Content-Length"))?,
+        None => 0,
+    };
+    if length > MAX_BODY_BYTES {
+        return Err(Response::error(413, "请求体过大"));
+    }
+
+    let mut body = buf.split_off(head_end + 4);
+    while body.len() < length {
+        let n = stream.read(&mut chunk).await.map_err(|e| Response::error(400, e.to_string()))?;
+        if n == 0 {
+            return Err(Response::error(400, "请求体不完整"));
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    body.truncate(length);
+    request.body = body;
+    Ok(request)
+}
+
+/// 解析请求行和请求头
+fn parse_head(head: &[u8]) -> Option<Request> {
+    let text = std::str::from_utf8(head).ok()?;
+    let mut lines = text.split("\r\n");
+    let mut parts = lines.next()?.split_whitespace();
+    let method = parts.next()?.to_string();
+    let path = parts.next()?.to_string();
+    parts.next().filter(|version| version.starts_with("HTTP/1."))?;
+
+    let headers = lines
+        .map(|line| {
+            let (key, value) = line.split_once(':')?;
+            Some((key.trim().to_string(), value.trim().to_string()))
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(Request {
+        method,
+        path,
+        headers,
+        body: Vec::new(),
+    })
+}
+
+/// 解码 URL 路径中的 %XX (账号名称可能包含中文)
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        _ => "Internal Server Error",
+    }
+}
+
+/// OpenAPI 3.0 描述
+pub fn openapi() -> Value {
+    let error = json!({ "description": "错误", "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Error" } } } });
+    let ok = |description: &str, schema: Value| {
+        json!({ "description": description, "content": { "application/json": { "schema": schema } } })
+    };
+    let array_of = |name: &str| json!({ "type": "array", "items": { "$ref": format!("#/components/schemas/{}", name) } });
+    let schema = |name: &str| json!({ "$ref": format!("#/components/schemas/{}", name) });
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "wecom-multi-open REST API",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "仅监听 127.0.0.1。除 /health 和 /openapi.json 外均需 Bearer 令牌。"
+        },
+        "servers": [{ "url": "http://127.0.0.1:17890" }],
+        "security": [{ "bearer": [] }],
+        "paths": {
+            "/health": {
+                "get": {
+                    "summary": "健康检查",
+                    "security": [],
+                    "responses": { "200": ok("服务正常", json!({ "type": "object", "properties": { "status": { "type": "string" }, "version": { "type": "string" } } })) }
+                }
+            },
+            "/openapi.json": {
+                "get": {
+                    "summary": "本文档",
+                    "security": [],
+                    "responses": { "200": { "description": "OpenAPI 描述" } }
+                }
+            },
+            "/instances": {
+                "get": {
+                    "summary": "列出由本工具管理的实例",
+                    "responses": { "200": ok("实例列表", array_of("InstanceRecord")), "401": error }
+                },
+                "post": {
+                    "summary": "启动实例",
+                    "requestBody": { "required": true, "content": { "application/json": { "schema": schema("SpawnRequest") } } },
+                    "responses": { "200": ok("启动结果", schema("SpawnResponse")), "400": error, "401": error, "500": error }
+                },
+                "delete": {
+                    "summary": "结束全部实例",
+                    "responses": { "200": ok("结束结果", schema("KillAllResult")), "401": error }
+                }
+            },
+            "/instances/{pid}": {
+                "delete": {
+                    "summary": "结束实例",
+                    "parameters": [{ "name": "pid", "in": "path", "required": true, "schema": { "type": "integer" } }],
+                    "responses": { "200": ok("被结束的实例", schema("InstanceRecord")), "401": error, "404": error, "500": error }
+                }
+            },
+            "/profiles": {
+                "get": {
+                    "summary": "列出账号配置",
+                    "responses": { "200": ok("账号配置列表", array_of("AccountProfile")), "401": error }
+                }
+            },
+            "/metrics": {
+                "get": {
+                    "summary": "Prometheus 文本格式的运行指标",
+                    "responses": { "200": { "description": "实例数、启动次数和耗时、重启次数、Mutex 关闭失败次数和各实例内存", "content": { "text/plain": { "schema": { "type": "string" } } } }, "401": error }
+                }
+            },
+            "/profiles/{name}/launch": {
+                "post": {
+                    "summary": "启动账号配置",
+                    "parameters": [{ "name": "name", "in": "path", "required": true, "schema": { "type": "string" } }],
+                    "responses": { "200": ok("启动的实例", schema("InstanceRecord")), "401": error, "404": error, "500": error }
+                }
+            }
+        },
+        "components": {
+            "securitySchemes": { "bearer": { "type": "http", "scheme": "bearer" } },
+            "schemas": {
+                "Error": {
+                    "type": "object",
+                    "properties": { "error": { "type": "object", "properties": { "code": { "type": "integer" }, "message": { "type": "string" } } } }
+                },
+                "InstanceConfig": {
+                    "type": "object",
+                    "properties": {
+                        "data_dir": { "type": "string", "nullable": true },
+                        "proxy": { "type": "string", "nullable": true, "example": "http://127.0.0.1:7890" },
+                        "instance_id": { "type": "integer", "nullable": true },
+                        "args": { "type": "array", "items": { "type": "string" } }
+                    }
+                },
+                "SpawnRequest": {
+                    "type": "object",
+                    "required": ["count"],
+                    "properties": {
+                        "count": { "type": "integer", "minimum": 1 },
+                        "app_path": { "type": "string", "nullable": true },
+                        "app_type": { "type": "string", "enum": ["WeCom", "WeChat"], "nullable": true },
+                        "instance_configs": { "type": "array", "nullable": true, "items": schema("InstanceConfig") },
+                        "dry_run": { "type": "boolean", "default": false }
+                    }
+                },
+                "SpawnResponse": {
+                    "type": "object",
+                    "properties": {
+                        "pids": { "type": "array", "items": { "type": "integer" } },
+                        "success": { "type": "integer" },
+                        "failed": { "type": "integer" },
+                        "plan": { "type": "object", "description": "启动计划 (仅 dry_run)" }
+                    }
+                },
+                "InstanceRecord": {
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "string" },
+                        "pid": { "type": "integer" },
+                        "app_type": { "type": "string", "enum": ["WeCom", "WeChat"] },
+                        "profile": { "type": "string", "nullable": true },
+                        "backend": { "type": "string", "enum": ["simple", "sandboxie"] },
+                        "sandbox_name": { "type": "string", "nullable": true },
+                        "data_dir": { "type": "string", "nullable": true },
+                        "exe_path": { "type": "string", "nullable": true },
+                        "started_at": { "type": "integer", "description": "Unix 秒" },
+                        "restarts": { "type": "integer", "description": "被守护进程重启的次数" }
+                    }
+                },
+                "KillAllResult": {
+                    "type": "object",
+                    "properties": {
+                        "killed": { "type": "array", "items": { "type": "integer" } },
+                        "failed": { "type": "array", "items": { "type": "object", "properties": { "pid": { "type": "integer" }, "error": { "type": "string" } } } }
+                    }
+                },
+                "AccountProfile": {
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string" },
+                        "app_type": { "type": "string", "enum": ["WeCom", "WeChat"] },
+                        "data_dir": { "type": "string", "nullable": true },
+                        "proxy": { "type": "string", "nullable": true },
+                        "color": { "type": "string", "nullable": true },
+                        "launch_args": { "type": "array", "items": { "type": "string" } },
+                        "isolation": { "type": "string", "enum": ["simple", "sandboxie"] }
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_head() {
+        let request = parse_head(b"POST /instances HTTP/1.1\r\nHost: 127.0.0.1\r\nAuthorization: Bearer abc").unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/instances");
+        assert_eq!(request.header("authorization"), Some("Bearer abc"));
+
+        assert!(parse_head(b"GET /instances\r\n").is_none());
+        assert!(parse_head(b"GET / HTTP/1.1\r\nbroken header").is_none());
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("Sales-Beijing"), "Sales-Beijing");
+        assert_eq!(percent_decode("%E9%94%80%E5%94%AE%201"), "销售 1");
+        assert_eq!(percent_decode("100%"), "100%");
+    }
+
+    #[test]
+    fn test_openapi_covers_routes() {
+        let spec = openapi();
+        for path in ["/health", "/instances", "/instances/{pid}", "/profiles", "/profiles/{name}/launch", "/metrics"] {
+            assert!(spec["paths"].get(path).is_some(), "{}", path);
+        }
+    }
+}
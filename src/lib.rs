@@ -4,13 +4,33 @@
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
-// Windows 特定模块
-#[cfg(target_os = "windows")]
+// Windows 特定模块 (Sandboxie 沙盒配置跨平台,用于生成启动计划)
 pub mod windows_sandbox;
 
 #[cfg(target_os = "windows")]
 pub mod wecom_manager;
 
+pub mod app_dirs;
+pub mod config;
+pub mod config_watch;
+pub mod daemon;
+pub mod discovery;
+pub mod doctor;
+pub mod hooks;
+pub mod http_api;
+pub mod metrics;
+pub mod migrations;
+pub mod path_resolver;
+pub mod plan;
+pub mod monitor;
+pub mod profiles;
+pub mod registry;
+pub mod sandboxie_ini;
+pub mod scanner;
+pub mod scheduler;
+pub mod single_instance;
+pub mod watchdog;
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
 pub enum AppType {
     WeCom,   // 企业微信
@@ -23,10 +43,36 @@ impl Default for AppType {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// 隔离模式
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum IsolationMode {
+    #[default]
+    Simple,    // 简单模式 (无隔离)
+    Sandboxie, // Sandboxie沙盒模式 (Windows)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
 pub struct InstanceConfig {
     pub data_dir: Option<PathBuf>,  // 数据目录
     pub proxy: Option<String>,       // 代理配置 (如 "http://127.0.0.1:7890")
+    #[serde(default)]
+    pub instance_id: Option<u8>,     // 实例编号 (macOS 克隆包编号,默认按启动顺序)
+    #[serde(default)]
+    pub args: Vec<String>,           // 额外启动参数
+}
+
+impl InstanceConfig {
+    /// 代理相关的环境变量
+    pub fn proxy_env(&self) -> Vec<(String, String)> {
+        match &self.proxy {
+            Some(proxy) => ["http_proxy", "https_proxy", "all_proxy", "HTTP_PROXY", "HTTPS_PROXY", "ALL_PROXY"]
+                .iter()
+                .map(|k| (k.to_string(), proxy.clone()))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -35,6 +81,8 @@ pub struct SpawnRequest {
     pub app_path: Option<PathBuf>,
     pub app_type: Option<AppType>,
     pub instance_configs: Option<Vec<InstanceConfig>>,  // 每个实例的配置
+    #[serde(default)]
+    pub dry_run: bool,                                  // 只生成启动计划,不启动
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -42,6 +90,21 @@ pub struct SpawnResponse {
     pub pids: Vec<u32>,
     pub success: usize,
     pub failed: usize,
+    /// 启动计划 (仅 dry_run 时返回)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan: Option<plan::SpawnPlan>,
+}
+
+impl SpawnResponse {
+    /// dry-run 的返回: 不启动任何实例,只附带启动计划
+    pub fn planned(plan: plan::SpawnPlan) -> Self {
+        Self {
+            pids: Vec::new(),
+            success: 0,
+            failed: 0,
+            plan: Some(plan),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -50,13 +113,33 @@ pub struct ProcessInfo {
     pub started_at: String,
 }
 
+/// 请求实例正常退出,超过 grace 仍未退出时强制结束
+pub async fn stop_process(pid: u32, grace: std::time::Duration) -> Result<(), String> {
+    if let Err(e) = platform::request_close(pid) {
+        eprintln!("⚠ 请求实例 {} 退出失败,强制结束: {}", pid, e);
+        return platform::kill_process(pid);
+    }
+
+    let deadline = tokio::time::Instant::now() + grace;
+    while tokio::time::Instant::now() < deadline {
+        if !platform::process_exists(pid) {
+            return Ok(());
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    }
+    if platform::process_exists(pid) {
+        eprintln!("⚠ 实例 {} 未在 {} 秒内退出,强制结束", pid, grace.as_secs());
+        platform::kill_process(pid)
+    } else {
+        Ok(())
+    }
+}
+
 // 平台特定实现
 #[cfg(target_os = "windows")]
 pub mod platform {
     use super::*;
     use std::{ffi::OsStr, mem, os::windows::ffi::OsStrExt, slice, time::Duration};
-    use std::sync::{Mutex, OnceLock};
-    use std::collections::HashMap;
     use windows::{
         core::*, Win32::Foundation::*,
         Win32::System::Threading::*,
@@ -64,95 +147,17 @@ pub mod platform {
         Win32::Storage::FileSystem::*,
     };
 
-    const WECOM_MUTEX_NAME: &str = "Tencent.WeWork.Exclusive"; // 企业微信 Mutex
-    const WECHAT_MUTEX_NAME: &str = "_WeChat_App_Instance_Identity_Mutex_Name"; // 个人微信 Mutex
-
-    /// 路径缓存 (应用类型 -> 路径)
-    static PATH_CACHE: OnceLock<Mutex<HashMap<AppType, PathBuf>>> = OnceLock::new();
-
-    /// 获取路径缓存
-    fn get_path_cache() -> &'static Mutex<HashMap<AppType, PathBuf>> {
-        PATH_CACHE.get_or_init(|| Mutex::new(HashMap::new()))
-    }
-
-    /// 从缓存获取路径
-    fn get_cached_path(app_type: &AppType) -> Option<PathBuf> {
-        let cache = get_path_cache().lock().ok()?;
-        cache.get(app_type).cloned()
-    }
-
-    /// 缓存路径
-    fn cache_path(app_type: AppType, path: PathBuf) {
-        if let Ok(mut cache) = get_path_cache().lock() {
-            cache.insert(app_type, path);
-        }
-    }
-
-    /// 清除缓存
-    pub fn clear_path_cache() {
-        if let Ok(mut cache) = get_path_cache().lock() {
-            cache.clear();
-            println!("✓ 已清除路径缓存");
-        }
-    }
-
-    pub fn get_default_app_path() -> PathBuf {
+    pub fn get_default_app_path() -> std::result::Result<PathBuf, String> {
         get_default_app_path_by_type(AppType::WeCom)
     }
 
-    pub fn get_default_app_path_by_type(app_type: AppType) -> PathBuf {
-        // 优先级0: 从缓存获取 (最快)
-        if let Some(cached_path) = get_cached_path(&app_type) {
-            if cached_path.exists() {
-                println!("✓ 从缓存读取路径: {}", cached_path.display());
-                return cached_path;
-            } else {
-                // 缓存的路径已失效,清除该缓存
-                println!("⚠ 缓存路径已失效,重新检测");
-            }
-        }
-
-        // 优先级1: 从注册表读取安装路径
-        if let Some(path) = get_path_from_registry(app_type.clone()) {
-            if path.exists() {
-                println!("✓ 从注册表找到路径: {}", path.display());
-                cache_path(app_type.clone(), path.clone());
-                return path;
-            }
-        }
-
-        // 优先级2: 从正在运行的进程获取路径
-        if let Some(path) = get_path_from_running_process(app_type.clone()) {
-            if path.exists() {
-                println!("✓ 从运行进程找到路径: {}", path.display());
-                cache_path(app_type.clone(), path.clone());
-                return path;
-            }
-        }
-
-        // 优先级3: 扫描常见安装目录
-        if let Some(path) = scan_common_directories(app_type.clone()) {
-            println!("✓ 从常见目录找到路径: {}", path.display());
-            cache_path(app_type.clone(), path.clone());
-            return path;
-        }
-
-        // 优先级4: 返回默认路径
-        let default_path = match app_type {
-            AppType::WeCom => PathBuf::from(r"C:\Program Files (x86)\WXWork\WXWork.exe"),
-            AppType::WeChat => PathBuf::from(r"C:\Program Files (x86)\Tencent\WeChat\WeChat.exe"),
-        };
-
-        // 如果默认路径存在,也缓存它
-        if default_path.exists() {
-            cache_path(app_type, default_path.clone());
-        }
-
-        default_path
+    /// 检测应用路径,未找到时返回错误 (详见 `path_resolver::diagnose`)
+    pub fn get_default_app_path_by_type(app_type: AppType) -> std::result::Result<PathBuf, String> {
+        crate::path_resolver::resolve_app_path(&app_type)
     }
 
     /// 从注册表读取应用安装路径
-    fn get_path_from_registry(app_type: AppType) -> Option<PathBuf> {
+    pub(crate) fn get_path_from_registry(app_type: AppType) -> Option<PathBuf> {
         use windows::Win32::System::Registry::*;
 
         unsafe {
@@ -216,121 +221,8 @@ pub mod platform {
         None
     }
 
-    /// 从正在运行的进程获取路径
-    fn get_path_from_running_process(app_type: AppType) -> Option<PathBuf> {
-        use windows::Win32::System::ProcessStatus::*;
-
-        // 支持多种进程名称 (不同版本/语言可能不同)
-        let process_names = match app_type {
-            AppType::WeCom => vec![
-                "WXWork.exe",           // 老版本企业微信
-                "wxwork.exe",           // 中文版
-                "wecom.exe",            // 英文版
-                "wework.exe",           // 可能的别名
-                "企业微信.exe",          // 中文名
-            ],
-            AppType::WeChat => vec![
-                "wechat.exe",           // 标准名称
-                "weixin.exe",           // 微信拼音
-                "微信.exe",             // 中文名
-            ],
-        };
-
-        unsafe {
-            let mut process_ids = vec![0u32; 2048];
-            let mut bytes_returned = 0u32;
-
-            if EnumProcesses(
-                process_ids.as_mut_ptr(),
-                (process_ids.len() * std::mem::size_of::<u32>()) as u32,
-                &mut bytes_returned,
-            )
-            .is_ok()
-            {
-                let count = bytes_returned as usize / std::mem::size_of::<u32>();
-
-                for &pid in &process_ids[..count] {
-                    if pid == 0 {
-                        continue;
-                    }
-
-                    if let Ok(h_process) = OpenProcess(
-                        PROCESS_QUERY_INFORMATION | PROCESS_VM_READ,
-                        false,
-                        pid,
-                    ) {
-                        let mut exe_path = vec![0u16; 512];
-                        let mut size = exe_path.len() as u32;
-
-                        if QueryFullProcessImageNameW(
-                            h_process,
-                            PROCESS_NAME_WIN32,
-                            PWSTR(exe_path.as_mut_ptr()),
-                            &mut size,
-                        )
-                        .is_ok()
-                        {
-                            let path_str = String::from_utf16_lossy(&exe_path[..size as usize]);
-                            let path_lower = path_str.to_lowercase();
-
-                            // 检查是否匹配任一进程名
-                            for name in &process_names {
-                                if path_lower.contains(&name.to_lowercase()) {
-                                    let _ = CloseHandle(h_process);
-                                    return Some(PathBuf::from(path_str));
-                                }
-                            }
-                        }
-
-                        let _ = CloseHandle(h_process);
-                    }
-                }
-            }
-        }
-
-        None
-    }
-
-    /// 扫描常见安装目录
-    fn scan_common_directories(app_type: AppType) -> Option<PathBuf> {
-        println!("开始扫描常见安装目录...");
-
-        let (app_dirs, exe_names) = match app_type {
-            AppType::WeCom => (
-                vec![
-                    r"WXWork",
-                    r"Tencent\WXWork",
-                    r"企业微信",
-                ],
-                vec![
-                    "WXWork.exe",       // 标准大写 (最常见,优先)
-                    "wxwork.exe",       // 小写
-                    "Wxwork.exe",       // 首字母大写
-                    "WXWORK.EXE",       // 全大写
-                    "wecom.exe",        // 英文版
-                    "WeCom.exe",        // 英文版大写
-                    "wework.exe",       // 别名
-                    "WeWork.exe",       // 别名大写
-                    "企业微信.exe",      // 中文名
-                ],
-            ),
-            AppType::WeChat => (
-                vec![
-                    r"WeChat",
-                    r"Tencent\WeChat",
-                    r"微信",
-                ],
-                vec![
-                    "WeChat.exe",       // 标准
-                    "wechat.exe",       // 小写
-                    "WECHAT.EXE",       // 全大写
-                    "weixin.exe",       // 拼音
-                    "WeiXin.exe",       // 拼音大写
-                    "微信.exe",         // 中文名
-                ],
-            ),
-        };
-
+    /// 常见的程序安装根目录 (C、D 盘优先,其次是其他驱动器)
+    pub(crate) fn common_install_roots() -> Vec<PathBuf> {
         // 优先扫描 C 和 D 盘 (99% 的安装位置)
         let priority_drives = vec!['C', 'D'];
 
@@ -342,50 +234,15 @@ pub mod platform {
             r"Apps",
         ];
 
-        // 第一轮: 快速扫描 C 和 D 盘
-        println!("快速扫描: C 和 D 盘...");
-        for drive in &priority_drives {
-            for base_dir in &base_dirs {
-                for app_dir in &app_dirs {
-                    for exe_name in &exe_names {
-                        let full_path = PathBuf::from(format!(r"{}:\{}\{}\{}",
-                            drive, base_dir, app_dir, exe_name));
-
-                        if full_path.exists() {
-                            println!("✓ 快速扫描找到: {}", full_path.display());
-                            return Some(full_path);
-                        }
-                    }
-                }
-            }
-        }
-
-        // 第二轮: 如果 C/D 盘没找到,才扫描其他盘符
-        println!("扩展扫描: 其他驱动器...");
-        let all_drives = get_available_drives();
-        let other_drives: Vec<char> = all_drives
+        let other_drives = get_available_drives()
             .into_iter()
-            .filter(|d| !priority_drives.contains(d))
-            .collect();
-
-        for drive in other_drives {
-            for base_dir in &base_dirs {
-                for app_dir in &app_dirs {
-                    for exe_name in &exe_names {
-                        let full_path = PathBuf::from(format!(r"{}:\{}\{}\{}",
-                            drive, base_dir, app_dir, exe_name));
-
-                        if full_path.exists() {
-                            println!("✓ 扩展扫描找到: {}", full_path.display());
-                            return Some(full_path);
-                        }
-                    }
-                }
-            }
-        }
+            .filter(|d| !priority_drives.contains(d));
+        let drives: Vec<char> = priority_drives.iter().copied().chain(other_drives).collect();
 
-        println!("⚠ 扫描完成,未找到安装路径");
-        None
+        drives
+            .iter()
+            .flat_map(|drive| base_dirs.iter().map(move |base| PathBuf::from(format!(r"{}:\{}", drive, base))))
+            .collect()
     }
 
     /// 获取所有可用的驱动器盘符
@@ -409,53 +266,64 @@ pub mod platform {
 
     pub async fn spawn_multiple(req: SpawnRequest) -> std::result::Result<SpawnResponse, String> {
         let app_type = req.app_type.unwrap_or_default();
-        let exe = req.app_path.unwrap_or_else(|| get_default_app_path_by_type(app_type.clone()));
+        let exe = match req.app_path {
+            Some(path) => path,
+            None => get_default_app_path_by_type(app_type.clone())?,
+        };
 
         if !exe.exists() {
             return Err(format!("应用程序不存在: {:?}", exe));
         }
 
+        let config = crate::config::current();
+        if req.dry_run {
+            return Ok(SpawnResponse::planned(crate::plan::SpawnPlan::windows_mutex(&req, &exe, &config)));
+        }
+
+        // 与其他进程 (CLI、GUI、后台服务) 的启动互斥,避免同时关闭 Mutex
+        let _spawn_lock = crate::single_instance::lock_spawn().await?;
+
         // 根据应用类型选择 Mutex 名称
-        let mutex_name = match app_type {
-            AppType::WeCom => WECOM_MUTEX_NAME,
-            AppType::WeChat => WECHAT_MUTEX_NAME,
-        };
+        let mutex_name = discovery::AppProfile::for_type(&app_type).mutex_name;
 
         let app_name = match app_type {
             AppType::WeCom => "企业微信",
             AppType::WeChat => "微信",
         };
 
-        println!("准备启动 {} {} 个实例", app_name, req.count);
+        eprintln!("准备启动 {} {} 个实例", app_name, req.count);
 
         let mut pids = vec![];
         let mut success = 0;
         let mut failed = 0;
 
         for i in 0..req.count {
+            let instance_config = req.instance_configs.as_ref().and_then(|c| c.get(i as usize));
+
             // 关闭 Mutex
             match close_mutex(mutex_name) {
                 Ok(_) => {
-                    println!("✓ 成功关闭 Mutex,准备启动实例 {}", i + 1);
+                    eprintln!("✓ 成功关闭 Mutex,准备启动实例 {}", i + 1);
                 }
                 Err(e) => {
                     // 如果是第一个实例,mutex 不存在是正常的
                     if i == 0 {
-                        println!("⚠ 未找到 Mutex (可能是首次启动): {}", e);
+                        eprintln!("⚠ 未找到 Mutex (可能是首次启动): {}", e);
                     } else {
                         eprintln!("✗ 关闭 Mutex 失败: {}", e);
+                        crate::metrics::counters().record_mutex_close_failure();
                     }
                 }
             }
 
-            tokio::time::sleep(Duration::from_millis(100)).await;
+            tokio::time::sleep(Duration::from_millis(config.spawn.mutex_delay_ms)).await;
 
             // 启动进程
-            match launch_process(&exe) {
+            match launch_process(&exe, instance_config) {
                 Ok(pid) => {
                     pids.push(pid);
                     success += 1;
-                    println!("✓ 实例 {} 启动成功 (PID: {})", i + 1, pid);
+                    eprintln!("✓ 实例 {} 启动成功 (PID: {})", i + 1, pid);
                 }
                 Err(e) => {
                     eprintln!("✗ 启动实例 {} 失败: {}", i + 1, e);
@@ -464,7 +332,7 @@ pub mod platform {
             }
 
             if i < req.count - 1 {
-                tokio::time::sleep(Duration::from_millis(800)).await;
+                tokio::time::sleep(config.launch_interval(800)).await;
             }
         }
 
@@ -472,6 +340,7 @@ pub mod platform {
             pids,
             success,
             failed,
+            plan: None,
         })
     }
 
@@ -504,7 +373,7 @@ pub mod platform {
             let mut mutex_count = 0;
             let mut checked_count = 0;
 
-            println!("扫描系统句柄,总数: {}", info.NumberOfHandles);
+            eprintln!("扫描系统句柄,总数: {}", info.NumberOfHandles);
 
             // 收集所有 ObjectTypeIndex 的统计 (仅在第一次调试时使用)
             let debug_mode = false; // 生产环境设为 false
@@ -551,7 +420,7 @@ pub mod platform {
 
                             // 检查名称是否匹配目标 mutex
                             if obj_name.to_lowercase().contains(&target_name) {
-                                println!("找到目标 Mutex: {} (PID: {})", obj_name, h.UniqueProcessId);
+                                eprintln!("找到目标 Mutex: {} (PID: {})", obj_name, h.UniqueProcessId);
 
                                 // 关闭源进程中的句柄
                                 let mut h_temp = HANDLE::default();
@@ -568,7 +437,7 @@ pub mod platform {
                                 {
                                     let _ = CloseHandle(h_temp);
                                     closed_count += 1;
-                                    println!("✓ 已关闭 Mutex");
+                                    eprintln!("✓ 已关闭 Mutex");
                                 }
                             }
                         }
@@ -580,16 +449,16 @@ pub mod platform {
                 }
             }
 
-            println!("扫描完成 - 检查 {} 个Mutex句柄, 查询 {} 个名称, 关闭 {} 个",
+            eprintln!("扫描完成 - 检查 {} 个Mutex句柄, 查询 {} 个名称, 关闭 {} 个",
                 mutex_count, checked_count, closed_count);
 
             if debug_mode {
                 // 输出类型统计的前 10 个 (仅调试模式)
                 let mut sorted_types: Vec<_> = type_counts.iter().collect();
                 sorted_types.sort_by(|a, b| b.1.cmp(a.1));
-                println!("ObjectTypeIndex 统计 (前10):");
+                eprintln!("ObjectTypeIndex 统计 (前10):");
                 for (idx, (type_id, count)) in sorted_types.iter().take(10).enumerate() {
-                    println!("  {}. Type {}: {} 个句柄", idx + 1, type_id, count);
+                    eprintln!("  {}. Type {}: {} 个句柄", idx + 1, type_id, count);
                 }
             }
 
@@ -634,9 +503,38 @@ pub mod platform {
         }
     }
 
-    fn launch_process(exe: &PathBuf) -> std::result::Result<u32, String> {
+    fn launch_process(exe: &PathBuf, config: Option<&InstanceConfig>) -> std::result::Result<u32, String> {
         let wide: Vec<u16> = OsStr::new(exe).encode_wide().chain(Some(0)).collect();
 
+        // 命令行 (有额外参数时才需要)
+        let mut command_line: Vec<u16> = match config.filter(|c| !c.args.is_empty()) {
+            Some(c) => build_command_line(exe, &c.args).encode_utf16().chain(Some(0)).collect(),
+            None => Vec::new(),
+        };
+
+        // 环境变量块 (设置代理时才需要): KEY=VALUE\0...\0\0
+        let env_block: Option<Vec<u16>> = config.map(|c| c.proxy_env()).filter(|v| !v.is_empty()).map(|extra| {
+            // Windows 环境变量名不区分大小写,只保留小写形式
+            let extra: Vec<(String, String)> = extra.into_iter().filter(|(k, _)| k.to_lowercase() == *k).collect();
+            let mut vars: Vec<(String, String)> = std::env::vars()
+                .filter(|(k, _)| !extra.iter().any(|(e, _)| e.eq_ignore_ascii_case(k)))
+                .collect();
+            vars.extend(extra);
+            vars.sort_by_key(|(k, _)| k.to_uppercase());
+
+            let mut block: Vec<u16> = vars
+                .iter()
+                .flat_map(|(k, v)| format!("{}={}", k, v).encode_utf16().chain(Some(0)).collect::<Vec<_>>())
+                .collect();
+            block.push(0);
+            block
+        });
+
+        let mut flags = PROCESS_CREATION_FLAGS::default();
+        if env_block.is_some() {
+            flags |= CREATE_UNICODE_ENVIRONMENT;
+        }
+
         unsafe {
             let mut si = STARTUPINFOW::default();
             si.cb = mem::size_of::<STARTUPINFOW>() as u32;
@@ -645,12 +543,12 @@ pub mod platform {
 
             CreateProcessW(
                 PCWSTR::from_raw(wide.as_ptr()),
-                PWSTR::null(),
+                if command_line.is_empty() { PWSTR::null() } else { PWSTR(command_line.as_mut_ptr()) },
                 None,
                 None,
                 false,
-                PROCESS_CREATION_FLAGS::default(),
-                None,
+                flags,
+                env_block.as_ref().map(|b| b.as_ptr() as *const std::ffi::c_void),
                 PCWSTR::null(),
                 &si,
                 &mut pi,
@@ -665,6 +563,22 @@ pub mod platform {
         }
     }
 
+    /// 按 Windows 规则拼接命令行 (含空格或引号的参数加引号)
+    fn build_command_line(exe: &PathBuf, args: &[String]) -> String {
+        let mut line = format!("\"{}\"", exe.display());
+        for arg in args {
+            line.push(' ');
+            if arg.is_empty() || arg.contains([' ', '\t', '"']) {
+                line.push('"');
+                line.push_str(&arg.replace('"', "\\\""));
+                line.push('"');
+            } else {
+                line.push_str(arg);
+            }
+        }
+        line
+    }
+
     pub fn kill_process(pid: u32) -> std::result::Result<(), String> {
         unsafe {
             match OpenProcess(PROCESS_TERMINATE, false, pid) {
@@ -678,6 +592,21 @@ pub mod platform {
         }
     }
 
+    /// 请求进程正常退出 (向窗口发送关闭消息)
+    pub fn request_close(pid: u32) -> std::result::Result<(), String> {
+        let status = std::process::Command::new("taskkill")
+            .arg("/PID")
+            .arg(pid.to_string())
+            .status()
+            .map_err(|e| format!("请求进程退出失败: {}", e))?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format!("请求进程退出失败,退出码: {}", status.code().unwrap_or(-1)))
+        }
+    }
+
     pub fn process_exists(pid: u32) -> bool {
         unsafe {
             match OpenProcess(PROCESS_QUERY_INFORMATION, false, pid) {
@@ -700,27 +629,19 @@ pub mod platform {
         find_processes_by_type(AppType::WeChat)
     }
 
-    /// 根据应用类型查找进程
+    /// 根据应用类型查找进程 (仅返回每个实例的主进程)
     pub fn find_processes_by_type(app_type: AppType) -> Vec<u32> {
+        crate::discovery::discover(&app_type)
+            .into_iter()
+            .map(|instance| instance.pid)
+            .collect()
+    }
+
+    /// 枚举所有可访问进程的可执行文件路径
+    pub(crate) fn list_process_images() -> Vec<(u32, PathBuf)> {
         use windows::Win32::System::ProcessStatus::*;
 
-        let mut pids = Vec::new();
-
-        // 支持多种进程名称 (不同版本/语言)
-        let process_names = match app_type {
-            AppType::WeCom => vec![
-                "WXWork.exe",           // 老版本企业微信
-                "wxwork.exe",           // 中文版
-                "wecom.exe",            // 英文版
-                "wework.exe",           // 可能的别名
-                "企业微信.exe",          // 中文名
-            ],
-            AppType::WeChat => vec![
-                "wechat.exe",           // 标准名称
-                "weixin.exe",           // 微信拼音
-                "微信.exe",             // 中文名
-            ],
-        };
+        let mut images = Vec::new();
 
         unsafe {
             // 枚举所有进程
@@ -748,7 +669,7 @@ pub mod platform {
                         pid,
                     ) {
                         // 获取进程可执行文件路径
-                        let mut exe_path = vec![0u16; 260];
+                        let mut exe_path = vec![0u16; 512];
                         let mut size = exe_path.len() as u32;
 
                         if QueryFullProcessImageNameW(
@@ -760,15 +681,7 @@ pub mod platform {
                         .is_ok()
                         {
                             let path = String::from_utf16_lossy(&exe_path[..size as usize]);
-                            let path_lower = path.to_lowercase();
-
-                            // 检查是否匹配任一进程名
-                            for name in &process_names {
-                                if path_lower.contains(&name.to_lowercase()) {
-                                    pids.push(pid);
-                                    break;
-                                }
-                            }
+                            images.push((pid, PathBuf::from(path)));
                         }
 
                         let _ = CloseHandle(h_process);
@@ -777,7 +690,7 @@ pub mod platform {
             }
         }
 
-        pids
+        images
     }
 
     #[repr(C)]
@@ -834,28 +747,15 @@ pub mod platform {
     use super::*;
     use std::process::Command;
     use std::fs;
+    use std::path::Path;
 
-    pub fn get_default_app_path() -> PathBuf {
-        // 尝试多个可能的路径
-        let possible_paths = vec![
-            "/Applications/企业微信.app",
-            "/Applications/WeCom.app",
-        ];
-
-        for path in possible_paths {
-            let p = PathBuf::from(path);
-            if p.exists() {
-                return p;
-            }
-        }
-
-        // 默认返回中文版路径
-        PathBuf::from("/Applications/企业微信.app")
+    pub fn get_default_app_path() -> std::result::Result<PathBuf, String> {
+        crate::path_resolver::resolve_app_path(&AppType::WeCom)
     }
 
     fn get_instances_dir() -> PathBuf {
         let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
-        PathBuf::from(format!("{}/Applications/WeComMulti", home))
+        PathBuf::from(home).join(crate::discovery::MACOS_CLONE_DIR)
     }
 
     fn create_app_instance(source_app: &PathBuf, instance_id: u8) -> std::result::Result<PathBuf, String> {
@@ -865,14 +765,9 @@ pub mod platform {
         fs::create_dir_all(&instances_dir)
             .map_err(|e| format!("创建实例目录失败: {}", e))?;
 
-        // 获取应用名称
-        let app_name = source_app
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .ok_or("无法获取应用名称")?;
-
         // 创建实例路径
-        let instance_path = instances_dir.join(format!("{}{}.app", app_name, instance_id));
+        let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
+        let instance_path = crate::discovery::macos_clone_path(Path::new(&home), source_app, instance_id);
 
         // 如果实例已存在,先删除
         if instance_path.exists() {
@@ -880,7 +775,7 @@ pub mod platform {
                 .map_err(|e| format!("删除旧实例失败: {}", e))?;
         }
 
-        println!("正在克隆应用到: {}", instance_path.display());
+        eprintln!("正在克隆应用到: {}", instance_path.display());
 
         // 复制应用
         let status = Command::new("cp")
@@ -896,9 +791,9 @@ pub mod platform {
 
         // 修改 Info.plist 中的 Bundle ID
         let plist_path = instance_path.join("Contents/Info.plist");
-        let new_bundle_id = format!("com.tencent.WeWorkMac.instance{}", instance_id);
+        let new_bundle_id = crate::discovery::macos_bundle_id(instance_id);
 
-        println!("正在修改 Bundle ID: {}", new_bundle_id);
+        eprintln!("正在修改 Bundle ID: {}", new_bundle_id);
 
         let status = Command::new("/usr/libexec/PlistBuddy")
             .arg("-c")
@@ -912,14 +807,14 @@ pub mod platform {
         }
 
         // 清除隔离属性
-        println!("正在清除隔离属性...");
+        eprintln!("正在清除隔离属性...");
         let _ = Command::new("/usr/bin/xattr")
             .arg("-rc")
             .arg(&instance_path)
             .status();
 
         // 重新签名
-        println!("正在重新签名...");
+        eprintln!("正在重新签名...");
         let _ = Command::new("codesign")
             .arg("--force")
             .arg("--deep")
@@ -933,31 +828,49 @@ pub mod platform {
     }
 
     pub async fn spawn_multiple(req: SpawnRequest) -> std::result::Result<SpawnResponse, String> {
-        let source_app = req.app_path.unwrap_or(get_default_app_path());
+        let source_app = match req.app_path {
+            Some(path) => path,
+            None => get_default_app_path()?,
+        };
 
         if !source_app.exists() {
             return Err(format!("应用程序不存在: {:?}", source_app));
         }
 
+        if req.dry_run {
+            let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
+            let plan = crate::plan::SpawnPlan::macos_clone(&req, &source_app, Path::new(&home), &crate::config::current());
+            return Ok(SpawnResponse::planned(plan));
+        }
+
+        // 与其他进程的启动互斥,避免克隆包编号冲突
+        let _spawn_lock = crate::single_instance::lock_spawn().await?;
+
         let mut pids = vec![];
         let mut success = 0;
         let mut failed = 0;
 
         for i in 0..req.count {
+            // 实例配置 (重启或指定数据目录时使用)
+            let instance_config = req.instance_configs.as_ref().and_then(|c| c.get(i as usize));
+            let instance_id = instance_config.and_then(|c| c.instance_id).unwrap_or(i + 1);
+
             // 为每个实例创建独立的应用副本
-            match create_app_instance(&source_app, i + 1) {
+            match create_app_instance(&source_app, instance_id) {
                 Ok(instance_path) => {
                     // 启动实例 - 使用直接启动可执行文件的方式,更稳定
                     tokio::time::sleep(std::time::Duration::from_millis(300)).await;
 
                     // 为每个实例创建独立的数据目录
                     let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
-                    let instance_home = PathBuf::from(format!("{}/Library/Containers/WeComInstance{}", home, i + 1));
+                    let instance_home = instance_config
+                        .and_then(|c| c.data_dir.clone())
+                        .unwrap_or_else(|| crate::discovery::macos_data_dir(&PathBuf::from(home), instance_id));
 
                     // 创建实例专用的数据目录
                     if !instance_home.exists() {
                         let _ = fs::create_dir_all(&instance_home);
-                        println!("创建实例 {} 数据目录: {}", i + 1, instance_home.display());
+                        eprintln!("创建实例 {} 数据目录: {}", i + 1, instance_home.display());
                     }
 
                     // 获取可执行文件路径
@@ -977,11 +890,9 @@ pub mod platform {
                     let _ = fs::create_dir_all(&instance_documents);
 
                     let launch_result = Command::new(&executable_path)
-                        .env("HOME", &instance_home)
-                        .env("TMPDIR", format!("{}/tmp", instance_home.display()))
-                        .env("XDG_CONFIG_HOME", format!("{}/config", instance_home.display()))
-                        .env("XDG_DATA_HOME", format!("{}/data", instance_home.display()))
-                        .env("XDG_CACHE_HOME", format!("{}/cache", instance_home.display()))
+                        .args(instance_config.map(|c| c.args.as_slice()).unwrap_or_default())
+                        .envs(crate::discovery::macos_instance_env(&instance_home))
+                        .envs(instance_config.map(|c| c.proxy_env()).unwrap_or_default())
                         .spawn();
 
                     match launch_result {
@@ -989,7 +900,7 @@ pub mod platform {
                             let pid = child.id();
                             pids.push(pid);
                             success += 1;
-                            println!("✓ 实例 {} 启动成功 (PID: {}, 数据目录: {})", i + 1, pid, instance_home.display());
+                            eprintln!("✓ 实例 {} 启动成功 (PID: {}, 数据目录: {})", i + 1, pid, instance_home.display());
                         }
                         Err(e) => {
                             // 方式2: 如果直接启动失败,尝试使用 open -n
@@ -1002,7 +913,7 @@ pub mod platform {
                                 Ok(child) => {
                                     pids.push(child.id());
                                     success += 1;
-                                    println!("✓ 实例 {} 使用 open 命令启动成功: {}", i + 1, instance_path.display());
+                                    eprintln!("✓ 实例 {} 使用 open 命令启动成功: {}", i + 1, instance_path.display());
                                 }
                                 Err(e2) => {
                                     eprintln!("✗ 启动实例 {} 失败: {}", i + 1, e2);
@@ -1019,7 +930,7 @@ pub mod platform {
             }
 
             if i < req.count - 1 {
-                tokio::time::sleep(std::time::Duration::from_millis(1000)).await;
+                tokio::time::sleep(crate::config::current().launch_interval(1000)).await;
             }
         }
 
@@ -1027,6 +938,7 @@ pub mod platform {
             pids,
             success,
             failed,
+            plan: None,
         })
     }
 
@@ -1039,33 +951,114 @@ pub mod platform {
             .map_err(|e| format!("终止进程失败: {}", e))
     }
 
+    /// 请求进程正常退出 (SIGTERM)
+    pub fn request_close(pid: u32) -> std::result::Result<(), String> {
+        Command::new("kill")
+            .arg("-TERM")
+            .arg(pid.to_string())
+            .output()
+            .map(|_| ())
+            .map_err(|e| format!("请求进程退出失败: {}", e))
+    }
+
     pub fn process_exists(pid: u32) -> bool {
+        // 已退出但未被回收的子进程 (状态 Z) 视为不存在
         Command::new("ps")
+            .arg("-o")
+            .arg("stat=")
             .arg("-p")
             .arg(pid.to_string())
             .output()
-            .map(|output| output.status.success())
+            .map(|output| {
+                output.status.success() && !String::from_utf8_lossy(&output.stdout).trim_start().starts_with('Z')
+            })
             .unwrap_or(false)
     }
+
+    /// 根据应用类型查找进程 (仅返回每个实例的主进程)
+    pub fn find_processes_by_type(app_type: AppType) -> Vec<u32> {
+        crate::discovery::discover(&app_type)
+            .into_iter()
+            .map(|instance| instance.pid)
+            .collect()
+    }
 }
 
 #[cfg(not(any(target_os = "windows", target_os = "macos")))]
 pub mod platform {
     use super::*;
 
-    pub fn get_default_app_path() -> PathBuf {
-        PathBuf::from("")
+    pub fn get_default_app_path() -> std::result::Result<PathBuf, String> {
+        crate::path_resolver::resolve_app_path(&AppType::WeCom)
     }
 
     pub async fn spawn_multiple(_req: SpawnRequest) -> std::result::Result<SpawnResponse, String> {
         Err("此平台不支持".to_string())
     }
 
+    #[cfg(target_os = "linux")]
+    pub fn kill_process(pid: u32) -> std::result::Result<(), String> {
+        let status = std::process::Command::new("kill")
+            .arg("-9")
+            .arg(pid.to_string())
+            .status()
+            .map_err(|e| format!("终止进程失败: {}", e))?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format!("终止进程失败,退出码: {}", status.code().unwrap_or(-1)))
+        }
+    }
+
+    /// 请求进程正常退出 (SIGTERM)
+    #[cfg(target_os = "linux")]
+    pub fn request_close(pid: u32) -> std::result::Result<(), String> {
+        let status = std::process::Command::new("kill")
+            .arg("-TERM")
+            .arg(pid.to_string())
+            .status()
+            .map_err(|e| format!("请求进程退出失败: {}", e))?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format!("请求进程退出失败,退出码: {}", status.code().unwrap_or(-1)))
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn process_exists(pid: u32) -> bool {
+        // 僵尸进程 (状态 Z) 视为不存在
+        match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+            Ok(stat) => stat
+                .rfind(')')
+                .and_then(|i| stat[i + 1..].split_whitespace().next())
+                .is_some_and(|state| state != "Z"),
+            Err(_) => false,
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
     pub fn kill_process(_pid: u32) -> std::result::Result<(), String> {
         Err("此平台不支持".to_string())
     }
 
+    #[cfg(not(target_os = "linux"))]
+    pub fn request_close(_pid: u32) -> std::result::Result<(), String> {
+        Err("此平台不支持".to_string())
+    }
+
+    #[cfg(not(target_os = "linux"))]
     pub fn process_exists(_pid: u32) -> bool {
         false
     }
+
+    /// 根据应用类型查找进程 (仅返回每个实例的主进程)
+    pub fn find_processes_by_type(app_type: AppType) -> Vec<u32> {
+        crate::discovery::discover(&app_type)
+            .into_iter()
+            .map(|instance| instance.pid)
+            .collect()
+    }
 }
@@ -1,50 +1,1018 @@
 // CLI 版本 - 命令行多开工具
+//
+// 结果输出到标准输出,进度和诊断信息输出到标准错误;--json 时标准输出只包含 JSON。
+mod cli;
+
+use cli::{Cli, CliError, Command, DaemonAction, ErrorKind, SpawnArgs};
+use serde::Serialize;
+use serde_json::json;
 use std::env;
-use wecom_multi_open::{platform, SpawnRequest};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use wecom_multi_open::config::{self, Config, LogLevel};
+use wecom_multi_open::config_watch::{self, ConfigReloader};
+use wecom_multi_open::discovery::{self, DiscoveredInstance};
+use wecom_multi_open::doctor;
+use wecom_multi_open::hooks::HookEvent;
+use wecom_multi_open::http_api::HttpApi;
+use wecom_multi_open::monitor::{format_bytes, ResourceMonitor};
+use wecom_multi_open::plan::SpawnPlan;
+use wecom_multi_open::path_resolver::{self, PathResolver};
+use wecom_multi_open::profiles::{self, AccountProfile, ProfileStore};
+use wecom_multi_open::daemon::{self, Daemon, InstanceStore, PlatformBackend};
+use wecom_multi_open::registry::{InstanceRecord, InstanceRegistry};
+use wecom_multi_open::single_instance;
+use wecom_multi_open::scheduler::{self, ScheduleAction, ScheduleRule, Scheduler, SystemClock};
+use wecom_multi_open::{platform, AppType, InstanceConfig, IsolationMode, SpawnRequest, SpawnResponse};
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // 从命令行参数获取启动数量
-    let count = env::args()
-        .nth(1)
-        .and_then(|s| s.parse::<u8>().ok())
-        .unwrap_or(3);
+async fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let mut cli = match cli::parse(&args) {
+        Ok(cli) => cli,
+        Err(e) => {
+            let e = CliError::new(ErrorKind::Usage, e);
+            report_error(&e, args.iter().any(|a| a == "--json"));
+            std::process::exit(e.kind.exit_code());
+        }
+    };
+
+    // 从脚本或计划任务运行时不等待回车
+    if let Command::Spawn(spawn) = &mut cli.command {
+        spawn.wait &= std::io::stdin().is_terminal();
+    }
+    // 旧版用法 (双击运行) 出错时同样等待回车,避免窗口直接关闭
+    let wait = matches!(&cli.command, Command::Spawn(SpawnArgs { wait: true, .. }));
+    let json = cli.json;
+
+    if let Err(e) = run(cli).await {
+        report_error(&e, json);
+        if wait {
+            wait_for_enter();
+        }
+        std::process::exit(e.kind.exit_code());
+    }
+}
+
+fn report_error(e: &CliError, json: bool) {
+    if json {
+        if !e.reported {
+            print_json(&json!({ "error": e }));
+        }
+    } else if e.kind == ErrorKind::Partial {
+        eprintln!("⚠ {}", e.message);
+    } else {
+        eprintln!("✗ {}", e.message);
+    }
+}
+
+fn print_json<T: Serialize + ?Sized>(value: &T) {
+    match serde_json::to_string_pretty(value) {
+        Ok(text) => println!("{}", text),
+        Err(e) => eprintln!("✗ 序列化结果失败: {}", e),
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), CliError> {
+    let json = cli.json;
+
+    // 帮助信息不依赖配置文件,配置有误时也能查看
+    let config = match cli.command {
+        Command::Help | Command::Version => Config::default(),
+        _ => Config::load(&cli.overrides).map_err(|e| CliError::new(ErrorKind::Usage, e))?,
+    };
+    config::install(config.clone());
+
+    match cli.command {
+        Command::Spawn(args) => run_spawn(&config, args, json).await,
+        Command::List { all } => run_list(all, json).await,
+        Command::Kill { targets } => run_kill(&targets, json).await,
+        Command::KillAll { all } => run_kill_all(all, json).await,
+        Command::Status => run_status(&config, json).await,
+        Command::Profile(args) => run_profile(&args, json).await,
+        Command::Paths { clear_cache } => run_paths(&config, clear_cache, json),
+        Command::Doctor { paths } => run_doctor(&config, paths, json),
+        Command::Clean { sandboxes } => run_clean(sandboxes, json).await,
+        Command::Daemon(action) => run_daemon(&config, &cli.overrides, action, json).await,
+        Command::Top { interval, once } => {
+            run_top(interval, once, json).await;
+            Ok(())
+        }
+        Command::Config => {
+            if !json {
+                println!("# {}", Config::default_path().display());
+            }
+            print_json(&config);
+            Ok(())
+        }
+        Command::Help => {
+            println!("{}", cli::USAGE);
+            Ok(())
+        }
+        Command::Version => {
+            if json {
+                print_json(&json!({ "version": env!("CARGO_PKG_VERSION") }));
+            } else {
+                println!("wecom-multi-open-cli {}", env!("CARGO_PKG_VERSION"));
+            }
+            Ok(())
+        }
+    }
+}
+
+fn app_name(app_type: &AppType) -> &'static str {
+    match app_type {
+        AppType::WeCom => "企业微信",
+        AppType::WeChat => "微信",
+    }
+}
+
+fn wait_for_enter() {
+    println!("\n按 Enter 键退出...");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input).ok();
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// 加载实例注册表,并移除已退出的实例
+/// 正在运行但不在注册表中的实例 (手动打开或由其他工具启动)
+fn unmanaged_instances(managed: &[InstanceRecord]) -> Vec<DiscoveredInstance> {
+    [AppType::WeCom, AppType::WeChat]
+        .iter()
+        .flat_map(discovery::discover)
+        .filter(|instance| managed.iter().all(|r| r.pid != instance.pid))
+        .collect()
+}
+
+/// 启动实例
+async fn run_spawn(config: &Config, args: SpawnArgs, json: bool) -> Result<(), CliError> {
+    let app_type = config.app.clone();
+
+    if args.dry_run {
+        let plan = plan_spawn(config, &args).await?;
+        if json {
+            print_json(&plan);
+        } else {
+            println!("{}", plan.render());
+        }
+        return Ok(());
+    }
+
+    if !json && config.log_enabled(LogLevel::Info) {
+        println!("{}多开工具 v{}", app_name(&app_type), env!("CARGO_PKG_VERSION"));
+        println!("准备启动 {} 个实例...\n", config.count);
+    }
+
+    // 由后台服务或实例注册表记录,GUI 启动后可继续管理
+    let mut store = InstanceStore::open().await?;
+    let pids = match config.isolation {
+        IsolationMode::Simple => spawn_simple(config, &args, &mut store).await,
+        IsolationMode::Sandboxie => spawn_sandboxie(config, &args, &mut store).await,
+    }
+    .map_err(|mut e| {
+        e.message = format!("启动失败: {}", e.message);
+        if !json && e.kind == ErrorKind::Failed {
+            e.message.push_str("\n\n可能的原因:\n1. 需要管理员权限运行 (Windows)\n2. 系统资源不足");
+        }
+        e
+    })?;
+
+    let response = SpawnResponse {
+        success: pids.len(),
+        failed: (config.count as usize).saturating_sub(pids.len()),
+        pids,
+        plan: None,
+    };
+    if response.success == 0 {
+        return Err(CliError::new(ErrorKind::Failed, "没有实例启动成功"));
+    }
+
+    if json {
+        print_json(&response);
+    } else {
+        println!("\n✓ 成功启动 {} 个实例!", response.success);
+        if response.failed > 0 {
+            println!("✗ 失败 {} 个实例", response.failed);
+        }
+        println!("进程 PID: {:?}", response.pids);
+    }
+
+    if args.wait {
+        println!("\n提示:");
+        println!("- 每个窗口可以登录不同的{}账号", app_name(&app_type));
+        println!("- 运行 wecom-multi-open-cli list 查看实例, kill <PID> 结束实例");
+        wait_for_enter();
+    }
+
+    if response.failed > 0 {
+        return Err(CliError::new(ErrorKind::Partial, format!("{} 个实例启动失败", response.failed)).reported());
+    }
+    Ok(())
+}
+
+/// 第 index 个实例 (从 1 开始) 的数据目录: 只启动一个实例时直接使用指定目录
+fn instance_data_dir(base: &Path, index: u8, count: u8) -> PathBuf {
+    if count == 1 {
+        base.to_path_buf()
+    } else {
+        base.join(format!("instance-{}", index))
+    }
+}
+
+/// 按配置生成启动计划,不启动实例
+async fn plan_spawn(config: &Config, args: &SpawnArgs) -> Result<SpawnPlan, CliError> {
+    match config.isolation {
+        IsolationMode::Simple => {
+            let mut req = simple_request(config, args)?;
+            req.dry_run = true;
+            platform::spawn_multiple(req)
+                .await?
+                .plan
+                .ok_or_else(|| CliError::new(ErrorKind::Unsupported, "当前平台不支持生成启动计划"))
+        }
+        IsolationMode::Sandboxie => sandboxie_plan(config),
+    }
+}
+
+/// 简单模式的启动请求
+fn simple_request(config: &Config, args: &SpawnArgs) -> Result<SpawnRequest, CliError> {
+    if !cfg!(any(target_os = "windows", target_os = "macos")) {
+        return Err(CliError::new(ErrorKind::Unsupported, "当前平台不支持启动实例"));
+    }
+
+    let count = config.count;
+    let app_path = match &args.app_path {
+        Some(path) if !path.exists() => {
+            return Err(CliError::new(ErrorKind::NotFound, format!("应用程序不存在: {}", path.display())))
+        }
+        Some(path) => path.clone(),
+        None => path_resolver::resolve_app_path(&config.app).map_err(|e| CliError::new(ErrorKind::NotFound, e))?,
+    };
 
-    println!("企业微信多开工具 v0.2.0");
-    println!("准备启动 {} 个实例...\n", count);
+    let customized = args.data_dir.is_some() || args.proxy.is_some() || !args.args.is_empty();
+    let instance_configs: Option<Vec<InstanceConfig>> = customized.then(|| {
+        (1..=count)
+            .map(|i| InstanceConfig {
+                data_dir: args.data_dir.as_deref().map(|dir| instance_data_dir(dir, i, count)),
+                proxy: args.proxy.clone(),
+                instance_id: None,
+                args: args.args.clone(),
+            })
+            .collect()
+    });
 
-    let req = SpawnRequest {
+    Ok(SpawnRequest {
         count,
-        app_path: None,
-        app_type: None,           // 默认企业微信
-        instance_configs: None,   // CLI不使用实例配置
+        app_path: Some(app_path),
+        app_type: Some(config.app.clone()),
+        instance_configs,
+        dry_run: false,
+    })
+}
+
+async fn spawn_simple(config: &Config, args: &SpawnArgs, store: &mut InstanceStore) -> Result<Vec<u32>, CliError> {
+    let req = simple_request(config, args)?;
+    for dir in req.instance_configs.iter().flatten().filter_map(|c| c.data_dir.as_ref()) {
+        std::fs::create_dir_all(dir).map_err(|e| format!("创建数据目录失败 {}: {}", dir.display(), e))?;
+    }
+
+    Ok(store.spawn(req).await?.pids)
+}
+
+#[cfg(target_os = "windows")]
+async fn spawn_sandboxie(config: &Config, args: &SpawnArgs, store: &mut InstanceStore) -> Result<Vec<u32>, CliError> {
+    use wecom_multi_open::wecom_manager::WeComManager;
+
+    if config.app != AppType::WeCom {
+        return Err(CliError::new(ErrorKind::Unsupported, "Sandboxie 隔离目前仅支持企业微信"));
+    }
+    if args.data_dir.is_some() || args.proxy.is_some() || args.app_path.is_some() || !args.args.is_empty() {
+        eprintln!("⚠ Sandboxie 模式下忽略 --app-path / --data-dir / --proxy / --arg,如需单独设置请使用账号配置 (profile)");
+    }
+
+    let manager = WeComManager::new()
+        .map_err(|e| CliError::new(ErrorKind::NotFound, format!("无法初始化 Sandboxie: {}", e)))?;
+    let count = config.count;
+    let instances = tokio::task::spawn_blocking(move || manager.spawn_multiple(count))
+        .await
+        .map_err(|e| e.to_string())??;
+
+    let records: Vec<InstanceRecord> = instances.iter().filter_map(|instance| instance.record()).collect();
+    let pids = records.iter().map(|r| r.pid).collect();
+    if let Err(e) = store.add(records).await {
+        eprintln!("⚠ 记录实例失败: {}", e);
+    }
+    Ok(pids)
+}
+
+#[cfg(not(target_os = "windows"))]
+async fn spawn_sandboxie(_config: &Config, _args: &SpawnArgs, _store: &mut InstanceStore) -> Result<Vec<u32>, CliError> {
+    Err(CliError::new(ErrorKind::Unsupported, "Sandboxie 隔离仅支持 Windows 平台"))
+}
+
+#[cfg(target_os = "windows")]
+fn sandboxie_plan(config: &Config) -> Result<SpawnPlan, CliError> {
+    use wecom_multi_open::wecom_manager::WeComManager;
+
+    if config.app != AppType::WeCom {
+        return Err(CliError::new(ErrorKind::Unsupported, "Sandboxie 隔离目前仅支持企业微信"));
+    }
+    let manager = WeComManager::new()
+        .map_err(|e| CliError::new(ErrorKind::NotFound, format!("无法初始化 Sandboxie: {}", e)))?;
+    Ok(manager.plan(config.count)?)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn sandboxie_plan(_config: &Config) -> Result<SpawnPlan, CliError> {
+    Err(CliError::new(ErrorKind::Unsupported, "Sandboxie 隔离仅支持 Windows 平台"))
+}
+
+/// 列出实例
+async fn run_list(all: bool, json: bool) -> Result<(), CliError> {
+    let store = InstanceStore::open().await?;
+    let instances = store.instances();
+    let unmanaged = if all { unmanaged_instances(instances) } else { Vec::new() };
+
+    if json {
+        print_json(&json!({ "instances": instances, "unmanaged": unmanaged }));
+        return Ok(());
+    }
+
+    if instances.is_empty() && unmanaged.is_empty() {
+        println!("没有运行中的实例");
+        return Ok(());
+    }
+
+    println!(
+        "{:<16}  {:>8}  {:<8}  {:<10}  {:<12}  {:>10}  数据目录",
+        "ID", "PID", "应用", "方式", "账号", "运行时长"
+    );
+    let now = now_secs();
+    for r in instances {
+        println!(
+            "{:<16}  {:>8}  {:<8}  {:<10}  {:<12}  {:>10}  {}",
+            r.id,
+            r.pid,
+            app_name(&r.app_type),
+            format!("{:?}", r.backend),
+            r.profile.as_deref().unwrap_or("-"),
+            format_uptime(now.saturating_sub(r.started_at)),
+            r.data_dir.as_ref().map(|d| d.display().to_string()).unwrap_or_else(|| "-".to_string()),
+        );
+    }
+    for instance in &unmanaged {
+        println!(
+            "{:<16}  {:>8}  {:<8}  {:<10}  {:<12}  {:>10}  {}",
+            "-",
+            instance.pid,
+            app_name(&instance.app_type),
+            "外部",
+            "-",
+            "-",
+            instance.data_dir.as_ref().map(|d| d.display().to_string()).unwrap_or_else(|| "-".to_string()),
+        );
+    }
+    Ok(())
+}
+
+/// 单个实例结束失败
+#[derive(Debug, Serialize)]
+struct KillFailure {
+    target: String,
+    error: CliError,
+}
+
+/// 输出 kill / kill-all 的结果,返回对应的错误
+fn finish_kill(killed: Vec<u32>, failures: Vec<KillFailure>, json: bool) -> Result<(), CliError> {
+    // 全部失败时只报告错误
+    if killed.is_empty() && !failures.is_empty() {
+        let kind = if failures.iter().all(|f| f.error.kind == ErrorKind::NotFound) {
+            ErrorKind::NotFound
+        } else {
+            ErrorKind::Failed
+        };
+        let messages: Vec<&str> = failures.iter().map(|f| f.error.message.as_str()).collect();
+        return Err(CliError::new(kind, messages.join("\n")));
+    }
+
+    if json {
+        print_json(&json!({ "killed": killed, "failed": failures }));
+    } else {
+        println!("✓ 已结束 {} 个实例", killed.len());
+        for f in &failures {
+            eprintln!("✗ {}", f.error.message);
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(CliError::new(ErrorKind::Partial, format!("{} 个实例结束失败", failures.len())).reported())
+    }
+}
+
+/// 结束指定实例 (注册表 ID 或 PID)
+async fn run_kill(targets: &[String], json: bool) -> Result<(), CliError> {
+    let mut store = InstanceStore::open().await?;
+    let mut killed = Vec::new();
+    let mut failures = Vec::new();
+
+    for target in targets {
+        let pid = target.parse::<u32>().ok();
+        let record = store
+            .instances()
+            .iter()
+            .find(|r| r.id == *target || Some(r.pid) == pid)
+            .cloned();
+
+        let result = match (record, pid) {
+            (Some(record), _) => store.kill(record.pid).await.map(|_| record.pid),
+            // 不在注册表中的 PID 只允许结束企业微信/微信进程,避免误杀其他程序
+            (None, Some(pid)) if unmanaged_instances(store.instances()).iter().any(|i| i.pid == pid) => {
+                platform::kill_process(pid).map(|_| pid)
+            }
+            (None, _) => {
+                failures.push(KillFailure {
+                    target: target.clone(),
+                    error: CliError::new(ErrorKind::NotFound, format!("未找到实例: {}", target)),
+                });
+                continue;
+            }
+        };
+
+        match result {
+            Ok(pid) => {
+                if !json {
+                    println!("✓ 已结束实例 {} (PID: {})", target, pid);
+                }
+                killed.push(pid);
+            }
+            Err(e) => failures.push(KillFailure {
+                target: target.clone(),
+                error: e.into(),
+            }),
+        }
+    }
+
+    finish_kill(killed, failures, json)
+}
+
+/// 结束全部实例
+async fn run_kill_all(all: bool, json: bool) -> Result<(), CliError> {
+    let mut store = InstanceStore::open().await?;
+    let managed: Vec<u32> = store.instances().iter().map(|r| r.pid).collect();
+    let unmanaged: Vec<u32> = if all {
+        unmanaged_instances(store.instances()).iter().map(|i| i.pid).collect()
+    } else {
+        Vec::new()
     };
 
-    match platform::spawn_multiple(req).await {
-        Ok(response) => {
-            println!("\n✓ 成功启动 {} 个实例!", response.success);
-            if response.failed > 0 {
-                println!("✗ 失败 {} 个实例", response.failed);
+    let mut killed = Vec::new();
+    let mut failures = Vec::new();
+    for pid in managed {
+        match store.kill(pid).await {
+            Ok(()) => killed.push(pid),
+            Err(e) => failures.push(KillFailure {
+                target: pid.to_string(),
+                error: format!("结束 PID {} 失败: {}", pid, e).into(),
+            }),
+        }
+    }
+    for pid in unmanaged {
+        match platform::kill_process(pid) {
+            Ok(()) => killed.push(pid),
+            Err(e) => failures.push(KillFailure {
+                target: pid.to_string(),
+                error: format!("结束 PID {} 失败: {}", pid, e).into(),
+            }),
+        }
+    }
+
+    finish_kill(killed, failures, json)
+}
+
+/// 查看运行状态
+async fn run_status(config: &Config, json: bool) -> Result<(), CliError> {
+    let config_path = Config::default_path();
+    let mut resolver = PathResolver::standard(config);
+    let wecom = resolver.resolve(&AppType::WeCom).map(|r| r.path);
+    let wechat = resolver.resolve(&AppType::WeChat).map(|r| r.path);
+    let store = InstanceStore::open().await?;
+    let managed = store.instances().len();
+    let unmanaged = unmanaged_instances(store.instances()).len();
+    let gui = single_instance::is_locked(&single_instance::gui_lock_path());
+
+    #[cfg(target_os = "windows")]
+    let sandboxie = Some(wecom_multi_open::windows_sandbox::SandboxieManager::new().is_ok());
+    #[cfg(not(target_os = "windows"))]
+    let sandboxie: Option<bool> = None;
+
+    if json {
+        print_json(&json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "config_path": config_path,
+            "config_exists": config_path.exists(),
+            "app": config.app,
+            "count": config.count,
+            "isolation": config.isolation,
+            "app_paths": { "wecom": wecom, "wechat": wechat },
+            "managed": managed,
+            "unmanaged": unmanaged,
+            "daemon": store.is_daemon(),
+            "gui": gui,
+            "sandboxie": sandboxie,
+        }));
+        return Ok(());
+    }
+
+    println!("版本:       {}", env!("CARGO_PKG_VERSION"));
+    println!(
+        "配置文件:   {}{}",
+        config_path.display(),
+        if config_path.exists() { "" } else { " (不存在,使用默认配置)" }
+    );
+    println!(
+        "默认设置:   {} × {}, 隔离方式 {:?}",
+        app_name(&config.app),
+        config.count,
+        config.isolation
+    );
+    for (app_type, path) in [(AppType::WeCom, wecom), (AppType::WeChat, wechat)] {
+        let path = path.map(|p| p.display().to_string()).unwrap_or_else(|| "未找到".to_string());
+        println!("{}路径: {}", app_name(&app_type), path);
+    }
+    println!("运行实例:   {} 个由本工具管理, {} 个外部实例", managed, unmanaged);
+    println!("后台服务:   {}", if store.is_daemon() { "运行中" } else { "未运行" });
+    println!("图形界面:   {}", if gui { "运行中" } else { "未运行" });
+    if let Some(available) = sandboxie {
+        println!("Sandboxie:  {}", if available { "可用" } else { "未安装" });
+    }
+
+    Ok(())
+}
+
+/// 查看检测到的应用路径
+fn run_paths(config: &Config, clear_cache: bool, json: bool) -> Result<(), CliError> {
+    if clear_cache {
+        path_resolver::clear_cache()?;
+        if !json {
+            println!("✓ 已清除路径缓存");
+        }
+    }
+
+    let mut resolver = PathResolver::standard(config);
+    let results: Vec<_> = [AppType::WeCom, AppType::WeChat]
+        .into_iter()
+        .map(|app_type| {
+            let resolution = resolver.resolve(&app_type);
+            (app_type, resolution)
+        })
+        .collect();
+
+    if json {
+        let entries: Vec<_> = results
+            .iter()
+            .map(|(app_type, resolution)| json!({ "app_type": app_type, "resolution": resolution }))
+            .collect();
+        print_json(&entries);
+    } else {
+        for (app_type, resolution) in &results {
+            match resolution {
+                Some(resolution) => println!(
+                    "{}: {} (来源: {}{})",
+                    app_name(app_type),
+                    resolution.path.display(),
+                    resolution.source,
+                    if resolution.cached { ", 缓存" } else { "" }
+                ),
+                None => println!("{}: 未找到 (运行 doctor 查看详情)", app_name(app_type)),
             }
-            println!("进程 PID: {:?}", response.pids);
-            println!("\n提示:");
-            println!("- 每个窗口可以登录不同的企业微信账号");
-            println!("- 关闭窗口或在任务管理器中结束进程即可退出");
-            println!("\n按 Enter 键退出...");
-            let mut input = String::new();
-            std::io::stdin().read_line(&mut input).ok();
         }
-        Err(e) => {
-            eprintln!("✗ 启动失败: {}", e);
-            eprintln!("\n可能的原因:");
-            eprintln!("1. 企业微信未安装或路径不正确");
-            eprintln!("2. 需要管理员权限运行 (Windows)");
-            eprintln!("3. 系统资源不足");
-            eprintln!("\n按 Enter 键退出...");
-            let mut input = String::new();
-            std::io::stdin().read_line(&mut input).ok();
+    }
+
+    if results.iter().any(|(app_type, resolution)| *app_type == config.app && resolution.is_none()) {
+        return Err(CliError::new(ErrorKind::NotFound, format!("未找到{}", app_name(&config.app))).reported());
+    }
+    Ok(())
+}
+
+/// 环境自检
+fn run_doctor(config: &Config, paths: bool, json: bool) -> Result<(), CliError> {
+    if paths {
+        return run_path_report(config, json);
+    }
+
+    let report = doctor::run(config);
+    if json {
+        print_json(&report);
+    } else {
+        println!("{}", report.render());
+    }
+
+    if report.status == doctor::Status::Fail {
+        return Err(CliError::new(ErrorKind::Failed, "环境检查未通过").reported());
+    }
+    Ok(())
+}
+
+/// 路径检测诊断
+fn run_path_report(config: &Config, json: bool) -> Result<(), CliError> {
+    let reports: Vec<_> = [AppType::WeCom, AppType::WeChat]
+        .iter()
+        .map(path_resolver::diagnose)
+        .collect();
+
+    if json {
+        print_json(&reports);
+    } else {
+        for report in &reports {
+            println!("{}", report.render());
+        }
+    }
+
+    if reports.iter().any(|r| r.app_type == config.app && r.selected.is_none()) {
+        return Err(CliError::new(ErrorKind::NotFound, format!("未找到{}", app_name(&config.app))).reported());
+    }
+    Ok(())
+}
+
+/// 清理失效的注册表记录、路径缓存和不再使用的应用副本
+async fn run_clean(sandboxes: bool, json: bool) -> Result<(), CliError> {
+    let mut store = InstanceStore::open().await?;
+    let report = store.reconcile().await?;
+    path_resolver::clear_cache()?;
+    let removed_clones = clean_clones()?;
+    let cleaned_sandboxes = if sandboxes { clean_sandboxes(store.instances())? } else { Vec::new() };
+
+    if json {
+        print_json(&json!({
+            "removed_records": report.removed,
+            "removed_clones": removed_clones,
+            "cleaned_sandboxes": cleaned_sandboxes,
+        }));
+        return Ok(());
+    }
+
+    println!("✓ 已移除 {} 条失效的实例记录", report.removed.len());
+    println!("✓ 已清除路径缓存");
+    if cfg!(target_os = "macos") {
+        println!("✓ 已删除 {} 个未使用的应用副本", removed_clones.len());
+    }
+    if sandboxes {
+        println!("✓ 已清理 {} 个未使用的沙盒", cleaned_sandboxes.len());
+    }
+    Ok(())
+}
+
+/// 删除没有实例在使用的 macOS 应用副本 (数据目录不受影响,下次启动时重新创建副本)
+#[cfg(target_os = "macos")]
+fn clean_clones() -> Result<Vec<PathBuf>, String> {
+    let home = env::var("HOME").map_err(|_| "无法获取 HOME 目录".to_string())?;
+    let dir = PathBuf::from(home).join(discovery::MACOS_CLONE_DIR);
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(_) => return Ok(Vec::new()),
+    };
+
+    let in_use: Vec<u8> = [AppType::WeCom, AppType::WeChat]
+        .iter()
+        .flat_map(discovery::discover)
+        .filter_map(|instance| instance.clone_id)
+        .collect();
+
+    let mut removed = Vec::new();
+    for path in entries.flatten().map(|e| e.path()) {
+        let clone_id = [AppType::WeCom, AppType::WeChat]
+            .iter()
+            .find_map(|t| discovery::AppProfile::for_type(t).clone_index(&path.to_string_lossy()));
+        if matches!(clone_id, Some(id) if !in_use.contains(&id)) {
+            std::fs::remove_dir_all(&path).map_err(|e| format!("删除 {} 失败: {}", path.display(), e))?;
+            removed.push(path);
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn clean_clones() -> Result<Vec<PathBuf>, String> {
+    Ok(Vec::new())
+}
+
+/// 清空没有实例在使用、也不属于账号配置的 Sandboxie 沙盒 (沙盒内的登录数据会被删除)
+#[cfg(target_os = "windows")]
+fn clean_sandboxes(managed: &[InstanceRecord]) -> Result<Vec<String>, CliError> {
+    use wecom_multi_open::windows_sandbox::SandboxieManager;
+
+    let manager = SandboxieManager::new().map_err(|e| CliError::new(ErrorKind::NotFound, e))?;
+    let store = ProfileStore::load_default()?;
+    let in_use: Vec<String> = managed
+        .iter()
+        .filter_map(|r| r.sandbox_name.clone())
+        .chain(store.list().iter().filter_map(AccountProfile::sandbox_name))
+        .collect();
+
+    let mut cleaned = Vec::new();
+    for name in manager.list_sandboxes()? {
+        if name.starts_with("WeCom_") && !in_use.contains(&name) {
+            manager.cleanup_sandbox(&name)?;
+            cleaned.push(name);
         }
     }
+    Ok(cleaned)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn clean_sandboxes(_managed: &[InstanceRecord]) -> Result<Vec<String>, CliError> {
+    Err(CliError::new(ErrorKind::Unsupported, "Sandboxie 仅支持 Windows 平台"))
+}
+
+/// 后台服务: 前台运行、停止或查看状态
+async fn run_daemon(
+    config: &Config,
+    overrides: &[(String, String)],
+    action: DaemonAction,
+    json: bool,
+) -> Result<(), CliError> {
+    let endpoint = daemon::default_endpoint();
+    let not_running = |_| CliError::new(ErrorKind::NotFound, format!("后台服务未运行 ({})", endpoint.display()));
 
+    match action {
+        DaemonAction::Start => {
+            let registry = InstanceRegistry::load_default()?;
+            let daemon = Daemon::new(PlatformBackend, registry);
+            // REST API 默认关闭,启用后与后台服务共享实例状态
+            if config.api.enabled {
+                let api = HttpApi::bind(config.api.port, config.api.token.as_deref().unwrap_or_default()).await?;
+                tokio::spawn(api.serve(daemon.clone()));
+            }
+            tokio::spawn(daemon.clone().reconcile_every(Duration::from_secs(5)));
+            // 配置文件修改后立即生效 (账号配置每次使用时重新读取)
+            let reloader = ConfigReloader::with_defaults(config.clone(), overrides.to_vec());
+            tokio::spawn(reloader.run(config_watch::DEFAULT_POLL_INTERVAL, |_| {}));
+            tokio::spawn(daemon.clone().run_scheduler(Scheduler::new(SystemClock), scheduler::TICK_INTERVAL));
+            daemon.serve(&endpoint).await?;
+            eprintln!("后台服务已停止");
+        }
+        DaemonAction::Stop => {
+            let mut client = daemon::Client::connect(&endpoint).await.map_err(not_running)?;
+            client.shutdown().await?;
+            if json {
+                print_json(&json!({ "stopped": true }));
+            } else {
+                println!("✓ 后台服务已停止");
+            }
+        }
+        DaemonAction::Status => {
+            let mut client = daemon::Client::connect(&endpoint).await.map_err(not_running)?;
+            let info = client.ping().await?;
+            if json {
+                print_json(&info);
+            } else {
+                println!("后台服务运行中 (PID: {}, 版本: {})", info.pid, info.version);
+                println!("地址: {}", endpoint.display());
+                println!("管理实例: {} 个", info.instances);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 类似 top 的实例资源视图 (JSON 模式下每次刷新输出一行)
+async fn run_top(interval: u64, once: bool, json: bool) {
+    let mut monitor = ResourceMonitor::new();
+
+    loop {
+        let instances: Vec<(u32, AppType)> = [AppType::WeCom, AppType::WeChat]
+            .iter()
+            .flat_map(|app_type| {
+                discovery::discover(app_type)
+                    .into_iter()
+                    .map(move |instance| (instance.pid, app_type.clone()))
+            })
+            .collect();
+
+        let pids: Vec<u32> = instances.iter().map(|(pid, _)| *pid).collect();
+        let stats = monitor.sample(&pids);
+        let app_of = |pid: u32| instances.iter().find(|(p, _)| *p == pid).map(|(_, app_type)| app_type);
+
+        if json {
+            let rows: Vec<_> = stats
+                .iter()
+                .map(|s| json!({ "app_type": app_of(s.pid), "stats": s }))
+                .collect();
+            println!("{}", serde_json::to_string(&rows).unwrap_or_default());
+        } else {
+            if !once {
+                // 清屏并将光标移到左上角
+                print!("\x1B[2J\x1B[H");
+            }
+
+            println!(
+                "{:>8}  {:<8}  {:>7}  {:>10}  {:>6}  {:>6}  {:>10}",
+                "PID", "应用", "CPU%", "内存", "线程", "子进程", "运行时长"
+            );
+
+            let mut total_rss = 0;
+            for s in &stats {
+                total_rss += s.rss_bytes;
+
+                println!(
+                    "{:>8}  {:<8}  {:>7.1}  {:>10}  {:>6}  {:>6}  {:>10}",
+                    s.pid,
+                    app_of(s.pid).map(app_name).unwrap_or("-"),
+                    s.cpu_percent,
+                    format_bytes(s.rss_bytes),
+                    s.threads,
+                    s.children.len(),
+                    format_uptime(s.uptime_secs),
+                );
+            }
+
+            println!("\n共 {} 个实例, 内存合计 {}", stats.len(), format_bytes(total_rss));
+        }
+
+        if once {
+            break;
+        }
+
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+    }
+}
+
+fn format_uptime(secs: u64) -> String {
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs % 3600 / 60, secs % 60)
+}
+
+const PROFILE_USAGE: &str = "用法: profile <list|show|add|set|rm|launch> [名称] [选项]
+选项 (add/set):
+  --app <wecom|wechat>          应用类型
+  --app-path <路径>             应用路径
+  --data-dir <目录>             数据目录
+  --proxy <scheme://host:port>  代理
+  --color <#RRGGBB>             标识颜色
+  --arg <参数>                  额外启动参数 (可重复)
+  --isolation <simple|sandboxie> 隔离方式
+  --hook <事件>=<命令>          生命周期钩子 (可重复,命令为空表示移除)
+                                事件: pre_spawn, post_spawn, on_exit, on_crash, pre_kill
+  --hook-timeout <秒>           钩子超时时间 (默认 30)
+  --window <时间段>             定时启动 / 停止 (可重复),如 \"mon-fri 09:00-18:00\"
+  --cron-start <cron 表达式>    按 cron 表达式定时启动 (可重复),如 \"0 9 * * 1-5\"
+  --cron-stop <cron 表达式>     按 cron 表达式定时停止 (可重复)
+  --schedule none               清除所有定时规则
+  --name <新名称>               重命名 (仅 set)";
+
+fn profile_not_found(name: &str) -> CliError {
+    CliError::new(ErrorKind::NotFound, format!("账号配置不存在: {}", name))
+}
+
+/// 账号配置子命令
+async fn run_profile(args: &[String], json: bool) -> Result<(), CliError> {
+    let usage = || CliError::new(ErrorKind::Usage, PROFILE_USAGE);
+    let action = args.first().map(String::as_str).ok_or_else(usage)?;
+    let mut store = ProfileStore::load_default()?;
+
+    if action == "list" {
+        if json {
+            print_json(store.list());
+            return Ok(());
+        }
+        if store.list().is_empty() {
+            println!("暂无账号配置");
+        }
+        for p in store.list() {
+            println!(
+                "{:<20}  {:<8}  {:<10}  {}",
+                p.name,
+                format!("{:?}", p.app_type),
+                format!("{:?}", p.isolation),
+                p.resolved_data_dir().display()
+            );
+        }
+        return Ok(());
+    }
+
+    let name = args.get(1).ok_or_else(usage)?;
+    let options = &args[2..];
+
+    match action {
+        "show" => {
+            let profile = store.get(name).ok_or_else(|| profile_not_found(name))?;
+            print_json(profile);
+        }
+        "add" => {
+            let mut profile = AccountProfile::new(name, AppType::WeCom);
+            apply_profile_options(&mut profile, options).map_err(|e| CliError::new(ErrorKind::Usage, e))?;
+            let profile = store.create(profile)?.clone();
+            store.save()?;
+            if json {
+                print_json(&profile);
+            } else {
+                println!("✓ 已创建账号配置 {}", name);
+            }
+        }
+        "set" => {
+            let mut profile = store.get(name).cloned().ok_or_else(|| profile_not_found(name))?;
+            apply_profile_options(&mut profile, options).map_err(|e| CliError::new(ErrorKind::Usage, e))?;
+            let profile = store.update(name, profile)?.clone();
+            store.save()?;
+            if json {
+                print_json(&profile);
+            } else {
+                println!("✓ 已更新账号配置 {}", name);
+            }
+        }
+        "rm" => {
+            if store.get(name).is_none() {
+                return Err(profile_not_found(name));
+            }
+            let removed = store.delete(name)?;
+            store.save()?;
+            if json {
+                print_json(&removed);
+            } else {
+                println!("✓ 已删除账号配置 {} (数据目录 {} 已保留)", name, removed.resolved_data_dir().display());
+            }
+        }
+        "launch" => {
+            let profile = store.get(name).ok_or_else(|| profile_not_found(name))?;
+            let record = profiles::launch(profile).await?;
+
+            let mut store = InstanceStore::open().await?;
+            if let Err(e) = store.add(vec![record.clone()]).await {
+                eprintln!("⚠ 记录实例失败: {}", e);
+            }
+            if json {
+                print_json(&record);
+            } else {
+                println!("✓ 已启动账号 {} (PID: {})", name, record.pid);
+            }
+        }
+        _ => return Err(usage()),
+    }
+
+    Ok(())
+}
+
+fn apply_profile_options(profile: &mut AccountProfile, options: &[String]) -> Result<(), String> {
+    let mut iter = options.iter();
+    while let Some(flag) = iter.next() {
+        let value = iter
+            .next()
+            .ok_or_else(|| format!("选项 {} 缺少参数值", flag))?
+            .clone();
+
+        match flag.as_str() {
+            "--app" => {
+                profile.app_type = match value.to_lowercase().as_str() {
+                    "wecom" => AppType::WeCom,
+                    "wechat" => AppType::WeChat,
+                    _ => return Err(format!("未知应用类型: {}", value)),
+                }
+            }
+            "--app-path" => profile.app_path = Some(PathBuf::from(value)),
+            "--data-dir" => profile.data_dir = Some(PathBuf::from(value)),
+            "--proxy" => profile.proxy = Some(value),
+            "--color" => profile.color = Some(value),
+            "--arg" => profile.launch_args.push(value),
+            "--isolation" => {
+                profile.isolation = match value.to_lowercase().as_str() {
+                    "simple" => IsolationMode::Simple,
+                    "sandboxie" => IsolationMode::Sandboxie,
+                    _ => return Err(format!("未知隔离方式: {}", value)),
+                }
+            }
+            "--hook" => {
+                let (event, command) = value
+                    .split_once('=')
+                    .ok_or_else(|| format!("钩子格式应为 <事件>=<命令>: {}", value))?;
+                profile.hooks.set(HookEvent::parse(event)?, command);
+            }
+            "--hook-timeout" => {
+                profile.hooks.timeout_secs = value
+                    .parse()
+                    .ok()
+                    .filter(|secs| *secs > 0)
+                    .ok_or_else(|| format!("无效的超时时间: {}", value))?
+            }
+            "--window" => profile.schedule.push(ScheduleRule::parse_window(&value)?),
+            "--cron-start" | "--cron-stop" => {
+                let action = if flag == "--cron-start" { ScheduleAction::Start } else { ScheduleAction::Stop };
+                let rule = ScheduleRule::Cron { expr: value, action };
+                rule.validate()?;
+                profile.schedule.push(rule);
+            }
+            "--schedule" if value == "none" => profile.schedule.clear(),
+            "--name" => profile.name = value,
+            _ => return Err(format!("未知选项: {}\n{}", flag, PROFILE_USAGE)),
+        }
+    }
     Ok(())
 }
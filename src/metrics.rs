@@ -0,0 +1,230 @@
+// 运行指标 - 以 Prometheus 文本格式输出
+//
+// 计数器为进程内全局值,由后台服务 (启动耗时、重启) 和各平台的启动流程 (Mutex 关闭失败) 累加;
+// 实例数、重启次数和内存占用在每次抓取时由实例注册表和资源监控计算。
+
+use crate::monitor::InstanceStats;
+use crate::registry::InstanceRecord;
+use crate::{AppType, IsolationMode, SpawnResponse};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// 启动耗时直方图的分桶上限 (秒)
+pub const SPAWN_DURATION_BUCKETS: [f64; 8] = [0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0];
+
+/// 启动流程的计数器
+#[derive(Debug, Default)]
+pub struct Counters {
+    spawn_requests: AtomicU64,
+    spawned: AtomicU64,
+    spawn_failed: AtomicU64,
+    /// 各分桶的计数 (不累加),最后一个为 +Inf
+    spawn_duration_buckets: [AtomicU64; SPAWN_DURATION_BUCKETS.len() + 1],
+    spawn_duration_ms: AtomicU64,
+    restarts: AtomicU64,
+    mutex_close_failures: AtomicU64,
+}
+
+impl Counters {
+    pub const fn new() -> Self {
+        Self {
+            spawn_requests: AtomicU64::new(0),
+            spawned: AtomicU64::new(0),
+            spawn_failed: AtomicU64::new(0),
+            spawn_duration_buckets: [const { AtomicU64::new(0) }; SPAWN_DURATION_BUCKETS.len() + 1],
+            spawn_duration_ms: AtomicU64::new(0),
+            restarts: AtomicU64::new(0),
+            mutex_close_failures: AtomicU64::new(0),
+        }
+    }
+
+    /// 记录一次启动请求的结果和耗时
+    pub fn record_spawn(&self, elapsed: Duration, response: &SpawnResponse) {
+        self.spawn_requests.fetch_add(1, Ordering::Relaxed);
+        self.spawned.fetch_add(response.success as u64, Ordering::Relaxed);
+        self.spawn_failed.fetch_add(response.failed as u64, Ordering::Relaxed);
+
+        let secs = elapsed.as_secs_f64();
+        let bucket = SPAWN_DURATION_BUCKETS
+            .iter()
+            .position(|&le| secs <= le)
+            .unwrap_or(SPAWN_DURATION_BUCKETS.len());
+        self.spawn_duration_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.spawn_duration_ms.fetch_add(elapsed.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// 记录一次启动失败的请求 (如找不到应用)
+    pub fn record_spawn_error(&self) {
+        self.spawn_requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_restart(&self) {
+        self.restarts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_mutex_close_failure(&self) {
+        self.mutex_close_failures.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+static COUNTERS: Counters = Counters::new();
+
+/// 当前进程的计数器
+pub fn counters() -> &'static Counters {
+    &COUNTERS
+}
+
+/// 输出 Prometheus 文本格式 (text/plain; version=0.0.4)
+pub fn render(counters: &Counters, instances: &[InstanceRecord], stats: &[InstanceStats]) -> String {
+    let mut out = String::new();
+    let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+
+    header(&mut out, "wecom_multi_instances", "gauge", "由本工具管理的实例数");
+    for app_type in [AppType::WeCom, AppType::WeChat] {
+        for backend in [IsolationMode::Simple, IsolationMode::Sandboxie] {
+            let count = instances
+                .iter()
+                .filter(|r| r.app_type == app_type && r.backend == backend)
+                .count();
+            let _ = writeln!(
+                out,
+                "wecom_multi_instances{{app=\"{}\",backend=\"{}\"}} {}",
+                app_label(&app_type),
+                backend_label(&backend),
+                count
+            );
+        }
+    }
+
+    header(&mut out, "wecom_multi_spawn_requests_total", "counter", "启动请求数");
+    let _ = writeln!(out, "wecom_multi_spawn_requests_total {}", load(&counters.spawn_requests));
+
+    header(&mut out, "wecom_multi_spawned_instances_total", "counter", "按结果统计的实例启动次数");
+    let _ = writeln!(out, "wecom_multi_spawned_instances_total{{result=\"success\"}} {}", load(&counters.spawned));
+    let _ = writeln!(out, "wecom_multi_spawned_instances_total{{result=\"failed\"}} {}", load(&counters.spawn_failed));
+
+    header(&mut out, "wecom_multi_spawn_duration_seconds", "histogram", "启动请求耗时");
+    let mut cumulative = 0;
+    for (i, counter) in counters.spawn_duration_buckets.iter().enumerate() {
+        cumulative += load(counter);
+        let le = SPAWN_DURATION_BUCKETS
+            .get(i)
+            .map(|le| le.to_string())
+            .unwrap_or_else(|| "+Inf".to_string());
+        let _ = writeln!(out, "wecom_multi_spawn_duration_seconds_bucket{{le=\"{}\"}} {}", le, cumulative);
+    }
+    let _ = writeln!(
+        out,
+        "wecom_multi_spawn_duration_seconds_sum {}",
+        load(&counters.spawn_duration_ms) as f64 / 1000.0
+    );
+    let _ = writeln!(out, "wecom_multi_spawn_duration_seconds_count {}", cumulative);
+
+    header(&mut out, "wecom_multi_restarts_total", "counter", "守护进程重启实例的次数");
+    let _ = writeln!(out, "wecom_multi_restarts_total {}", load(&counters.restarts));
+
+    header(&mut out, "wecom_multi_mutex_close_failures_total", "counter", "关闭单实例 Mutex 失败的次数");
+    let _ = writeln!(out, "wecom_multi_mutex_close_failures_total {}", load(&counters.mutex_close_failures));
+
+    header(&mut out, "wecom_multi_instance_restarts", "gauge", "各实例被重启的次数");
+    for record in instances {
+        let _ = writeln!(out, "wecom_multi_instance_restarts{{{}}} {}", instance_labels(record), record.restarts);
+    }
+
+    header(&mut out, "wecom_multi_instance_memory_bytes", "gauge", "各实例占用的物理内存 (含子进程)");
+    for record in instances {
+        if let Some(s) = stats.iter().find(|s| s.pid == record.pid) {
+            let _ = writeln!(out, "wecom_multi_instance_memory_bytes{{{}}} {}", instance_labels(record), s.rss_bytes);
+        }
+    }
+
+    out
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn instance_labels(record: &InstanceRecord) -> String {
+    format!(
+        "pid=\"{}\",app=\"{}\",profile=\"{}\"",
+        record.pid,
+        app_label(&record.app_type),
+        escape(record.profile.as_deref().unwrap_or_default())
+    )
+}
+
+fn app_label(app_type: &AppType) -> &'static str {
+    match app_type {
+        AppType::WeCom => "wecom",
+        AppType::WeChat => "wechat",
+    }
+}
+
+fn backend_label(backend: &IsolationMode) -> &'static str {
+    match backend {
+        IsolationMode::Simple => "simple",
+        IsolationMode::Sandboxie => "sandboxie",
+    }
+}
+
+/// 转义标签值中的 `\`、`"` 和换行
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let counters = Counters::new();
+        let response = SpawnResponse {
+            pids: vec![100, 101],
+            success: 2,
+            failed: 1,
+            plan: None,
+        };
+        counters.record_spawn(Duration::from_millis(1500), &response);
+        counters.record_spawn(Duration::from_secs(200), &response);
+        counters.record_restart();
+        counters.record_mutex_close_failure();
+
+        let mut record = InstanceRecord::new(100, AppType::WeCom, IsolationMode::Simple);
+        record.profile = Some("销售 \"北京\"".to_string());
+        record.restarts = 2;
+        let stats = InstanceStats {
+            pid: 100,
+            cpu_percent: 0.0,
+            rss_bytes: 4096,
+            threads: 1,
+            uptime_secs: 1,
+            children: Vec::new(),
+        };
+
+        let text = render(&counters, &[record], &[stats]);
+        let lines: Vec<&str> = text.lines().collect();
+        for expected in [
+            "wecom_multi_instances{app=\"wecom\",backend=\"simple\"} 1",
+            "wecom_multi_instances{app=\"wechat\",backend=\"sandboxie\"} 0",
+            "wecom_multi_spawn_requests_total 2",
+            "wecom_multi_spawned_instances_total{result=\"success\"} 4",
+            "wecom_multi_spawned_instances_total{result=\"failed\"} 2",
+            "wecom_multi_spawn_duration_seconds_bucket{le=\"1\"} 0",
+            "wecom_multi_spawn_duration_seconds_bucket{le=\"2.5\"} 1",
+            "wecom_multi_spawn_duration_seconds_bucket{le=\"120\"} 1",
+            "wecom_multi_spawn_duration_seconds_bucket{le=\"+Inf\"} 2",
+            "wecom_multi_spawn_duration_seconds_sum 201.5",
+            "wecom_multi_spawn_duration_seconds_count 2",
+            "wecom_multi_restarts_total 1",
+            "wecom_multi_mutex_close_failures_total 1",
+            "wecom_multi_instance_restarts{pid=\"100\",app=\"wecom\",profile=\"销售 \\\"北京\\\"\"} 2",
+            "wecom_multi_instance_memory_bytes{pid=\"100\",app=\"wecom\",profile=\"销售 \\\"北京\\\"\"} 4096",
+        ] {
+            assert!(lines.contains(&expected), "缺少: {}\n{}", expected, text);
+        }
+    }
+}
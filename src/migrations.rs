@@ -0,0 +1,254 @@
+// 存储格式迁移 - 升级后自动转换旧版本的配置、账号配置和实例注册表
+//
+// 每个持久化文件都带有 schema_version 字段 (缺失视为 0)。
+// 加载时在内存中逐级执行迁移函数; 只有迁移改变了数据时才写回文件,
+// 写回前把原文件备份为 <文件名>.v<旧版本>-<时间戳>.bak。仅缺少版本号时不改动文件,
+// 版本号由下一次正常保存写入 (status / list / doctor 等只读命令和热加载不会改写文件)。
+
+use crate::app_dirs;
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 版本字段名
+pub const VERSION_KEY: &str = "schema_version";
+
+/// 迁移函数: 把版本 N 的内容升级到 N + 1
+pub type Migration = fn(&mut Map<String, Value>) -> Result<(), String>;
+
+/// 文件格式定义
+pub struct Schema {
+    /// 文件描述 (用于错误信息)
+    pub name: &'static str,
+    /// 当前版本
+    pub current: u32,
+    /// migrations[i] 把版本 i 升级到 i + 1
+    pub migrations: &'static [Migration],
+}
+
+/// 配置文件 (config.json)
+pub const CONFIG: Schema = Schema {
+    name: "配置文件",
+    current: 1,
+    migrations: &[config_v0_to_v1],
+};
+
+/// 账号配置 (profiles.json)
+pub const PROFILES: Schema = Schema {
+    name: "账号配置",
+    current: 1,
+    migrations: &[profiles_v0_to_v1],
+};
+
+/// 实例注册表 (instances.json)
+pub const REGISTRY: Schema = Schema {
+    name: "实例注册表",
+    current: 1,
+    migrations: &[registry_v0_to_v1],
+};
+
+/// v0 (0.5.x 未带版本号) -> v1: 仅添加版本号
+fn config_v0_to_v1(_doc: &mut Map<String, Value>) -> Result<(), String> {
+    Ok(())
+}
+
+/// v0 -> v1: 补全缺失的 profiles 列表
+fn profiles_v0_to_v1(doc: &mut Map<String, Value>) -> Result<(), String> {
+    doc.entry("profiles").or_insert_with(|| Value::Array(Vec::new()));
+    Ok(())
+}
+
+/// v0 -> v1: 补全缺失的 instances 列表
+fn registry_v0_to_v1(doc: &mut Map<String, Value>) -> Result<(), String> {
+    doc.entry("instances").or_insert_with(|| Value::Array(Vec::new()));
+    Ok(())
+}
+
+/// 读取文件的格式版本 (缺失视为 0)
+pub fn version_of(doc: &Map<String, Value>) -> Result<u32, String> {
+    match doc.get(VERSION_KEY) {
+        None => Ok(0),
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| format!("{} 无效: {}", VERSION_KEY, v)),
+    }
+}
+
+/// 把内容迁移到当前版本,返回原版本号
+pub fn migrate(schema: &Schema, doc: &mut Map<String, Value>) -> Result<u32, String> {
+    debug_assert_eq!(schema.migrations.len() as u32, schema.current);
+
+    let from = version_of(doc)?;
+    if from > schema.current {
+        return Err(format!(
+            "{} 的格式版本 ({}) 高于当前支持的版本 ({}),请升级本工具",
+            schema.name, from, schema.current
+        ));
+    }
+
+    for (version, migration) in schema.migrations.iter().enumerate().skip(from as usize) {
+        migration(doc).map_err(|e| format!("{} 从版本 {} 迁移失败: {}", schema.name, version, e))?;
+        doc.insert(VERSION_KEY.to_string(), Value::from(version as u32 + 1));
+    }
+
+    Ok(from)
+}
+
+/// 必要时迁移文件内容
+///
+/// 已是当前版本,或迁移只添加了版本号时原样返回 (不改动文件,调用方按当前版本解析);
+/// 迁移改变了数据时先备份原文件,再写回迁移后的内容。
+pub fn upgrade_file(schema: &Schema, path: &Path, text: String) -> Result<String, String> {
+    let mut doc = match serde_json::from_str::<Value>(&text) {
+        Ok(Value::Object(doc)) => doc,
+        // 语法错误交给调用方解析时报告 (带行号)
+        Ok(_) | Err(_) => return Ok(text),
+    };
+
+    let from = version_of(&doc)?;
+    if from == schema.current {
+        return Ok(text);
+    }
+
+    let original = doc.clone();
+    migrate(schema, &mut doc)?;
+    if without_version(&doc) == without_version(&original) {
+        return Ok(text);
+    }
+
+    let backup = backup_path(path, from);
+    fs::copy(path, &backup).map_err(|e| format!("备份 {} 失败: {}", path.display(), e))?;
+
+    let migrated = serde_json::to_string_pretty(&doc).map_err(|e| format!("序列化{}失败: {}", schema.name, e))?;
+    app_dirs::write_atomic(path, migrated.as_bytes())?;
+
+    eprintln!(
+        "✓ {} 已从版本 {} 迁移到 {} (原文件备份为 {})",
+        schema.name,
+        from,
+        schema.current,
+        backup.display()
+    );
+    Ok(migrated)
+}
+
+fn without_version(doc: &Map<String, Value>) -> Map<String, Value> {
+    let mut doc = doc.clone();
+    doc.remove(VERSION_KEY);
+    doc
+}
+
+fn backup_path(path: &Path, version: u32) -> PathBuf {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    path.with_file_name(format!("{}.v{}-{}.bak", name, version, secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, CONFIG_FILE};
+    use crate::profiles::{ProfileStore, PROFILES_FILE};
+    use crate::registry::{InstanceRegistry, REGISTRY_FILE};
+
+    fn backups(dir: &Path) -> Vec<PathBuf> {
+        fs::read_dir(dir)
+            .unwrap()
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "bak"))
+            .collect()
+    }
+
+    #[test]
+    fn test_config_v0() {
+        let dir = app_dirs::temp_test_dir("migrate-config-v0");
+        let path = dir.join(CONFIG_FILE);
+        let original = r#"{ "count": 4, "isolation": "sandboxie" }"#;
+        fs::write(&path, original).unwrap();
+
+        let config = Config::load_file(&path).unwrap();
+        assert_eq!(config.count, 4);
+        assert_eq!(config.schema_version, CONFIG.current);
+
+        // 迁移只添加版本号: 不改写文件也不备份
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+        assert!(backups(&dir).is_empty());
+    }
+
+    #[test]
+    fn test_profiles_v0() {
+        let dir = app_dirs::temp_test_dir("migrate-profiles-v0");
+        let path = dir.join(PROFILES_FILE);
+        fs::write(&path, r#"{ "profiles": [ { "name": "Sales", "proxy": "http://127.0.0.1:7890" } ] }"#).unwrap();
+
+        let store = ProfileStore::load(&path).unwrap();
+        assert_eq!(store.list()[0].name, "Sales");
+        assert_eq!(store.schema_version, PROFILES.current);
+        assert!(backups(&dir).is_empty());
+
+        let mut doc = Map::new();
+        migrate(&PROFILES, &mut doc).unwrap();
+        assert_eq!(doc.get("profiles"), Some(&Value::Array(Vec::new())));
+    }
+
+    #[test]
+    fn test_registry_v0() {
+        let dir = app_dirs::temp_test_dir("migrate-registry-v0");
+        let path = dir.join(REGISTRY_FILE);
+        fs::write(
+            &path,
+            r#"{ "instances": [ { "id": "1-42", "pid": 42, "app_type": "WeCom", "backend": "simple", "started_at": 1 } ] }"#,
+        )
+        .unwrap();
+
+        let registry = InstanceRegistry::load(&path).unwrap();
+        assert_eq!(registry.pids(), vec![42]);
+        assert_eq!(registry.schema_version, REGISTRY.current);
+        assert!(backups(&dir).is_empty());
+    }
+
+    #[test]
+    fn test_data_change_is_written_with_backup() {
+        let dir = app_dirs::temp_test_dir("migrate-registry-rewrite");
+        let path = dir.join(REGISTRY_FILE);
+        let original = "{}";
+        fs::write(&path, original).unwrap();
+
+        let registry = InstanceRegistry::load(&path).unwrap();
+        assert!(registry.pids().is_empty());
+
+        let rewritten = fs::read_to_string(&path).unwrap();
+        assert!(rewritten.contains("\"schema_version\": 1"));
+        assert!(rewritten.contains("\"instances\": []"));
+
+        let backups = backups(&dir);
+        assert_eq!(backups.len(), 1);
+        assert_eq!(fs::read_to_string(&backups[0]).unwrap(), original);
+    }
+
+    #[test]
+    fn test_current_version_is_untouched() {
+        let dir = app_dirs::temp_test_dir("migrate-current");
+        let path = dir.join(CONFIG_FILE);
+        fs::write(&path, r#"{ "schema_version": 1, "count": 2 }"#).unwrap();
+
+        assert_eq!(Config::load_file(&path).unwrap().count, 2);
+        assert!(backups(&dir).is_empty());
+    }
+
+    #[test]
+    fn test_newer_version_is_rejected() {
+        let dir = app_dirs::temp_test_dir("migrate-newer");
+        let path = dir.join(REGISTRY_FILE);
+        fs::write(&path, r#"{ "schema_version": 99, "instances": [] }"#).unwrap();
+
+        let err = InstanceRegistry::load(&path).unwrap_err();
+        assert!(err.contains("99"), "{}", err);
+        assert!(backups(&dir).is_empty());
+    }
+}
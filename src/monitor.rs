@@ -0,0 +1,457 @@
+// 资源监控 - 采样每个实例的 CPU / 内存 / 线程数 / 运行时长
+// 实例的统计值包含其所有子进程
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// 单个进程的原始采样
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProcSample {
+    pub pid: u32,
+    pub ppid: u32,
+    /// 累计 CPU 时间 (用户态 + 内核态)
+    pub cpu_time: Duration,
+    pub rss_bytes: u64,
+    pub threads: u32,
+    /// 进程已运行时长
+    pub uptime: Duration,
+}
+
+/// 实例资源统计 (含子进程)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InstanceStats {
+    pub pid: u32,
+    /// CPU 占用 (100 = 一个核心满载)
+    pub cpu_percent: f64,
+    pub rss_bytes: u64,
+    pub threads: u32,
+    pub uptime_secs: u64,
+    /// 子进程 PID 列表
+    pub children: Vec<u32>,
+}
+
+/// 资源监控器
+///
+/// CPU 占用通过相邻两次采样的 CPU 时间差计算,因此需要复用同一个监控器。
+/// 首次采样时使用进程生命周期内的平均值。
+#[derive(Debug, Default)]
+pub struct ResourceMonitor {
+    last: HashMap<u32, (Instant, Duration)>,
+}
+
+impl ResourceMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 采样指定实例
+    pub fn sample(&mut self, pids: &[u32]) -> Vec<InstanceStats> {
+        let table = read_process_table();
+        self.sample_from(pids, &table, Instant::now())
+    }
+
+    /// 基于进程表计算统计值 (不存在的实例会被跳过)
+    pub fn sample_from(&mut self, pids: &[u32], table: &[ProcSample], now: Instant) -> Vec<InstanceStats> {
+        let by_pid: HashMap<u32, &ProcSample> = table.iter().map(|p| (p.pid, p)).collect();
+        let children_of = children_map(table);
+        let tracked: HashSet<u32> = pids.iter().copied().collect();
+
+        let mut stats = Vec::new();
+        let mut seen = HashMap::new();
+
+        for &pid in pids {
+            let Some(root) = by_pid.get(&pid) else {
+                continue;
+            };
+
+            // 同时被监控的子进程 (及其子孙) 单独统计,不计入父实例
+            let children = collect_descendants(pid, &children_of, &tracked);
+            let members = std::iter::once(*root).chain(children.iter().filter_map(|c| by_pid.get(c).copied()));
+
+            let mut cpu_time = Duration::ZERO;
+            let mut rss_bytes = 0;
+            let mut threads = 0;
+            for p in members {
+                cpu_time += p.cpu_time;
+                rss_bytes += p.rss_bytes;
+                threads += p.threads;
+            }
+
+            let cpu_percent = match self.last.get(&pid) {
+                Some((at, prev_cpu)) if now > *at => {
+                    let wall = now.duration_since(*at).as_secs_f64();
+                    cpu_time.saturating_sub(*prev_cpu).as_secs_f64() / wall * 100.0
+                }
+                _ if !root.uptime.is_zero() => cpu_time.as_secs_f64() / root.uptime.as_secs_f64() * 100.0,
+                _ => 0.0,
+            };
+
+            seen.insert(pid, (now, cpu_time));
+            stats.push(InstanceStats {
+                pid,
+                cpu_percent,
+                rss_bytes,
+                threads,
+                uptime_secs: root.uptime.as_secs(),
+                children,
+            });
+        }
+
+        // 只保留仍在监控的实例
+        self.last = seen;
+        stats
+    }
+}
+
+/// 查找所有子孙进程
+pub fn descendants(pid: u32, table: &[ProcSample]) -> Vec<u32> {
+    collect_descendants(pid, &children_map(table), &HashSet::new())
+}
+
+/// 父进程 PID 到子进程 PID 的映射
+fn children_map(table: &[ProcSample]) -> HashMap<u32, Vec<u32>> {
+    let mut map: HashMap<u32, Vec<u32>> = HashMap::new();
+    for p in table.iter().filter(|p| p.pid != p.ppid) {
+        map.entry(p.ppid).or_default().push(p.pid);
+    }
+    map
+}
+
+/// 查找子孙进程,不进入 exclude 中的进程及其子孙
+fn collect_descendants(pid: u32, children_of: &HashMap<u32, Vec<u32>>, exclude: &HashSet<u32>) -> Vec<u32> {
+    let mut result = Vec::new();
+    let mut seen = HashSet::from([pid]);
+    let mut queue = vec![pid];
+
+    while let Some(parent) = queue.pop() {
+        for &child in children_of.get(&parent).into_iter().flatten() {
+            // 已访问过的进程跳过,避免异常数据 (如父子关系成环) 导致死循环
+            if exclude.contains(&child) || !seen.insert(child) {
+                continue;
+            }
+            result.push(child);
+            queue.push(child);
+        }
+    }
+
+    result
+}
+
+/// 读取进程表 (Linux: /proc)
+#[cfg(target_os = "linux")]
+pub fn read_process_table() -> Vec<ProcSample> {
+    use std::fs;
+
+    // USER_HZ (通常为 100)
+    let clock_ticks = match unsafe { libc::sysconf(libc::_SC_CLK_TCK) } {
+        ticks if ticks > 0 => ticks as f64,
+        _ => 100.0,
+    };
+
+    let system_uptime = fs::read_to_string("/proc/uptime")
+        .ok()
+        .and_then(|s| s.split_whitespace().next()?.parse::<f64>().ok())
+        .unwrap_or(0.0);
+
+    let Ok(dir) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+
+    dir.flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .filter_map(|pid| {
+            let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+            let status = fs::read_to_string(format!("/proc/{}/status", pid)).unwrap_or_default();
+            parse_proc_stat(pid, &stat, &status, system_uptime, clock_ticks)
+        })
+        .collect()
+}
+
+/// 解析 /proc/<pid>/stat 和 /proc/<pid>/status
+#[cfg(target_os = "linux")]
+pub(crate) fn parse_proc_stat(pid: u32, stat: &str, status: &str, system_uptime: f64, ticks: f64) -> Option<ProcSample> {
+    // 进程名 (第2字段) 可能包含空格,从最后一个 ')' 之后开始按字段解析
+    // 之后的字段从第3个 (state) 开始编号
+    let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+    let field = |n: usize| fields.get(n - 3).and_then(|v| v.parse::<u64>().ok());
+
+    let ppid = field(4)? as u32;
+    let cpu_ticks = field(14)? + field(15)?;
+    let threads = field(20)? as u32;
+    let start_secs = field(22)? as f64 / ticks;
+
+    // RSS 优先使用 status 中的 VmRSS (单位 kB),避免依赖页大小
+    let rss_bytes = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))
+        .and_then(|v| v.split_whitespace().next()?.parse::<u64>().ok())
+        .map(|kb| kb * 1024)
+        .unwrap_or_else(|| field(24).unwrap_or(0) * 4096);
+
+    Some(ProcSample {
+        pid,
+        ppid,
+        cpu_time: Duration::from_secs_f64(cpu_ticks as f64 / ticks),
+        rss_bytes,
+        threads,
+        uptime: Duration::from_secs_f64((system_uptime - start_secs).max(0.0)),
+    })
+}
+
+/// 读取进程表 (macOS: ps)
+#[cfg(target_os = "macos")]
+pub fn read_process_table() -> Vec<ProcSample> {
+    use std::process::Command;
+
+    let output = match Command::new("ps")
+        .args(["-axo", "pid=,ppid=,time=,rss=,etime="])
+        .output()
+    {
+        Ok(output) if output.status.success() => output,
+        _ => return Vec::new(),
+    };
+
+    let mut table: Vec<ProcSample> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            if cols.len() < 5 {
+                return None;
+            }
+            Some(ProcSample {
+                pid: cols[0].parse().ok()?,
+                ppid: cols[1].parse().ok()?,
+                cpu_time: parse_ps_time(cols[2])?,
+                rss_bytes: cols[3].parse::<u64>().ok()? * 1024,
+                threads: 0,
+                uptime: parse_ps_time(cols[4])?,
+            })
+        })
+        .collect();
+
+    // ps -M 每个线程输出一行 (含表头)
+    if let Ok(output) = Command::new("ps").args(["-axM", "-o", "pid="]).output() {
+        let mut counts: HashMap<u32, u32> = HashMap::new();
+        let mut current = None;
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            // 线程的后续行不重复 PID
+            if let Some(pid) = line.split_whitespace().find_map(|c| c.parse::<u32>().ok()) {
+                current = Some(pid);
+            }
+            if let Some(pid) = current {
+                *counts.entry(pid).or_insert(0) += 1;
+            }
+        }
+        for p in &mut table {
+            p.threads = counts.get(&p.pid).copied().unwrap_or(1);
+        }
+    }
+
+    table
+}
+
+/// 解析 ps 的时间格式: `[dd-][hh:]mm:ss[.cc]`
+#[cfg(any(target_os = "macos", test))]
+pub(crate) fn parse_ps_time(value: &str) -> Option<Duration> {
+    let (days, rest) = match value.split_once('-') {
+        Some((d, rest)) => (d.parse::<f64>().ok()?, rest),
+        None => (0.0, value),
+    };
+
+    let mut secs = 0.0;
+    for part in rest.split(':') {
+        secs = secs * 60.0 + part.parse::<f64>().ok()?;
+    }
+
+    Some(Duration::from_secs_f64(days * 86400.0 + secs))
+}
+
+/// 读取进程表 (Windows: ToolHelp 快照 + 进程计时/内存信息)
+#[cfg(target_os = "windows")]
+pub fn read_process_table() -> Vec<ProcSample> {
+    use windows::Win32::Foundation::*;
+    use windows::Win32::System::Diagnostics::ToolHelp::*;
+    use windows::Win32::System::ProcessStatus::*;
+    use windows::Win32::System::SystemInformation::GetSystemTimeAsFileTime;
+    use windows::Win32::System::Threading::*;
+
+    fn filetime_ticks(ft: &FILETIME) -> u64 {
+        ((ft.dwHighDateTime as u64) << 32) | ft.dwLowDateTime as u64
+    }
+
+    // FILETIME 单位为 100 纳秒
+    fn ticks_to_duration(ticks: u64) -> Duration {
+        Duration::from_nanos(ticks.saturating_mul(100))
+    }
+
+    let mut table = Vec::new();
+
+    unsafe {
+        let Ok(snapshot) = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) else {
+            return table;
+        };
+
+        let now = filetime_ticks(&GetSystemTimeAsFileTime());
+
+        let mut entry = PROCESSENTRY32W {
+            dwSize: std::mem::size_of::<PROCESSENTRY32W>() as u32,
+            ..Default::default()
+        };
+
+        let mut ok = Process32FirstW(snapshot, &mut entry).is_ok();
+        while ok {
+            let mut sample = ProcSample {
+                pid: entry.th32ProcessID,
+                ppid: entry.th32ParentProcessID,
+                threads: entry.cntThreads,
+                ..Default::default()
+            };
+
+            // 只申请受限查询权限: 计时和内存信息都不需要 PROCESS_VM_READ,
+            // 多申请的权限会让受保护或提权运行的进程打开失败
+            if let Ok(h_process) = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, sample.pid) {
+                let mut creation = FILETIME::default();
+                let mut exit = FILETIME::default();
+                let mut kernel = FILETIME::default();
+                let mut user = FILETIME::default();
+
+                if GetProcessTimes(h_process, &mut creation, &mut exit, &mut kernel, &mut user).is_ok() {
+                    sample.cpu_time = ticks_to_duration(filetime_ticks(&kernel) + filetime_ticks(&user));
+                    sample.uptime = ticks_to_duration(now.saturating_sub(filetime_ticks(&creation)));
+                }
+
+                let mut counters = PROCESS_MEMORY_COUNTERS::default();
+                if GetProcessMemoryInfo(
+                    h_process,
+                    &mut counters,
+                    std::mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32,
+                )
+                .is_ok()
+                {
+                    sample.rss_bytes = counters.WorkingSetSize as u64;
+                }
+
+                let _ = CloseHandle(h_process);
+            }
+
+            table.push(sample);
+            ok = Process32NextW(snapshot, &mut entry).is_ok();
+        }
+
+        let _ = CloseHandle(snapshot);
+    }
+
+    table
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+pub fn read_process_table() -> Vec<ProcSample> {
+    Vec::new()
+}
+
+/// 格式化字节数 (用于 CLI 展示)
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proc(pid: u32, ppid: u32, cpu_ms: u64, rss: u64) -> ProcSample {
+        ProcSample {
+            pid,
+            ppid,
+            cpu_time: Duration::from_millis(cpu_ms),
+            rss_bytes: rss,
+            threads: 2,
+            uptime: Duration::from_secs(10),
+        }
+    }
+
+    #[test]
+    fn test_descendants() {
+        let table = vec![proc(1, 0, 0, 0), proc(10, 1, 0, 0), proc(11, 10, 0, 0), proc(12, 11, 0, 0), proc(20, 1, 0, 0)];
+        let mut children = descendants(10, &table);
+        children.sort();
+        assert_eq!(children, vec![11, 12]);
+    }
+
+    #[test]
+    fn test_sample_includes_children_and_cpu_delta() {
+        let mut monitor = ResourceMonitor::new();
+        let start = Instant::now();
+
+        let first = vec![proc(10, 1, 1000, 100), proc(11, 10, 1000, 50)];
+        let stats = monitor.sample_from(&[10, 99], &first, start);
+        assert_eq!(stats.len(), 1, "不存在的实例应被跳过");
+        assert_eq!(stats[0].rss_bytes, 150);
+        assert_eq!(stats[0].threads, 4);
+        assert_eq!(stats[0].children, vec![11]);
+        // 首次采样: 2 秒 CPU / 10 秒运行时长
+        assert!((stats[0].cpu_percent - 20.0).abs() < 0.01);
+
+        let second = vec![proc(10, 1, 1500, 100), proc(11, 10, 1500, 50)];
+        let stats = monitor.sample_from(&[10], &second, start + Duration::from_secs(2));
+        // 2 秒内增加 1 秒 CPU
+        assert!((stats[0].cpu_percent - 50.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_tracked_child_is_not_counted_twice() {
+        let mut monitor = ResourceMonitor::new();
+        let table = vec![proc(10, 1, 0, 100), proc(11, 10, 0, 50), proc(12, 11, 0, 20), proc(13, 10, 0, 5)];
+
+        let stats = monitor.sample_from(&[10, 11], &table, Instant::now());
+        assert_eq!(stats[0].children, vec![13]);
+        assert_eq!(stats[0].rss_bytes, 105);
+        assert_eq!(stats[1].children, vec![12]);
+        assert_eq!(stats[1].rss_bytes, 70);
+    }
+
+    #[test]
+    fn test_parse_ps_time() {
+        assert_eq!(parse_ps_time("01:05"), Some(Duration::from_secs(65)));
+        assert_eq!(parse_ps_time("1:00:00"), Some(Duration::from_secs(3600)));
+        assert_eq!(parse_ps_time("2-00:00:01"), Some(Duration::from_secs(2 * 86400 + 1)));
+        assert_eq!(parse_ps_time("0:01.50"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_ps_time("abc"), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_proc_stat() {
+        let stat = "1234 (WXWork (x).exe) S 1 1234 1234 0 -1 4194560 100 0 0 0 250 50 0 0 20 0 17 0 1000 123456 300";
+        let status = "Name:\tWXWork.exe\nVmRSS:\t  2048 kB\nThreads:\t17\n";
+        let sample = parse_proc_stat(1234, stat, status, 100.0, 100.0).unwrap();
+        assert_eq!(sample.ppid, 1);
+        assert_eq!(sample.cpu_time, Duration::from_secs(3));
+        assert_eq!(sample.threads, 17);
+        assert_eq!(sample.rss_bytes, 2048 * 1024);
+        assert_eq!(sample.uptime, Duration::from_secs(90));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_sample_own_process() {
+        let mut monitor = ResourceMonitor::new();
+        let stats = monitor.sample(&[std::process::id()]);
+        assert_eq!(stats.len(), 1);
+        assert!(stats[0].rss_bytes > 0);
+        assert!(stats[0].threads >= 1);
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512.0 B");
+        assert_eq!(format_bytes(1536 * 1024), "1.5 MB");
+    }
+}
@@ -0,0 +1,911 @@
+// 应用路径检测 - 按顺序尝试多个来源,结果缓存到磁盘
+//
+// 默认来源顺序:
+//   1. 显式指定 (配置文件 app_paths)
+//   2. 环境变量 (WECOM_MULTI_WECOM_PATH / WECOM_MULTI_WECHAT_PATH)
+//   3. 缓存 (路径、文件大小、修改时间均一致才算命中)
+//   4. 注册表 (Windows)
+//   5. 正在运行的进程
+//   6. 常见安装目录
+//   7. 用户指定的搜索目录 (配置文件 app_paths.search_roots)
+
+use crate::config::{AppPaths, Config, LogLevel};
+use crate::discovery::{self, MatchKind};
+use crate::scanner::{self, Pattern, ScanOptions};
+use crate::{app_dirs, AppType};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// 缓存文件名 (位于缓存目录)
+pub const CACHE_FILE: &str = "paths.json";
+
+/// 路径来源
+pub trait PathSource: Send + Sync {
+    /// 来源名称 (用于日志和诊断)
+    fn name(&self) -> &str;
+
+    /// 按优先级列出考虑的所有候选路径 (不检查是否存在)
+    fn candidates(&self, app_type: &AppType) -> Vec<PathBuf>;
+
+    /// 第一个存在的候选路径
+    fn find(&self, app_type: &AppType) -> Option<PathBuf> {
+        self.candidates(app_type).into_iter().find(|p| p.exists())
+    }
+
+    /// 结果是否写入缓存 (显式指定的路径本身就很快,无需缓存)
+    fn cacheable(&self) -> bool {
+        true
+    }
+}
+
+/// 显式指定的路径
+pub struct OverrideSource {
+    paths: AppPaths,
+}
+
+impl OverrideSource {
+    pub fn new(paths: AppPaths) -> Self {
+        Self { paths }
+    }
+}
+
+impl PathSource for OverrideSource {
+    fn name(&self) -> &str {
+        "配置文件"
+    }
+
+    fn candidates(&self, app_type: &AppType) -> Vec<PathBuf> {
+        self.paths.get(app_type).cloned().into_iter().collect()
+    }
+
+    fn cacheable(&self) -> bool {
+        false
+    }
+}
+
+/// 环境变量
+pub struct EnvSource;
+
+impl EnvSource {
+    pub fn var_name(app_type: &AppType) -> &'static str {
+        match app_type {
+            AppType::WeCom => "WECOM_MULTI_WECOM_PATH",
+            AppType::WeChat => "WECOM_MULTI_WECHAT_PATH",
+        }
+    }
+}
+
+impl PathSource for EnvSource {
+    fn name(&self) -> &str {
+        "环境变量"
+    }
+
+    fn candidates(&self, app_type: &AppType) -> Vec<PathBuf> {
+        std::env::var_os(Self::var_name(app_type))
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .into_iter()
+            .collect()
+    }
+
+    fn cacheable(&self) -> bool {
+        false
+    }
+}
+
+/// 注册表中的安装路径 (Windows)
+pub struct RegistrySource;
+
+impl PathSource for RegistrySource {
+    fn name(&self) -> &str {
+        "注册表"
+    }
+
+    #[cfg(target_os = "windows")]
+    fn candidates(&self, app_type: &AppType) -> Vec<PathBuf> {
+        crate::platform::get_path_from_registry(app_type.clone()).into_iter().collect()
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    fn candidates(&self, _app_type: &AppType) -> Vec<PathBuf> {
+        Vec::new()
+    }
+}
+
+/// 正在运行的进程
+pub struct RunningProcessSource;
+
+impl PathSource for RunningProcessSource {
+    fn name(&self) -> &str {
+        "运行进程"
+    }
+
+    fn candidates(&self, app_type: &AppType) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = Vec::new();
+        let found = discovery::discover(app_type)
+            .into_iter()
+            // 仅按可执行文件名识别的进程才是真实的安装位置 (排除克隆实例和命令行匹配)
+            .filter(|instance| instance.matched_by == MatchKind::ExecutableName && instance.clone_id.is_none())
+            .filter_map(|instance| instance.exe_path)
+            .map(|exe| app_bundle_of(&exe).unwrap_or(exe));
+        for path in found {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+        paths
+    }
+}
+
+/// macOS 上可执行文件所在的 .app 包
+fn app_bundle_of(exe: &Path) -> Option<PathBuf> {
+    if !cfg!(target_os = "macos") {
+        return None;
+    }
+    exe.ancestors()
+        .find(|p| p.extension().is_some_and(|ext| ext == "app"))
+        .map(Path::to_path_buf)
+}
+
+/// 常见安装目录
+pub struct CommonDirsSource {
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    options: ScanOptions,
+}
+
+impl CommonDirsSource {
+    pub fn new(options: ScanOptions) -> Self {
+        Self { options }
+    }
+}
+
+impl PathSource for CommonDirsSource {
+    fn name(&self) -> &str {
+        "常见安装目录"
+    }
+
+    #[cfg(target_os = "windows")]
+    fn candidates(&self, app_type: &AppType) -> Vec<PathBuf> {
+        // 安装目录一般为 <根目录>\WXWork 或 <根目录>\Tencent\WXWork
+        let options = ScanOptions {
+            max_depth: self.options.max_depth.min(3),
+            ..self.options.clone()
+        };
+        let patterns: Vec<Pattern> = scanner::default_patterns(app_type).iter().map(|p| Pattern::new(p)).collect();
+        scanner::scan(&crate::platform::common_install_roots(), &patterns, &options).matches
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    fn candidates(&self, app_type: &AppType) -> Vec<PathBuf> {
+        let candidates: &[&str] = match app_type {
+            AppType::WeCom => &["/Applications/企业微信.app", "/Applications/WeCom.app"],
+            AppType::WeChat => &["/Applications/WeChat.app", "/Applications/微信.app"],
+        };
+        candidates.iter().map(PathBuf::from).collect()
+    }
+}
+
+/// 用户指定的搜索目录 (按通配符并行扫描)
+pub struct SearchRootsSource {
+    roots: Vec<PathBuf>,
+    patterns: Vec<String>,
+    options: ScanOptions,
+}
+
+impl SearchRootsSource {
+    /// `patterns` 为空时使用内置的文件名
+    pub fn new(roots: Vec<PathBuf>, patterns: Vec<String>, options: ScanOptions) -> Self {
+        Self { roots, patterns, options }
+    }
+}
+
+impl PathSource for SearchRootsSource {
+    fn name(&self) -> &str {
+        "搜索目录"
+    }
+
+    /// 找到的程序;不存在的搜索目录本身也列出,便于诊断
+    fn candidates(&self, app_type: &AppType) -> Vec<PathBuf> {
+        if self.roots.is_empty() {
+            return Vec::new();
+        }
+
+        let patterns: Vec<Pattern> = if self.patterns.is_empty() {
+            scanner::default_patterns(app_type).iter().map(|p| Pattern::new(p)).collect()
+        } else {
+            self.patterns.iter().map(|p| Pattern::new(p)).collect()
+        };
+
+        let mut candidates: Vec<PathBuf> = self.roots.iter().filter(|r| !r.exists()).cloned().collect();
+        let result = scanner::scan(&self.roots, &patterns, &self.options);
+        if result.timed_out {
+            eprintln!("⚠ 扫描搜索目录超时 ({}ms),结果可能不完整", self.options.timeout_ms);
+        }
+        candidates.extend(result.matches);
+        candidates
+    }
+}
+
+/// 缓存条目
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct CacheEntry {
+    app_type: AppType,
+    path: PathBuf,
+    size: u64,
+    /// 修改时间 (Unix 毫秒)
+    modified_ms: u64,
+    source: String,
+}
+
+/// 文件大小和修改时间
+fn file_key(path: &Path) -> Option<(u64, u64)> {
+    let meta = fs::metadata(path).ok()?;
+    let modified = meta
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_millis() as u64;
+    Some((meta.len(), modified))
+}
+
+/// 持久化的路径缓存 (可随时删除,格式不符时直接丢弃)
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PathCache {
+    #[serde(skip)]
+    path: PathBuf,
+    entries: Vec<CacheEntry>,
+}
+
+impl PathCache {
+    pub fn default_path() -> PathBuf {
+        app_dirs::cache_dir().join(CACHE_FILE)
+    }
+
+    pub fn load(path: &Path) -> Self {
+        let mut cache = fs::read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<Self>(&text).ok())
+            .unwrap_or_default();
+        cache.path = path.to_path_buf();
+        cache
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| format!("序列化路径缓存失败: {}", e))?;
+        app_dirs::write_atomic(&self.path, json.as_bytes())
+    }
+
+    /// 查询缓存,文件已被删除、替换或升级时视为未命中
+    fn get(&self, app_type: &AppType) -> Option<&CacheEntry> {
+        let entry = self.entries.iter().find(|e| &e.app_type == app_type)?;
+        match file_key(&entry.path) {
+            Some((size, modified_ms)) if size == entry.size && modified_ms == entry.modified_ms => Some(entry),
+            _ => None,
+        }
+    }
+
+    fn insert(&mut self, app_type: &AppType, path: &Path, source: &str) {
+        self.entries.retain(|e| &e.app_type != app_type);
+        if let Some((size, modified_ms)) = file_key(path) {
+            self.entries.push(CacheEntry {
+                app_type: app_type.clone(),
+                path: path.to_path_buf(),
+                size,
+                modified_ms,
+                source: source.to_string(),
+            });
+        }
+    }
+
+    fn remove(&mut self, app_type: &AppType) -> bool {
+        let before = self.entries.len();
+        self.entries.retain(|e| &e.app_type != app_type);
+        self.entries.len() != before
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// 检测结果
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Resolution {
+    pub path: PathBuf,
+    /// 来源名称
+    pub source: String,
+    /// 是否来自缓存
+    pub cached: bool,
+}
+
+/// 应用路径检测器
+#[derive(Default)]
+pub struct PathResolver {
+    sources: Vec<Box<dyn PathSource>>,
+    cache: Option<PathCache>,
+    /// 不把检测结果写入缓存文件
+    read_only: bool,
+}
+
+impl PathResolver {
+    /// 不带任何来源的检测器
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 默认来源和磁盘缓存
+    pub fn standard(config: &Config) -> Self {
+        Self::new()
+            .with_source(OverrideSource::new(config.app_paths.clone()))
+            .with_source(EnvSource)
+            .with_source(RegistrySource)
+            .with_source(RunningProcessSource)
+            .with_source(CommonDirsSource::new(config.scan.clone()))
+            .with_source(SearchRootsSource::new(
+                config.app_paths.search_roots.clone(),
+                config.app_paths.search_patterns.clone(),
+                config.scan.clone(),
+            ))
+            .with_cache(PathCache::load(&PathCache::default_path()))
+    }
+
+    /// 追加来源 (按添加顺序尝试)
+    pub fn with_source(mut self, source: impl PathSource + 'static) -> Self {
+        self.sources.push(Box::new(source));
+        self
+    }
+
+    pub fn with_cache(mut self, cache: PathCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// 只读取缓存,不写入缓存文件 (用于 doctor 等诊断命令)
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    /// 检测应用路径
+    ///
+    /// 不写缓存的来源 (显式指定) 优先于缓存,其余来源在缓存未命中时才尝试。
+    pub fn resolve(&mut self, app_type: &AppType) -> Option<Resolution> {
+        for source in self.sources.iter().filter(|s| !s.cacheable()) {
+            if let Some(path) = source.find(app_type) {
+                return Some(Resolution {
+                    path,
+                    source: source.name().to_string(),
+                    cached: false,
+                });
+            }
+        }
+
+        if let Some(entry) = self.cache.as_ref().and_then(|c| c.get(app_type)) {
+            return Some(Resolution {
+                path: entry.path.clone(),
+                source: entry.source.clone(),
+                cached: true,
+            });
+        }
+
+        let found = self
+            .sources
+            .iter()
+            .filter(|s| s.cacheable())
+            .find_map(|source| Some((source.find(app_type)?, source.name().to_string())));
+
+        match (found, self.cache.as_mut().filter(|_| !self.read_only)) {
+            (Some((path, source)), Some(cache)) => {
+                cache.insert(app_type, &path, &source);
+                if let Err(e) = cache.save() {
+                    eprintln!("⚠ 保存路径缓存失败: {}", e);
+                }
+                Some(Resolution { path, source, cached: false })
+            }
+            (Some((path, source)), None) => Some(Resolution { path, source, cached: false }),
+            (None, Some(cache)) => {
+                // 缓存已失效且重新检测失败,移除旧条目
+                if cache.remove(app_type) {
+                    let _ = cache.save();
+                }
+                None
+            }
+            (None, None) => None,
+        }
+    }
+
+    /// 诊断: 列出每个来源考虑过的候选路径及未被采用的原因 (不写缓存)
+    pub fn diagnose(&self, app_type: &AppType) -> PathReport {
+        let mut report = PathReport {
+            app_type: app_type.clone(),
+            selected: None,
+            sources: Vec::new(),
+        };
+
+        for source in self.sources.iter().filter(|s| !s.cacheable()) {
+            report.inspect(source.as_ref(), app_type);
+        }
+
+        if let Some(cache) = &self.cache {
+            let mut section = SourceReport {
+                source: "缓存".to_string(),
+                candidates: Vec::new(),
+            };
+            if let Some(entry) = cache.entries.iter().find(|e| &e.app_type == app_type) {
+                let valid = cache.get(app_type).is_some();
+                let rejected = if !entry.path.exists() {
+                    Some("文件不存在".to_string())
+                } else if !valid {
+                    Some("文件大小或修改时间已变化 (应用可能已升级)".to_string())
+                } else if report.selected.is_some() {
+                    Some("已采用更高优先级的结果".to_string())
+                } else {
+                    None
+                };
+                if rejected.is_none() {
+                    report.selected = Some(Resolution {
+                        path: entry.path.clone(),
+                        source: entry.source.clone(),
+                        cached: true,
+                    });
+                }
+                section.candidates.push(Candidate::new(&entry.path, rejected));
+            }
+            report.sources.push(section);
+        }
+
+        for source in self.sources.iter().filter(|s| s.cacheable()) {
+            report.inspect(source.as_ref(), app_type);
+        }
+
+        report
+    }
+
+    /// 清除缓存
+    pub fn clear_cache(&mut self) -> Result<(), String> {
+        match self.cache.as_mut() {
+            Some(cache) => {
+                cache.clear();
+                cache.save()
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// 候选路径
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Candidate {
+    pub path: PathBuf,
+    pub exists: bool,
+    /// 应用版本 (能读取时)
+    pub version: Option<String>,
+    /// 未被采用的原因 (为空表示被采用)
+    pub rejected: Option<String>,
+}
+
+impl Candidate {
+    fn new(path: &Path, rejected: Option<String>) -> Self {
+        let exists = path.exists();
+        Self {
+            path: path.to_path_buf(),
+            exists,
+            version: if exists { app_version(path) } else { None },
+            rejected,
+        }
+    }
+}
+
+/// 单个来源的诊断结果
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceReport {
+    pub source: String,
+    pub candidates: Vec<Candidate>,
+}
+
+/// 路径检测诊断报告
+#[derive(Debug, Clone, Serialize)]
+pub struct PathReport {
+    pub app_type: AppType,
+    /// 最终采用的路径
+    pub selected: Option<Resolution>,
+    pub sources: Vec<SourceReport>,
+}
+
+impl PathReport {
+    fn inspect(&mut self, source: &dyn PathSource, app_type: &AppType) {
+        let found = source.find(app_type);
+        let mut passed_found = false;
+        let candidates = source
+            .candidates(app_type)
+            .iter()
+            .map(|path| {
+                let is_found = !passed_found && found.as_ref() == Some(path);
+                let rejected = if !path.exists() {
+                    Some("文件不存在".to_string())
+                } else if is_found && self.selected.is_none() {
+                    None
+                } else if is_found || passed_found {
+                    Some("已采用更高优先级的结果".to_string())
+                } else {
+                    Some("不是有效的应用程序".to_string())
+                };
+                passed_found |= is_found;
+                Candidate::new(path, rejected)
+            })
+            .collect::<Vec<_>>();
+
+        if self.selected.is_none() {
+            if let Some(path) = &found {
+                self.selected = Some(Resolution {
+                    path: path.clone(),
+                    source: source.name().to_string(),
+                    cached: false,
+                });
+            }
+        }
+
+        self.sources.push(SourceReport {
+            source: source.name().to_string(),
+            candidates,
+        });
+    }
+
+    /// 未找到时的错误信息
+    pub fn failure_message(&self) -> String {
+        let app_name = match self.app_type {
+            AppType::WeCom => "企业微信",
+            AppType::WeChat => "微信",
+        };
+        let checked: Vec<String> = self
+            .sources
+            .iter()
+            .map(|s| format!("{} ({} 个候选)", s.source, s.candidates.len()))
+            .collect();
+
+        format!(
+            "未找到{}安装路径 (已检查: {})。请在配置文件中设置 app_paths,或运行 `wecom-multi-open-cli doctor paths` 查看详情",
+            app_name,
+            checked.join(", ")
+        )
+    }
+
+    /// 文本格式
+    pub fn render(&self) -> String {
+        let mut out = format!("[{:?}]\n", self.app_type);
+        for section in &self.sources {
+            out.push_str(&format!("  {}:\n", section.source));
+            if section.candidates.is_empty() {
+                out.push_str("    (无候选)\n");
+            }
+            for c in &section.candidates {
+                // 常见目录的候选很多,只列出存在的
+                if !c.exists && section.candidates.len() > 8 {
+                    continue;
+                }
+                let mark = if c.rejected.is_none() { "✓" } else if c.exists { "-" } else { "✗" };
+                out.push_str(&format!("    {} {}", mark, c.path.display()));
+                if let Some(version) = &c.version {
+                    out.push_str(&format!(" (版本 {})", version));
+                }
+                if let Some(reason) = &c.rejected {
+                    out.push_str(&format!(" - {}", reason));
+                }
+                out.push('\n');
+            }
+            let missing = section.candidates.iter().filter(|c| !c.exists).count();
+            if section.candidates.len() > 8 && missing > 0 {
+                out.push_str(&format!("    ✗ 另有 {} 个候选路径不存在\n", missing));
+            }
+        }
+        match &self.selected {
+            Some(r) => out.push_str(&format!("  => {} (来源: {})\n", r.path.display(), r.source)),
+            None => out.push_str("  => 未找到\n"),
+        }
+        out
+    }
+}
+
+/// 读取应用版本号
+#[cfg(target_os = "windows")]
+pub fn app_version(path: &Path) -> Option<String> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows::core::PCWSTR;
+    use windows::Win32::Storage::FileSystem::{
+        GetFileVersionInfoSizeW, GetFileVersionInfoW, VerQueryValueW, VS_FIXEDFILEINFO,
+    };
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let root: Vec<u16> = "\\".encode_utf16().chain(Some(0)).collect();
+
+    unsafe {
+        let size = GetFileVersionInfoSizeW(PCWSTR(wide.as_ptr()), None);
+        if size == 0 {
+            return None;
+        }
+
+        let mut buf = vec![0u8; size as usize];
+        GetFileVersionInfoW(PCWSTR(wide.as_ptr()), 0, size, buf.as_mut_ptr() as _).ok()?;
+
+        let mut info: *mut std::ffi::c_void = std::ptr::null_mut();
+        let mut len = 0u32;
+        if !VerQueryValueW(buf.as_ptr() as _, PCWSTR(root.as_ptr()), &mut info, &mut len).as_bool() || info.is_null() {
+            return None;
+        }
+
+        let info = &*(info as *const VS_FIXEDFILEINFO);
+        Some(format!(
+            "{}.{}.{}.{}",
+            info.dwFileVersionMS >> 16,
+            info.dwFileVersionMS & 0xffff,
+            info.dwFileVersionLS >> 16,
+            info.dwFileVersionLS & 0xffff
+        ))
+    }
+}
+
+/// 读取应用版本号 (Info.plist 中的 CFBundleShortVersionString)
+#[cfg(target_os = "macos")]
+pub fn app_version(path: &Path) -> Option<String> {
+    let output = std::process::Command::new("/usr/libexec/PlistBuddy")
+        .arg("-c")
+        .arg("Print :CFBundleShortVersionString")
+        .arg(path.join("Contents/Info.plist"))
+        .output()
+        .ok()?;
+    let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !version.is_empty()).then_some(version)
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+pub fn app_version(_path: &Path) -> Option<String> {
+    None
+}
+
+/// 使用当前配置诊断应用路径检测
+pub fn diagnose(app_type: &AppType) -> PathReport {
+    PathResolver::standard(&crate::config::current()).diagnose(app_type)
+}
+
+/// 使用当前配置检测应用路径,未找到时返回带诊断摘要的错误
+pub fn resolve_app_path(app_type: &AppType) -> Result<PathBuf, String> {
+    let config = crate::config::current();
+    let mut resolver = PathResolver::standard(&config);
+    let resolution = match resolver.resolve(app_type) {
+        Some(resolution) => resolution,
+        None => return Err(resolver.diagnose(app_type).failure_message()),
+    };
+
+    if !resolution.cached {
+        eprintln!("✓ 从{}找到路径: {}", resolution.source, resolution.path.display());
+    } else if config.log_enabled(LogLevel::Debug) {
+        eprintln!("✓ 从缓存读取路径: {}", resolution.path.display());
+    }
+
+    Ok(resolution.path)
+}
+
+/// 清除磁盘上的路径缓存
+pub fn clear_cache() -> Result<(), String> {
+    PathResolver::new()
+        .with_cache(PathCache::load(&PathCache::default_path()))
+        .clear_cache()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// 返回固定结果并记录调用次数的来源
+    struct FakeSource {
+        name: &'static str,
+        path: Option<PathBuf>,
+        cacheable: bool,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl FakeSource {
+        fn new(name: &'static str, path: Option<PathBuf>) -> (Self, Arc<AtomicUsize>) {
+            let calls = Arc::new(AtomicUsize::new(0));
+            let source = Self {
+                name,
+                path,
+                cacheable: true,
+                calls: calls.clone(),
+            };
+            (source, calls)
+        }
+    }
+
+    impl PathSource for FakeSource {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn candidates(&self, _app_type: &AppType) -> Vec<PathBuf> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            self.path.clone().into_iter().collect()
+        }
+
+        fn cacheable(&self) -> bool {
+            self.cacheable
+        }
+    }
+
+    fn touch(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn test_sources_tried_in_order() {
+        let dir = app_dirs::temp_test_dir("resolver-order");
+        let exe = dir.join("WXWork.exe");
+        touch(&exe, "x");
+
+        let (missing, missing_calls) = FakeSource::new("a", None);
+        let (stale, _) = FakeSource::new("b", Some(dir.join("gone.exe")));
+        let (found, _) = FakeSource::new("c", Some(exe.clone()));
+        let (unused, unused_calls) = FakeSource::new("d", Some(exe.clone()));
+
+        let mut resolver = PathResolver::new()
+            .with_source(missing)
+            .with_source(stale)
+            .with_source(found)
+            .with_source(unused);
+
+        let resolution = resolver.resolve(&AppType::WeCom).unwrap();
+        assert_eq!(resolution.path, exe);
+        assert_eq!(resolution.source, "c");
+        assert_eq!(missing_calls.load(Ordering::SeqCst), 1);
+        assert_eq!(unused_calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_cache_persists_across_resolvers() {
+        let dir = app_dirs::temp_test_dir("resolver-cache");
+        let exe = dir.join("WXWork.exe");
+        let cache_path = dir.join(CACHE_FILE);
+        touch(&exe, "v1");
+
+        let (source, calls) = FakeSource::new("scan", Some(exe.clone()));
+        let mut resolver = PathResolver::new()
+            .with_source(source)
+            .with_cache(PathCache::load(&cache_path));
+        assert!(!resolver.resolve(&AppType::WeCom).unwrap().cached);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // 新进程: 直接命中磁盘缓存,不再调用来源
+        let (source, calls) = FakeSource::new("scan", Some(exe.clone()));
+        let mut resolver = PathResolver::new()
+            .with_source(source)
+            .with_cache(PathCache::load(&cache_path));
+        let resolution = resolver.resolve(&AppType::WeCom).unwrap();
+        assert!(resolution.cached);
+        assert_eq!(resolution.source, "scan");
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_read_only_does_not_write_cache() {
+        let dir = app_dirs::temp_test_dir("resolver-read-only");
+        let exe = dir.join("WXWork.exe");
+        let cache_path = dir.join(CACHE_FILE);
+        touch(&exe, "v1");
+
+        let (source, _) = FakeSource::new("scan", Some(exe.clone()));
+        let mut resolver = PathResolver::new()
+            .with_source(source)
+            .with_cache(PathCache::load(&cache_path))
+            .read_only();
+        assert_eq!(resolver.resolve(&AppType::WeCom).unwrap().path, exe);
+        assert!(!cache_path.exists());
+    }
+
+    #[test]
+    fn test_cache_invalidated_when_file_changes() {
+        let dir = app_dirs::temp_test_dir("resolver-invalidate");
+        let exe = dir.join("WXWork.exe");
+        let cache_path = dir.join(CACHE_FILE);
+        touch(&exe, "v1");
+
+        let (source, _) = FakeSource::new("scan", Some(exe.clone()));
+        PathResolver::new()
+            .with_source(source)
+            .with_cache(PathCache::load(&cache_path))
+            .resolve(&AppType::WeCom);
+
+        // 升级后文件大小变化
+        touch(&exe, "version 2");
+
+        let (source, calls) = FakeSource::new("scan", Some(exe.clone()));
+        let mut resolver = PathResolver::new()
+            .with_source(source)
+            .with_cache(PathCache::load(&cache_path));
+        assert!(!resolver.resolve(&AppType::WeCom).unwrap().cached);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_override_beats_cache() {
+        let dir = app_dirs::temp_test_dir("resolver-override");
+        let cached = dir.join("old/WXWork.exe");
+        let chosen = dir.join("new/WXWork.exe");
+        touch(&cached, "x");
+        touch(&chosen, "x");
+
+        let mut cache = PathCache::load(&dir.join(CACHE_FILE));
+        cache.insert(&AppType::WeCom, &cached, "scan");
+
+        let mut resolver = PathResolver::new()
+            .with_source(OverrideSource::new(AppPaths {
+                wecom: Some(chosen.clone()),
+                ..Default::default()
+            }))
+            .with_cache(cache);
+
+        let resolution = resolver.resolve(&AppType::WeCom).unwrap();
+        assert_eq!(resolution.path, chosen);
+        assert!(!resolution.cached);
+    }
+
+    #[test]
+    fn test_search_roots_find_nested_binary() {
+        let dir = app_dirs::temp_test_dir("resolver-roots");
+        let name = if cfg!(target_os = "macos") { "WeCom.app" } else { "WXWork.exe" };
+        let target = dir.join("Tencent").join(name);
+        if cfg!(target_os = "macos") {
+            fs::create_dir_all(&target).unwrap();
+        } else {
+            touch(&target, "x");
+        }
+        touch(&dir.join("Tencent/readme.txt"), "x");
+
+        let source = SearchRootsSource::new(vec![dir.join("missing"), dir.clone()], Vec::new(), ScanOptions::default());
+        assert_eq!(source.find(&AppType::WeCom), Some(target.clone()));
+        assert_eq!(source.find(&AppType::WeChat), None);
+
+        // 自定义通配符
+        let source = SearchRootsSource::new(vec![dir.clone()], vec!["tencent/*".to_string()], ScanOptions::default());
+        assert_eq!(source.candidates(&AppType::WeChat).len(), 2);
+    }
+
+    #[test]
+    fn test_diagnose_explains_rejections() {
+        let dir = app_dirs::temp_test_dir("resolver-diagnose");
+        let exe = dir.join("WXWork.exe");
+        touch(&exe, "x");
+
+        let (missing, _) = FakeSource::new("registry", Some(dir.join("gone.exe")));
+        let (found, _) = FakeSource::new("scan", Some(exe.clone()));
+        let (shadowed, _) = FakeSource::new("roots", Some(exe.clone()));
+        let resolver = PathResolver::new()
+            .with_source(missing)
+            .with_source(found)
+            .with_source(shadowed);
+
+        let report = resolver.diagnose(&AppType::WeCom);
+        assert_eq!(report.selected.as_ref().unwrap().source, "scan");
+        assert_eq!(report.sources[0].candidates[0].rejected.as_deref(), Some("文件不存在"));
+        assert!(report.sources[1].candidates[0].rejected.is_none());
+        assert!(report.sources[1].candidates[0].exists);
+        assert_eq!(report.sources[2].candidates[0].rejected.as_deref(), Some("已采用更高优先级的结果"));
+    }
+
+    #[test]
+    fn test_nothing_found_is_an_error_with_summary() {
+        let dir = app_dirs::temp_test_dir("resolver-missing");
+        let (missing, _) = FakeSource::new("registry", Some(dir.join("gone.exe")));
+        let mut resolver = PathResolver::new().with_source(missing);
+
+        assert!(resolver.resolve(&AppType::WeChat).is_none());
+        let message = resolver.diagnose(&AppType::WeChat).failure_message();
+        assert!(message.contains("registry (1 个候选)"), "{}", message);
+        assert!(message.contains("doctor paths"), "{}", message);
+    }
+}
@@ -0,0 +1,316 @@
+// 启动计划 - dry-run 模式下列出将要执行的操作
+//
+// 生成计划不会启动进程、关闭 Mutex、克隆应用或修改 Sandboxie 配置。
+
+use crate::config::Config;
+use crate::discovery::{self, AppProfile};
+use crate::windows_sandbox::{IniChange, ReconcileReport};
+use crate::{AppType, InstanceConfig, IsolationMode, SpawnRequest};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Windows 简单模式默认实例间隔
+const WINDOWS_LAUNCH_INTERVAL_MS: u64 = 800;
+/// macOS 默认实例间隔
+const MACOS_LAUNCH_INTERVAL_MS: u64 = 1000;
+/// Sandboxie 模式实例间隔
+const SANDBOXIE_LAUNCH_INTERVAL_MS: u64 = 2000;
+
+/// 启动计划
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SpawnPlan {
+    pub platform: String,
+    pub app_type: AppType,
+    pub isolation: IsolationMode,
+    /// 解析出的可执行文件 (macOS 为原始应用包)
+    pub executable: PathBuf,
+    /// 每个实例启动前要关闭的 Mutex
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mutex_names: Vec<String>,
+    /// 关闭 Mutex 后的等待时间
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mutex_delay_ms: Option<u64>,
+    pub launch_interval_ms: u64,
+    pub instances: Vec<PlannedInstance>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// 计划中的单个实例
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct PlannedInstance {
+    /// 实例序号 (从 1 开始)
+    pub index: u8,
+    /// 实际启动的程序
+    pub program: PathBuf,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_dir: Option<PathBuf>,
+    /// macOS 克隆应用包路径
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clone_path: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bundle_id: Option<String>,
+    /// Sandboxie 沙盒名称
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ini_changes: Vec<IniChange>,
+}
+
+impl SpawnPlan {
+    /// Windows 简单模式: 关闭 Mutex 后直接启动
+    pub fn windows_mutex(req: &SpawnRequest, exe: &Path, config: &Config) -> Self {
+        let app_type = req.app_type.clone().unwrap_or_default();
+        let mut warnings = Vec::new();
+
+        let instances = (0..req.count)
+            .map(|i| {
+                let instance_config = instance_config(req, i);
+                if instance_config.data_dir.is_some() {
+                    warnings.push(format!("实例 {}: Windows 简单模式不支持独立数据目录,将忽略该设置", i + 1));
+                }
+                PlannedInstance {
+                    index: i + 1,
+                    program: exe.to_path_buf(),
+                    args: instance_config.args.clone(),
+                    env: instance_config.proxy_env().into_iter().collect(),
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        Self {
+            platform: "windows".to_string(),
+            mutex_names: vec![AppProfile::for_type(&app_type).mutex_name.to_string()],
+            mutex_delay_ms: Some(config.spawn.mutex_delay_ms),
+            launch_interval_ms: config.spawn.launch_interval_ms.unwrap_or(WINDOWS_LAUNCH_INTERVAL_MS),
+            app_type,
+            isolation: IsolationMode::Simple,
+            executable: exe.to_path_buf(),
+            instances,
+            warnings,
+        }
+    }
+
+    /// macOS: 为每个实例克隆应用包并修改 Bundle ID
+    pub fn macos_clone(req: &SpawnRequest, source_app: &Path, home: &Path, config: &Config) -> Self {
+        let app_stem = source_app.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+
+        let instances = (0..req.count)
+            .map(|i| {
+                let instance_config = instance_config(req, i);
+                let instance_id = instance_config.instance_id.unwrap_or(i + 1);
+                let clone_path = discovery::macos_clone_path(home, source_app, instance_id);
+                let data_dir = instance_config
+                    .data_dir
+                    .clone()
+                    .unwrap_or_else(|| discovery::macos_data_dir(home, instance_id));
+
+                let mut env: BTreeMap<String, String> = discovery::macos_instance_env(&data_dir).into_iter().collect();
+                env.extend(instance_config.proxy_env());
+
+                PlannedInstance {
+                    index: i + 1,
+                    program: clone_path.join("Contents/MacOS").join(&app_stem),
+                    args: instance_config.args.clone(),
+                    env,
+                    data_dir: Some(data_dir),
+                    bundle_id: Some(discovery::macos_bundle_id(instance_id)),
+                    clone_path: Some(clone_path),
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        Self {
+            platform: "macos".to_string(),
+            app_type: req.app_type.clone().unwrap_or_default(),
+            isolation: IsolationMode::Simple,
+            executable: source_app.to_path_buf(),
+            mutex_names: Vec::new(),
+            mutex_delay_ms: None,
+            launch_interval_ms: config.spawn.launch_interval_ms.unwrap_or(MACOS_LAUNCH_INTERVAL_MS),
+            instances,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Windows Sandboxie 模式: 每个实例一个沙盒,列出与现有沙盒对账后实际要执行的修改
+    pub fn sandboxie(exe: &Path, boxes: &[ReconcileReport]) -> Self {
+        let instances = boxes
+            .iter()
+            .enumerate()
+            .map(|(i, report)| PlannedInstance {
+                index: i as u8 + 1,
+                program: exe.to_path_buf(),
+                sandbox: Some(report.name.clone()),
+                ini_changes: report.changes.clone(),
+                ..Default::default()
+            })
+            .collect();
+
+        Self {
+            platform: "windows".to_string(),
+            app_type: AppType::WeCom,
+            isolation: IsolationMode::Sandboxie,
+            executable: exe.to_path_buf(),
+            mutex_names: Vec::new(),
+            mutex_delay_ms: None,
+            launch_interval_ms: SANDBOXIE_LAUNCH_INTERVAL_MS,
+            instances,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// 文本格式输出
+    pub fn render(&self) -> String {
+        let app_name = match self.app_type {
+            AppType::WeCom => "企业微信",
+            AppType::WeChat => "微信",
+        };
+        let isolation = match self.isolation {
+            IsolationMode::Simple => "simple",
+            IsolationMode::Sandboxie => "sandboxie",
+        };
+
+        let mut out = format!(
+            "启动计划 (dry-run,不会启动任何实例)\n应用: {} × {} ({}, {} 模式)\n可执行文件: {}\n",
+            app_name,
+            self.instances.len(),
+            self.platform,
+            isolation,
+            self.executable.display()
+        );
+        for name in &self.mutex_names {
+            out.push_str(&format!("关闭 Mutex: {}", name));
+            if let Some(delay) = self.mutex_delay_ms {
+                out.push_str(&format!(" (之后等待 {}ms)", delay));
+            }
+            out.push('\n');
+        }
+        out.push_str(&format!("实例间隔: {}ms\n", self.launch_interval_ms));
+
+        for instance in &self.instances {
+            out.push_str(&format!("\n实例 {}:\n", instance.index));
+            if let Some(clone_path) = &instance.clone_path {
+                out.push_str(&format!("  克隆到: {}\n", clone_path.display()));
+            }
+            if let Some(bundle_id) = &instance.bundle_id {
+                out.push_str(&format!("  Bundle ID: {}\n", bundle_id));
+            }
+            if let Some(sandbox) = &instance.sandbox {
+                out.push_str(&format!("  沙盒: {}\n", sandbox));
+                if instance.ini_changes.is_empty() {
+                    out.push_str("  Sandboxie.ini: 已符合配置,无需修改\n");
+                }
+            }
+            for change in &instance.ini_changes {
+                out.push_str(&format!("  Sandboxie.ini: {} [{}] {}={}\n", change.op, change.section, change.key, change.value));
+            }
+            out.push_str(&format!("  启动: {}", instance.program.display()));
+            for arg in &instance.args {
+                out.push_str(&format!(" {}", arg));
+            }
+            out.push('\n');
+            if let Some(data_dir) = &instance.data_dir {
+                out.push_str(&format!("  数据目录: {}\n", data_dir.display()));
+            }
+            for (key, value) in &instance.env {
+                out.push_str(&format!("  环境变量: {}={}\n", key, value));
+            }
+        }
+
+        for warning in &self.warnings {
+            out.push_str(&format!("\n⚠ {}", warning));
+        }
+        out.trim_end().to_string()
+    }
+}
+
+fn instance_config(req: &SpawnRequest, index: u8) -> InstanceConfig {
+    req.instance_configs
+        .as_ref()
+        .and_then(|c| c.get(index as usize))
+        .cloned()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::windows_sandbox::SandboxConfig;
+
+    fn request(count: u8, instance_configs: Option<Vec<InstanceConfig>>) -> SpawnRequest {
+        SpawnRequest {
+            count,
+            app_path: None,
+            app_type: Some(AppType::WeCom),
+            instance_configs,
+            dry_run: true,
+        }
+    }
+
+    #[test]
+    fn test_windows_mutex_plan() {
+        let configs = vec![InstanceConfig {
+            data_dir: Some(PathBuf::from(r"D:\WeCom\1")),
+            proxy: Some("http://127.0.0.1:7890".to_string()),
+            args: vec!["--debug".to_string()],
+            ..Default::default()
+        }];
+        let exe = PathBuf::from(r"C:\Program Files\WXWork\WXWork.exe");
+        let plan = SpawnPlan::windows_mutex(&request(2, Some(configs)), &exe, &Config::default());
+
+        assert_eq!(plan.mutex_names, ["Tencent.WeWork.Exclusive"]);
+        assert_eq!(plan.instances.len(), 2);
+        assert_eq!(plan.instances[0].args, ["--debug"]);
+        assert_eq!(plan.instances[0].env.get("HTTPS_PROXY").map(String::as_str), Some("http://127.0.0.1:7890"));
+        assert!(plan.instances[1].env.is_empty());
+        assert_eq!(plan.warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_macos_clone_plan() {
+        let home = PathBuf::from("/Users/test");
+        let app = PathBuf::from("/Applications/企业微信.app");
+        let configs = vec![
+            InstanceConfig::default(),
+            InstanceConfig {
+                instance_id: Some(101),
+                data_dir: Some(PathBuf::from("/data/sales")),
+                ..Default::default()
+            },
+        ];
+        let plan = SpawnPlan::macos_clone(&request(2, Some(configs)), &app, &home, &Config::default());
+
+        let first = &plan.instances[0];
+        assert_eq!(first.clone_path, Some(home.join("Applications/WeComMulti/企业微信1.app")));
+        assert_eq!(first.bundle_id.as_deref(), Some("com.tencent.WeWorkMac.instance1"));
+        assert_eq!(first.program, home.join("Applications/WeComMulti/企业微信1.app/Contents/MacOS/企业微信"));
+        assert_eq!(first.data_dir, Some(discovery::macos_data_dir(&home, 1)));
+
+        let second = &plan.instances[1];
+        assert_eq!(second.bundle_id.as_deref(), Some("com.tencent.WeWorkMac.instance101"));
+        assert_eq!(second.env.get("HOME").map(String::as_str), Some("/data/sales"));
+        assert_eq!(second.env.get("TMPDIR").map(String::as_str), Some("/data/sales/tmp"));
+    }
+
+    #[test]
+    fn test_sandboxie_plan_json() {
+        let exe = PathBuf::from(r"C:\Program Files\WXWork\WXWork.exe");
+        let plan = SpawnPlan::sandboxie(&exe, &[SandboxConfig::new("WeCom_1").reconcile(None)]);
+
+        assert_eq!(plan.instances[0].sandbox.as_deref(), Some("WeCom_1"));
+        assert_eq!(plan.instances[0].ini_changes.len(), 2);
+
+        let json = serde_json::to_value(&plan).unwrap();
+        assert_eq!(json["isolation"], "sandboxie");
+        assert!(json["instances"][0].get("bundle_id").is_none());
+        assert!(plan.render().contains("Sandboxie.ini: set [WeCom_1] ConfigLevel=7"));
+    }
+}
@@ -0,0 +1,406 @@
+// 账号配置 - 为实例命名并固定其数据目录、代理、启动参数等
+//
+// 同一个账号配置每次启动都使用相同的数据目录,登录状态得以保留。
+
+use crate::hooks::{self, HookContext, HookEvent, Hooks};
+use crate::registry::InstanceRecord;
+use crate::scheduler::ScheduleRule;
+use crate::{app_dirs, migrations, platform, AppType, InstanceConfig, IsolationMode, SpawnRequest};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 账号配置文件名 (位于配置目录)
+pub const PROFILES_FILE: &str = "profiles.json";
+
+/// 账号配置的 macOS 克隆包编号起始值,与匿名实例 (1, 2, 3...) 区分
+const PROFILE_INSTANCE_ID_BASE: u8 = 101;
+
+/// 账号配置
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AccountProfile {
+    /// 名称 (唯一),如 "Sales-Beijing"
+    pub name: String,
+    #[serde(default)]
+    pub app_type: AppType,
+    /// 应用路径 (为空时自动检测)
+    #[serde(default)]
+    pub app_path: Option<PathBuf>,
+    /// 数据目录 (为空时使用 <数据目录>/profiles/<名称>)
+    #[serde(default)]
+    pub data_dir: Option<PathBuf>,
+    /// 代理 (如 "http://127.0.0.1:7890")
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// 标识颜色 (#RRGGBB)
+    #[serde(default)]
+    pub color: Option<String>,
+    /// 额外启动参数
+    #[serde(default)]
+    pub launch_args: Vec<String>,
+    #[serde(default)]
+    pub isolation: IsolationMode,
+    /// 实例编号 (macOS 克隆包编号),创建时自动分配
+    #[serde(default)]
+    pub instance_id: Option<u8>,
+    /// 生命周期钩子
+    #[serde(default)]
+    pub hooks: Hooks,
+    /// 定时启动 / 停止规则
+    #[serde(default)]
+    pub schedule: Vec<ScheduleRule>,
+}
+
+impl AccountProfile {
+    pub fn new(name: &str, app_type: AppType) -> Self {
+        Self {
+            name: name.to_string(),
+            app_type,
+            app_path: None,
+            data_dir: None,
+            proxy: None,
+            color: None,
+            launch_args: Vec::new(),
+            isolation: IsolationMode::Simple,
+            instance_id: None,
+            hooks: Hooks::default(),
+            schedule: Vec::new(),
+        }
+    }
+
+    /// 校验配置
+    pub fn validate(&self) -> Result<(), String> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            return Err("账号配置名称不能为空".to_string());
+        }
+        if name != self.name {
+            return Err(format!("账号配置名称首尾不能有空格: '{}'", self.name));
+        }
+        // 名称会用作目录名
+        if name.chars().any(|c| matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') || c.is_control()) {
+            return Err(format!("账号配置名称包含非法字符: '{}'", self.name));
+        }
+        if name == "." || name == ".." {
+            return Err(format!("账号配置名称无效: '{}'", self.name));
+        }
+
+        if let Some(color) = &self.color {
+            let hex = color.strip_prefix('#').unwrap_or("");
+            if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!("颜色格式应为 #RRGGBB: '{}'", color));
+            }
+        }
+
+        if let Some(proxy) = &self.proxy {
+            if !proxy.contains("://") {
+                return Err(format!("代理格式应为 scheme://host:port: '{}'", proxy));
+            }
+        }
+
+        for rule in &self.schedule {
+            rule.validate()?;
+        }
+
+        Ok(())
+    }
+
+    /// 实际使用的数据目录
+    pub fn resolved_data_dir(&self) -> PathBuf {
+        self.data_dir
+            .clone()
+            .unwrap_or_else(|| app_dirs::data_dir().join("profiles").join(&self.name))
+    }
+
+    /// Sandboxie 沙盒名称
+    ///
+    /// 由唯一的实例编号生成,与匿名实例的 WeCom_N 区分,改名后仍使用原来的沙盒。
+    /// 尚未保存 (没有实例编号) 的配置没有沙盒。
+    pub fn sandbox_name(&self) -> Option<String> {
+        self.instance_id.map(|id| format!("WeComP_{}", id))
+    }
+
+    /// 实例配置
+    pub fn instance_config(&self) -> InstanceConfig {
+        InstanceConfig {
+            data_dir: Some(self.resolved_data_dir()),
+            proxy: self.proxy.clone(),
+            instance_id: self.instance_id,
+            args: self.launch_args.clone(),
+        }
+    }
+
+    /// 启动该账号的请求
+    pub fn spawn_request(&self) -> SpawnRequest {
+        SpawnRequest {
+            count: 1,
+            app_path: self.app_path.clone(),
+            app_type: Some(self.app_type.clone()),
+            instance_configs: Some(vec![self.instance_config()]),
+            dry_run: false,
+        }
+    }
+}
+
+/// 账号配置存储
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProfileStore {
+    #[serde(skip)]
+    path: PathBuf,
+    /// 格式版本 (旧文件缺失时迁移不改写文件,按当前版本处理)
+    #[serde(default = "current_version")]
+    pub schema_version: u32,
+    pub profiles: Vec<AccountProfile>,
+}
+
+fn current_version() -> u32 {
+    migrations::PROFILES.current
+}
+
+impl Default for ProfileStore {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::new(),
+            schema_version: migrations::PROFILES.current,
+            profiles: Vec::new(),
+        }
+    }
+}
+
+impl ProfileStore {
+    /// 默认存储路径
+    pub fn default_path() -> PathBuf {
+        app_dirs::config_dir().join(PROFILES_FILE)
+    }
+
+    pub fn load_default() -> Result<Self, String> {
+        Self::load(&Self::default_path())
+    }
+
+    /// 加载账号配置,文件不存在时返回空列表
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let mut store = match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str::<Self>(&migrations::upgrade_file(&migrations::PROFILES, path, text)?)
+                .map_err(|e| format!("解析账号配置失败 {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => return Err(format!("读取账号配置失败 {}: {}", path.display(), e)),
+        };
+
+        store.path = path.to_path_buf();
+        Ok(store)
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| format!("序列化账号配置失败: {}", e))?;
+        app_dirs::write_atomic(&self.path, json.as_bytes())
+    }
+
+    pub fn list(&self) -> &[AccountProfile] {
+        &self.profiles
+    }
+
+    pub fn get(&self, name: &str) -> Option<&AccountProfile> {
+        self.profiles.iter().find(|p| p.name == name)
+    }
+
+    /// 新建账号配置
+    pub fn create(&mut self, mut profile: AccountProfile) -> Result<&AccountProfile, String> {
+        profile.validate()?;
+        if self.get(&profile.name).is_some() {
+            return Err(format!("账号配置已存在: {}", profile.name));
+        }
+
+        if profile.instance_id.is_none() {
+            profile.instance_id = Some(self.next_instance_id()?);
+        }
+
+        self.profiles.push(profile);
+        Ok(self.profiles.last().unwrap())
+    }
+
+    /// 更新账号配置 (支持改名)
+    pub fn update(&mut self, name: &str, mut profile: AccountProfile) -> Result<&AccountProfile, String> {
+        profile.validate()?;
+
+        let index = self
+            .profiles
+            .iter()
+            .position(|p| p.name == name)
+            .ok_or_else(|| format!("账号配置不存在: {}", name))?;
+
+        if profile.name != name && self.get(&profile.name).is_some() {
+            return Err(format!("账号配置已存在: {}", profile.name));
+        }
+
+        // 改名后继续使用原来的数据目录和克隆包,避免丢失登录状态
+        let old = &self.profiles[index];
+        if profile.data_dir.is_none() {
+            profile.data_dir = Some(old.resolved_data_dir());
+        }
+        if profile.instance_id.is_none() {
+            profile.instance_id = old.instance_id;
+        }
+
+        self.profiles[index] = profile;
+        Ok(&self.profiles[index])
+    }
+
+    /// 删除账号配置 (数据目录保留)
+    pub fn delete(&mut self, name: &str) -> Result<AccountProfile, String> {
+        let index = self
+            .profiles
+            .iter()
+            .position(|p| p.name == name)
+            .ok_or_else(|| format!("账号配置不存在: {}", name))?;
+        Ok(self.profiles.remove(index))
+    }
+
+    fn next_instance_id(&self) -> Result<u8, String> {
+        (PROFILE_INSTANCE_ID_BASE..=u8::MAX)
+            .find(|id| !self.profiles.iter().any(|p| p.instance_id == Some(*id)))
+            .ok_or_else(|| "账号配置数量已达上限".to_string())
+    }
+}
+
+/// 启动账号配置,返回应记录到实例注册表的实例信息
+pub async fn launch(profile: &AccountProfile) -> Result<InstanceRecord, String> {
+    // Windows 简单模式无法指定数据目录,以共享数据启动会混用账号,也不能记录未生效的数据目录
+    if cfg!(target_os = "windows") && profile.isolation == IsolationMode::Simple {
+        return Err(format!(
+            "账号配置 {} 使用简单模式: Windows 上简单模式无法使用账号独立的数据目录,请改用 Sandboxie 隔离 (profile set {} --isolation sandboxie)",
+            profile.name, profile.name
+        ));
+    }
+
+    let data_dir = profile.resolved_data_dir();
+    fs::create_dir_all(&data_dir).map_err(|e| format!("创建数据目录失败 {}: {}", data_dir.display(), e))?;
+
+    eprintln!("启动账号配置: {} (数据目录: {})", profile.name, data_dir.display());
+    hooks::run(&profile.hooks, &HookContext::for_profile(HookEvent::PreSpawn, profile)).await;
+
+    let mut record = match profile.isolation {
+        IsolationMode::Simple => {
+            let response = platform::spawn_multiple(profile.spawn_request()).await?;
+            let pid = response
+                .pids
+                .first()
+                .copied()
+                .ok_or_else(|| format!("账号 {} 启动失败", profile.name))?;
+            InstanceRecord::new(pid, profile.app_type.clone(), IsolationMode::Simple)
+        }
+        IsolationMode::Sandboxie => launch_in_sandbox(profile).await?,
+    };
+
+    record.profile = Some(profile.name.clone());
+    record.data_dir = Some(data_dir);
+    if record.exe_path.is_none() {
+        record.exe_path = profile.app_path.clone();
+    }
+    hooks::run(&profile.hooks, &HookContext::for_record(HookEvent::PostSpawn, &record)).await;
+    Ok(record)
+}
+
+#[cfg(target_os = "windows")]
+async fn launch_in_sandbox(profile: &AccountProfile) -> Result<InstanceRecord, String> {
+    // 创建沙盒和等待启动锁会阻塞,放到阻塞线程池执行,不占用异步运行时的工作线程
+    let owned = profile.clone();
+    let instance = tokio::task::spawn_blocking(move || crate::wecom_manager::WeComManager::launch_profile(&owned))
+        .await
+        .map_err(|e| format!("账号 {} 启动失败: {}", profile.name, e))??;
+    let pid = instance.pid.ok_or_else(|| format!("账号 {} 启动失败", profile.name))?;
+
+    let mut record = InstanceRecord::new(pid, profile.app_type.clone(), IsolationMode::Sandboxie);
+    record.sandbox_name = Some(instance.sandbox_name);
+    Ok(record)
+}
+
+#[cfg(not(target_os = "windows"))]
+async fn launch_in_sandbox(_profile: &AccountProfile) -> Result<InstanceRecord, String> {
+    Err("Sandboxie 仅支持 Windows 平台".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        assert!(AccountProfile::new("Sales-Beijing", AppType::WeCom).validate().is_ok());
+        assert!(AccountProfile::new("客服 2", AppType::WeCom).validate().is_ok());
+        assert!(AccountProfile::new("", AppType::WeCom).validate().is_err());
+        assert!(AccountProfile::new("a/b", AppType::WeCom).validate().is_err());
+        assert!(AccountProfile::new(" a", AppType::WeCom).validate().is_err());
+
+        let mut p = AccountProfile::new("a", AppType::WeCom);
+        p.color = Some("red".to_string());
+        assert!(p.validate().is_err());
+        p.color = Some("#FF8800".to_string());
+        assert!(p.validate().is_ok());
+        p.proxy = Some("127.0.0.1:7890".to_string());
+        assert!(p.validate().is_err());
+    }
+
+    #[test]
+    fn test_crud() {
+        let dir = app_dirs::temp_test_dir("profiles-crud");
+        let path = dir.join(PROFILES_FILE);
+
+        let mut store = ProfileStore::load(&path).unwrap();
+        let mut sales = AccountProfile::new("Sales-Beijing", AppType::WeCom);
+        sales.data_dir = Some(PathBuf::from("/data/sales"));
+        assert_eq!(store.create(sales).unwrap().instance_id, Some(101));
+        assert_eq!(
+            store.create(AccountProfile::new("Support-2", AppType::WeChat)).unwrap().instance_id,
+            Some(102)
+        );
+        assert!(store.create(AccountProfile::new("Support-2", AppType::WeCom)).is_err());
+        store.save().unwrap();
+
+        let mut store = ProfileStore::load(&path).unwrap();
+        assert_eq!(store.list().len(), 2);
+
+        // 改名保留数据目录和实例编号
+        let renamed = AccountProfile::new("Sales-Shanghai", AppType::WeCom);
+        let updated = store.update("Sales-Beijing", renamed).unwrap();
+        assert_eq!(updated.data_dir, Some(PathBuf::from("/data/sales")));
+        assert_eq!(updated.instance_id, Some(101));
+        assert!(store.get("Sales-Beijing").is_none());
+
+        assert!(store.update("Support-2", AccountProfile::new("Sales-Shanghai", AppType::WeCom)).is_err());
+
+        store.delete("Support-2").unwrap();
+        assert!(store.delete("Support-2").is_err());
+        assert_eq!(store.list().len(), 1);
+    }
+
+    #[test]
+    fn test_spawn_request_reuses_data_dir() {
+        let mut p = AccountProfile::new("Sales-Beijing", AppType::WeChat);
+        p.data_dir = Some(PathBuf::from("/data/sales"));
+        p.proxy = Some("http://127.0.0.1:7890".to_string());
+        p.launch_args = vec!["--debug".to_string()];
+
+        let req = p.spawn_request();
+        assert_eq!(req.count, 1);
+        assert_eq!(req.app_type, Some(AppType::WeChat));
+        let config = &req.instance_configs.unwrap()[0];
+        assert_eq!(config.data_dir, Some(PathBuf::from("/data/sales")));
+        assert_eq!(config.args, vec!["--debug".to_string()]);
+    }
+
+    #[test]
+    fn test_sandbox_name() {
+        let mut store = ProfileStore::default();
+        for name in ["销售北京", "客服北京", "Support-2", "Support_2", "1"] {
+            store.create(AccountProfile::new(name, AppType::WeCom)).unwrap();
+        }
+
+        let names: Vec<String> = store.list().iter().filter_map(AccountProfile::sandbox_name).collect();
+        assert_eq!(names, ["WeComP_101", "WeComP_102", "WeComP_103", "WeComP_104", "WeComP_105"]);
+        assert!(AccountProfile::new("销售北京", AppType::WeCom).sandbox_name().is_none());
+
+        // 改名后沙盒不变
+        let renamed = store.update("销售北京", AccountProfile::new("销售上海", AppType::WeCom)).unwrap();
+        assert_eq!(renamed.sandbox_name().as_deref(), Some("WeComP_101"));
+    }
+}
@@ -0,0 +1,388 @@
+// 实例注册表 - 持久化记录由本工具管理的实例
+// GUI / CLI 重启后可以重新接管仍在运行的实例
+
+use crate::discovery::{self, AppProfile};
+use crate::single_instance::LockFile;
+use crate::{app_dirs, migrations, platform, AppType, IsolationMode, SpawnRequest, SpawnResponse};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// 注册表文件名 (位于数据目录)
+pub const REGISTRY_FILE: &str = "instances.json";
+/// 等待注册表锁的最长时间 (持有者只在读写文件期间持有)
+const REGISTRY_LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 实例记录
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InstanceRecord {
+    /// 唯一标识 (PID 会被系统复用,不能单独作为标识)
+    pub id: String,
+    pub pid: u32,
+    pub app_type: AppType,
+    /// 账号配置名称
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// 启动方式
+    pub backend: IsolationMode,
+    /// Sandboxie 沙盒名称
+    #[serde(default)]
+    pub sandbox_name: Option<String>,
+    #[serde(default)]
+    pub data_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub exe_path: Option<PathBuf>,
+    /// 启动时间 (Unix 秒)
+    pub started_at: u64,
+    /// 被守护进程重启的次数
+    #[serde(default)]
+    pub restarts: u32,
+}
+
+impl InstanceRecord {
+    pub fn new(pid: u32, app_type: AppType, backend: IsolationMode) -> Self {
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        Self {
+            id: format!("{:x}-{}", started_at, pid),
+            pid,
+            app_type,
+            profile: None,
+            backend,
+            sandbox_name: None,
+            data_dir: None,
+            exe_path: None,
+            started_at,
+            restarts: 0,
+        }
+    }
+}
+
+/// 简单模式启动后应记录的实例
+///
+/// 有实例失败时无法确定 PID 与数据目录的对应关系,不记录数据目录。
+pub fn records_for_spawn(req: &SpawnRequest, response: &SpawnResponse) -> Vec<InstanceRecord> {
+    let app_type = req.app_type.clone().unwrap_or_default();
+    let configs = req.instance_configs.as_deref().filter(|_| response.failed == 0).unwrap_or_default();
+
+    response
+        .pids
+        .iter()
+        .enumerate()
+        .map(|(i, &pid)| {
+            let mut record = InstanceRecord::new(pid, app_type.clone(), IsolationMode::Simple);
+            record.data_dir = configs.get(i).and_then(|c| c.data_dir.clone());
+            record.exe_path = req.app_path.clone();
+            record
+        })
+        .collect()
+}
+
+/// 对账结果
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ReconcileReport {
+    /// 仍在运行的实例
+    pub alive: Vec<u32>,
+    /// 已退出 (或 PID 已被其他程序复用) 而被移除的实例
+    pub removed: Vec<InstanceRecord>,
+}
+
+/// 实例注册表
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InstanceRegistry {
+    #[serde(skip)]
+    path: PathBuf,
+    /// 格式版本 (旧文件缺失时迁移不改写文件,按当前版本处理)
+    #[serde(default = "current_version")]
+    pub schema_version: u32,
+    pub instances: Vec<InstanceRecord>,
+}
+
+fn current_version() -> u32 {
+    migrations::REGISTRY.current
+}
+
+impl Default for InstanceRegistry {
+    fn default() -> Self {
+        Self::new(PathBuf::new())
+    }
+}
+
+impl InstanceRegistry {
+    /// 创建空注册表
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            schema_version: migrations::REGISTRY.current,
+            instances: Vec::new(),
+        }
+    }
+
+    /// 默认注册表路径
+    pub fn default_path() -> PathBuf {
+        app_dirs::data_dir().join(REGISTRY_FILE)
+    }
+
+    /// 从默认路径加载
+    pub fn load_default() -> Result<Self, String> {
+        Self::load(&Self::default_path())
+    }
+
+    /// 加载注册表,文件不存在时返回空注册表
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let mut registry = match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str::<Self>(&migrations::upgrade_file(&migrations::REGISTRY, path, text)?)
+                .map_err(|e| format!("解析实例注册表失败 {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => return Err(format!("读取实例注册表失败 {}: {}", path.display(), e)),
+        };
+
+        registry.path = path.to_path_buf();
+        Ok(registry)
+    }
+
+    /// 在文件锁内重新加载、修改并保存注册表
+    ///
+    /// GUI 和 CLI 可能同时修改注册表,先重新加载再修改可以保留对方写入的实例;
+    /// 修改后的注册表同时替换 self。内容没有变化时不写入文件。
+    pub fn update<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> Result<T, String> {
+        let _lock = self.lock()?;
+        let mut current = Self::load(&self.path)?;
+        let before = current.instances.clone();
+        let result = f(&mut current);
+        if current.instances != before {
+            current.save()?;
+        }
+        *self = current;
+        Ok(result)
+    }
+
+    /// 在阻塞线程池中执行 [`update`](Self::update),等待文件锁时不占用异步运行时的工作线程
+    pub async fn update_async<T, F>(&mut self, f: F) -> Result<T, String>
+    where
+        F: FnOnce(&mut Self) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let path = self.path.clone();
+        let (registry, result) = tokio::task::spawn_blocking(move || {
+            let mut registry = Self::new(path);
+            let result = registry.update(f)?;
+            Ok::<_, String>((registry, result))
+        })
+        .await
+        .map_err(|e| format!("更新实例注册表失败: {}", e))??;
+        *self = registry;
+        Ok(result)
+    }
+
+    /// 获取注册表的文件锁
+    fn lock(&self) -> Result<LockFile, String> {
+        let path = self.path.with_extension("lock");
+        let started = Instant::now();
+        loop {
+            if let Some(lock) = LockFile::try_acquire(&path)? {
+                return Ok(lock);
+            }
+            if started.elapsed() >= REGISTRY_LOCK_TIMEOUT {
+                return Err(format!("等待实例注册表锁超时: {}", path.display()));
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    /// 保存到磁盘
+    pub fn save(&self) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| format!("序列化实例注册表失败: {}", e))?;
+        app_dirs::write_atomic(&self.path, json.as_bytes())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 添加实例 (相同 PID 的旧记录会被替换)
+    pub fn add(&mut self, record: InstanceRecord) {
+        self.instances.retain(|r| r.pid != record.pid);
+        self.instances.push(record);
+    }
+
+    pub fn remove(&mut self, pid: u32) -> Option<InstanceRecord> {
+        let index = self.instances.iter().position(|r| r.pid == pid)?;
+        Some(self.instances.remove(index))
+    }
+
+    pub fn get(&self, pid: u32) -> Option<&InstanceRecord> {
+        self.instances.iter().find(|r| r.pid == pid)
+    }
+
+    /// 实例重启后更新 PID (id 保持不变,客户端持有的 id 仍然有效)
+    pub fn update_pid(&mut self, old_pid: u32, new_pid: u32) {
+        if let Some(record) = self.instances.iter_mut().find(|r| r.pid == old_pid) {
+            record.pid = new_pid;
+            record.restarts += 1;
+        }
+    }
+
+    pub fn pids(&self) -> Vec<u32> {
+        self.instances.iter().map(|r| r.pid).collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.instances.clear();
+    }
+
+    /// 与实际运行的进程对账,移除已不存在的实例
+    pub fn reconcile_with<F>(&mut self, mut is_alive: F) -> ReconcileReport
+    where
+        F: FnMut(&InstanceRecord) -> bool,
+    {
+        let mut report = ReconcileReport::default();
+
+        self.instances.retain(|record| {
+            if is_alive(record) {
+                report.alive.push(record.pid);
+                true
+            } else {
+                report.removed.push(record.clone());
+                false
+            }
+        });
+
+        report
+    }
+
+    /// 与当前系统进程对账
+    ///
+    /// 除了检查 PID 是否存在,还会核对进程是否仍是目标应用,避免 PID 复用导致误判。
+    pub fn reconcile(&mut self) -> ReconcileReport {
+        let snapshot = discovery::snapshot();
+
+        self.reconcile_with(|record| {
+            if !platform::process_exists(record.pid) {
+                return false;
+            }
+
+            // Sandboxie 实例的命令行和数据目录与简单模式不同,无法按应用特征核对
+            if record.backend == IsolationMode::Sandboxie {
+                return true;
+            }
+
+            // 快照中查不到 (如权限不足) 时无法核对,按存活处理
+            match snapshot.iter().find(|e| e.pid == record.pid) {
+                Some(entry) => AppProfile::for_type(&record.app_type).classify(entry).is_some(),
+                None => true,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load_roundtrip() {
+        let dir = app_dirs::temp_test_dir("registry-roundtrip");
+        let path = dir.join(REGISTRY_FILE);
+
+        let mut registry = InstanceRegistry::load(&path).unwrap();
+        assert!(registry.instances.is_empty());
+
+        let mut record = InstanceRecord::new(1234, AppType::WeCom, IsolationMode::Sandboxie);
+        record.sandbox_name = Some("WeCom_1".to_string());
+        record.data_dir = Some(PathBuf::from("/data/1"));
+        registry.add(record.clone());
+        registry.save().unwrap();
+
+        let loaded = InstanceRegistry::load(&path).unwrap();
+        assert_eq!(loaded.instances, vec![record]);
+    }
+
+    #[test]
+    fn test_update_keeps_concurrent_writes() {
+        let dir = app_dirs::temp_test_dir("registry-update");
+        let path = dir.join(REGISTRY_FILE);
+
+        // 两个进程各自加载了注册表
+        let mut gui = InstanceRegistry::load(&path).unwrap();
+        let mut cli = InstanceRegistry::load(&path).unwrap();
+
+        gui.update(|r| r.add(InstanceRecord::new(1, AppType::WeCom, IsolationMode::Simple))).unwrap();
+        cli.update(|r| r.add(InstanceRecord::new(2, AppType::WeChat, IsolationMode::Simple))).unwrap();
+        assert_eq!(cli.pids(), vec![1, 2]);
+
+        // 只移除自己管理的实例,不影响对方的
+        let removed = gui.update(|r| r.remove(1)).unwrap();
+        assert_eq!(removed.map(|r| r.pid), Some(1));
+        assert_eq!(gui.pids(), vec![2]);
+        assert_eq!(InstanceRegistry::load(&path).unwrap().pids(), vec![2]);
+
+        // 持有锁时其他进程等待后超时
+        let _lock = gui.lock().unwrap();
+        assert!(cli.update(|r| r.clear()).unwrap_err().contains("超时"));
+        assert_eq!(InstanceRegistry::load(&path).unwrap().pids(), vec![2]);
+    }
+
+    #[tokio::test]
+    async fn test_update_async() {
+        let dir = app_dirs::temp_test_dir("registry-update-async");
+        let path = dir.join(REGISTRY_FILE);
+        let mut registry = InstanceRegistry::load(&path).unwrap();
+
+        let count = registry
+            .update_async(|r| {
+                r.add(InstanceRecord::new(7, AppType::WeCom, IsolationMode::Simple));
+                r.instances.len()
+            })
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(registry.pids(), vec![7]);
+        assert_eq!(registry.path(), path);
+        assert_eq!(InstanceRegistry::load(&path).unwrap().pids(), vec![7]);
+    }
+
+    #[test]
+    fn test_reconcile_removes_dead() {
+        let mut registry = InstanceRegistry::default();
+        registry.add(InstanceRecord::new(1, AppType::WeCom, IsolationMode::Simple));
+        registry.add(InstanceRecord::new(2, AppType::WeChat, IsolationMode::Simple));
+
+        let report = registry.reconcile_with(|r| r.pid == 2);
+        assert_eq!(report.alive, vec![2]);
+        assert_eq!(report.removed.len(), 1);
+        assert_eq!(registry.pids(), vec![2]);
+    }
+
+    #[test]
+    fn test_update_pid_keeps_identity_fields() {
+        let mut registry = InstanceRegistry::default();
+        let mut record = InstanceRecord::new(1, AppType::WeCom, IsolationMode::Simple);
+        record.profile = Some("Sales-Beijing".to_string());
+        let id = record.id.clone();
+        registry.add(record);
+
+        registry.update_pid(1, 9);
+        let updated = registry.get(9).unwrap();
+        assert_eq!(updated.id, id);
+        assert_eq!(updated.profile.as_deref(), Some("Sales-Beijing"));
+        assert_eq!(updated.restarts, 1);
+        assert!(registry.get(1).is_none());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_reconcile_rejects_reused_pid() {
+        // 当前测试进程存在但不是企业微信,应视为 PID 已被复用
+        let mut registry = InstanceRegistry::default();
+        registry.add(InstanceRecord::new(std::process::id(), AppType::WeCom, IsolationMode::Simple));
+
+        let report = registry.reconcile();
+        assert!(report.alive.is_empty());
+        assert_eq!(report.removed.len(), 1);
+    }
+}
@@ -0,0 +1,626 @@
+// Sandboxie.ini 读写 - 无损解析并修改 Sandboxie 配置文件
+//
+// Sandboxie.ini 通常为带 BOM 的 UTF-16LE、CRLF 换行。解析后保留注释、空行、
+// 顺序和原始写法,未修改的行按原样写回;节名和键名不区分大小写,
+// 同一个键可以出现多次 (如 OpenFilePath)。
+//
+// 修改以 IniChange (与 SbieIni 的 set / append / delete 语义相同) 表示,可以直接应用到文件,
+// 也可以由两份配置比较得出,修改完成后只需重载一次。跨平台编译,便于在 Linux 上测试。
+
+use crate::app_dirs;
+use crate::windows_sandbox::IniChange;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// 配置文件名
+pub const INI_FILE: &str = "Sandboxie.ini";
+/// 全局设置节
+pub const GLOBAL_SECTION: &str = "GlobalSettings";
+
+/// 文件编码
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    /// 带 BOM 的 UTF-16LE (Sandboxie 默认)
+    #[default]
+    Utf16Le,
+    /// 带 BOM 的 UTF-16BE
+    Utf16Be,
+    /// 带 BOM 的 UTF-8
+    Utf8Bom,
+    Utf8,
+}
+
+impl Encoding {
+    /// 识别编码并解码
+    pub fn decode(bytes: &[u8]) -> Result<(Self, String), String> {
+        let utf16 = |bytes: &[u8], from: fn([u8; 2]) -> u16| {
+            if !bytes.len().is_multiple_of(2) {
+                return Err("UTF-16 文件长度不是偶数".to_string());
+            }
+            let units: Vec<u16> = bytes.chunks_exact(2).map(|c| from([c[0], c[1]])).collect();
+            String::from_utf16(&units).map_err(|e| format!("UTF-16 解码失败: {}", e))
+        };
+
+        match bytes {
+            [0xFF, 0xFE, rest @ ..] => Ok((Encoding::Utf16Le, utf16(rest, u16::from_le_bytes)?)),
+            [0xFE, 0xFF, rest @ ..] => Ok((Encoding::Utf16Be, utf16(rest, u16::from_be_bytes)?)),
+            [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8(rest.to_vec())
+                .map(|text| (Encoding::Utf8Bom, text))
+                .map_err(|e| format!("UTF-8 解码失败: {}", e)),
+            _ => String::from_utf8(bytes.to_vec())
+                .map(|text| (Encoding::Utf8, text))
+                .map_err(|_| "无法识别的编码 (应为 UTF-16 或 UTF-8)".to_string()),
+        }
+    }
+
+    pub fn encode(self, text: &str) -> Vec<u8> {
+        match self {
+            Encoding::Utf16Le => [0xFF, 0xFE]
+                .into_iter()
+                .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+                .collect(),
+            Encoding::Utf16Be => [0xFE, 0xFF]
+                .into_iter()
+                .chain(text.encode_utf16().flat_map(u16::to_be_bytes))
+                .collect(),
+            Encoding::Utf8Bom => [0xEF, 0xBB, 0xBF].into_iter().chain(text.bytes()).collect(),
+            Encoding::Utf8 => text.as_bytes().to_vec(),
+        }
+    }
+}
+
+/// 一行
+#[derive(Debug, Clone, PartialEq)]
+enum Line {
+    /// 键值对,raw 为原始写法 (修改后为空)
+    Setting { key: String, value: String, raw: Option<String> },
+    /// 注释、空行或无法识别的行
+    Other(String),
+}
+
+impl Line {
+    fn parse(text: &str) -> Self {
+        let trimmed = text.trim_start();
+        if trimmed.starts_with('#') || trimmed.starts_with(';') {
+            return Line::Other(text.to_string());
+        }
+        match text.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => Line::Setting {
+                key: key.trim().to_string(),
+                value: value.trim().to_string(),
+                raw: Some(text.to_string()),
+            },
+            _ => Line::Other(text.to_string()),
+        }
+    }
+
+    fn setting(key: &str, value: &str) -> Self {
+        Line::Setting {
+            key: key.to_string(),
+            value: value.to_string(),
+            raw: None,
+        }
+    }
+
+    fn is_key(&self, name: &str) -> bool {
+        matches!(self, Line::Setting { key, .. } if key.eq_ignore_ascii_case(name))
+    }
+
+    fn is_blank(&self) -> bool {
+        matches!(self, Line::Other(text) if text.trim().is_empty())
+    }
+
+    fn render(&self) -> String {
+        match self {
+            Line::Setting { raw: Some(raw), .. } => raw.clone(),
+            Line::Setting { key, value, raw: None } => format!("{}={}", key, value),
+            Line::Other(text) => text.clone(),
+        }
+    }
+}
+
+/// 一个节 (沙盒、全局设置或用户设置)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    name: String,
+    /// 原始的节标题行
+    header: String,
+    lines: Vec<Line>,
+}
+
+impl Section {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            header: format!("[{}]", name),
+            lines: vec![Line::Other(String::new())],
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 所有键值对 (按文件中的顺序)
+    pub fn settings(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.lines.iter().filter_map(|line| match line {
+            Line::Setting { key, value, .. } => Some((key.as_str(), value.as_str())),
+            Line::Other(_) => None,
+        })
+    }
+
+    /// 键的第一个值
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.get_all(key).into_iter().next()
+    }
+
+    /// 键的所有值 (多值设置)
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        self.settings()
+            .filter(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v)
+            .collect()
+    }
+
+    /// y / n 设置
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        match self.get(key)?.to_ascii_lowercase().as_str() {
+            "y" | "yes" | "true" | "1" => Some(true),
+            "n" | "no" | "false" | "0" => Some(false),
+            _ => None,
+        }
+    }
+
+    /// 数值设置
+    pub fn get_number<T: std::str::FromStr>(&self, key: &str) -> Option<T> {
+        self.get(key)?.parse().ok()
+    }
+
+    /// 设置单值: 替换第一个值并移除其余值,不存在时追加;值为空时移除该键
+    pub fn set(&mut self, key: &str, value: &str) {
+        if value.is_empty() {
+            self.remove(key);
+            return;
+        }
+
+        let mut replaced = false;
+        self.lines.retain_mut(|line| {
+            if !line.is_key(key) {
+                return true;
+            }
+            if replaced {
+                return false;
+            }
+            replaced = true;
+            if let Line::Setting { value: old, raw, .. } = line {
+                if old != value {
+                    *old = value.to_string();
+                    *raw = None;
+                }
+            }
+            true
+        });
+        if !replaced {
+            self.insert_at_end(Line::setting(key, value));
+        }
+    }
+
+    pub fn set_bool(&mut self, key: &str, value: bool) {
+        self.set(key, if value { "y" } else { "n" });
+    }
+
+    pub fn set_number<T: fmt::Display>(&mut self, key: &str, value: T) {
+        self.set(key, &value.to_string());
+    }
+
+    /// 追加一个值 (多值设置),已存在相同的值时不重复添加
+    pub fn append(&mut self, key: &str, value: &str) {
+        if self.get_all(key).contains(&value) {
+            return;
+        }
+        // 紧跟在同名键之后,保持分组
+        match self.lines.iter().rposition(|line| line.is_key(key)) {
+            Some(index) => self.lines.insert(index + 1, Line::setting(key, value)),
+            None => self.insert_at_end(Line::setting(key, value)),
+        }
+    }
+
+    /// 移除键的所有值,返回是否存在
+    pub fn remove(&mut self, key: &str) -> bool {
+        let before = self.lines.len();
+        self.lines.retain(|line| !line.is_key(key));
+        self.lines.len() != before
+    }
+
+    /// 移除多值设置中的一个值
+    pub fn remove_value(&mut self, key: &str, value: &str) -> bool {
+        let before = self.lines.len();
+        self.lines
+            .retain(|line| !(line.is_key(key) && matches!(line, Line::Setting { value: v, .. } if v == value)));
+        self.lines.len() != before
+    }
+
+    /// 插入到最后一个非空行之后 (节末尾的空行保留在最后)
+    fn insert_at_end(&mut self, line: Line) {
+        let index = self.lines.iter().rposition(|l| !l.is_blank()).map(|i| i + 1).unwrap_or(self.lines.len());
+        self.lines.insert(index, line);
+    }
+}
+
+/// Sandboxie.ini 文档
+#[derive(Debug, Clone, PartialEq)]
+pub struct SandboxieIni {
+    encoding: Encoding,
+    /// 换行符 ("\r\n" 或 "\n")
+    newline: &'static str,
+    /// 第一个节之前的行
+    preamble: Vec<Line>,
+    sections: Vec<Section>,
+    /// 文件是否以换行结尾
+    trailing_newline: bool,
+}
+
+impl Default for SandboxieIni {
+    fn default() -> Self {
+        Self {
+            encoding: Encoding::default(),
+            newline: "\r\n",
+            preamble: Vec::new(),
+            sections: Vec::new(),
+            trailing_newline: true,
+        }
+    }
+}
+
+impl SandboxieIni {
+    /// 解析文本
+    pub fn parse(text: &str) -> Self {
+        let newline = if text.contains("\r\n") || text.is_empty() { "\r\n" } else { "\n" };
+        let trailing_newline = text.is_empty() || text.ends_with('\n');
+        let body = text.strip_suffix('\n').unwrap_or(text);
+
+        let mut ini = Self {
+            newline,
+            trailing_newline,
+            ..Self::default()
+        };
+        if text.is_empty() {
+            return ini;
+        }
+
+        for raw in body.split('\n') {
+            let raw = raw.strip_suffix('\r').unwrap_or(raw);
+            let trimmed = raw.trim();
+            if trimmed.starts_with('[') && trimmed.ends_with(']') && trimmed.len() >= 2 {
+                ini.sections.push(Section {
+                    name: trimmed[1..trimmed.len() - 1].trim().to_string(),
+                    header: raw.to_string(),
+                    lines: Vec::new(),
+                });
+                continue;
+            }
+            let line = Line::parse(raw);
+            match ini.sections.last_mut() {
+                Some(section) => section.lines.push(line),
+                None => ini.preamble.push(line),
+            }
+        }
+        ini
+    }
+
+    /// 解析文件内容 (自动识别编码)
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let (encoding, text) = Encoding::decode(bytes)?;
+        let mut ini = Self::parse(&text);
+        ini.encoding = encoding;
+        Ok(ini)
+    }
+
+    /// 按原编码编码
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.encoding.encode(&self.to_string())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("读取 {} 失败: {}", path.display(), e))?;
+        Self::from_bytes(&bytes).map_err(|e| format!("解析 {} 失败: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        app_dirs::write_atomic(path, &self.to_bytes())
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|s| s.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn section_mut(&mut self, name: &str) -> Option<&mut Section> {
+        self.sections.iter_mut().find(|s| s.name.eq_ignore_ascii_case(name))
+    }
+
+    /// 获取节,不存在时在末尾创建
+    pub fn section_or_insert(&mut self, name: &str) -> &mut Section {
+        let index = match self.sections.iter().position(|s| s.name.eq_ignore_ascii_case(name)) {
+            Some(index) => index,
+            None => {
+                // 与上一节之间空一行
+                let previous = match self.sections.last_mut() {
+                    Some(section) => &mut section.lines,
+                    None => &mut self.preamble,
+                };
+                if previous.last().is_some_and(|line| !line.is_blank()) {
+                    previous.push(Line::Other(String::new()));
+                }
+                self.sections.push(Section::new(name));
+                self.sections.len() - 1
+            }
+        };
+        &mut self.sections[index]
+    }
+
+    /// 删除节,返回是否存在
+    pub fn remove_section(&mut self, name: &str) -> bool {
+        let before = self.sections.len();
+        self.sections.retain(|s| !s.name.eq_ignore_ascii_case(name));
+        self.sections.len() != before
+    }
+
+    /// 沙盒名称 (不含全局设置、用户设置和模板)
+    pub fn box_names(&self) -> Vec<&str> {
+        self.sections
+            .iter()
+            .map(|s| s.name.as_str())
+            .filter(|name| is_box_section(name))
+            .collect()
+    }
+
+    /// 应用一条修改 (set / append / delete,set 空值表示删除整个设置)
+    pub fn apply(&mut self, change: &IniChange) -> Result<(), String> {
+        match change.op.as_str() {
+            "set" if change.value.is_empty() => {
+                if let Some(section) = self.section_mut(&change.section) {
+                    section.remove(&change.key);
+                }
+            }
+            "set" => self.section_or_insert(&change.section).set(&change.key, &change.value),
+            "append" => self.section_or_insert(&change.section).append(&change.key, &change.value),
+            "delete" => {
+                if let Some(section) = self.section_mut(&change.section) {
+                    section.remove_value(&change.key, &change.value);
+                }
+            }
+            op => return Err(format!("不支持的修改操作: {}", op)),
+        }
+        Ok(())
+    }
+
+    /// 从当前配置变为 target 所需的修改 (按节和键的出现顺序)
+    pub fn diff(&self, target: &SandboxieIni) -> Vec<IniChange> {
+        let mut names: Vec<&str> = self.sections.iter().map(|s| s.name.as_str()).collect();
+        for section in &target.sections {
+            if !names.iter().any(|n| n.eq_ignore_ascii_case(&section.name)) {
+                names.push(&section.name);
+            }
+        }
+
+        let mut changes = Vec::new();
+        for name in names {
+            let (before, after) = (self.section(name), target.section(name));
+            let mut keys: Vec<&str> = Vec::new();
+            for (key, _) in before.into_iter().chain(after).flat_map(Section::settings) {
+                if !keys.iter().any(|k| k.eq_ignore_ascii_case(key)) {
+                    keys.push(key);
+                }
+            }
+
+            for key in keys {
+                let old = before.map(|s| s.get_all(key)).unwrap_or_default();
+                let new = after.map(|s| s.get_all(key)).unwrap_or_default();
+                if old == new {
+                    continue;
+                }
+                let change = |op: &str, value: &str| IniChange {
+                    op: op.to_string(),
+                    section: name.to_string(),
+                    key: key.to_string(),
+                    value: value.to_string(),
+                };
+                // 只新增了值时追加,否则整体重写
+                let (first, rest) = match new.split_first() {
+                    None => {
+                        changes.push(change("set", ""));
+                        continue;
+                    }
+                    Some(_) if new.starts_with(&old) && !old.is_empty() => (None, &new[old.len()..]),
+                    Some((first, rest)) => (Some(*first), rest),
+                };
+                if let Some(first) = first {
+                    changes.push(change("set", first));
+                }
+                changes.extend(rest.iter().map(|value| change("append", value)));
+            }
+        }
+        changes
+    }
+}
+
+impl fmt::Display for SandboxieIni {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut lines = self.preamble.iter().map(Line::render).collect::<Vec<_>>();
+        for section in &self.sections {
+            lines.push(section.header.clone());
+            lines.extend(section.lines.iter().map(Line::render));
+        }
+        f.write_str(&lines.join(self.newline))?;
+        if self.trailing_newline && !lines.is_empty() {
+            f.write_str(self.newline)?;
+        }
+        Ok(())
+    }
+}
+
+/// 是否为沙盒节
+pub fn is_box_section(name: &str) -> bool {
+    let lower = name.to_ascii_lowercase();
+    !(lower == GLOBAL_SECTION.to_ascii_lowercase()
+        || lower.starts_with("usersettings_")
+        || lower.starts_with("template_")
+        || lower == "templatesettings")
+}
+
+/// 查找 Sandboxie 实际使用的配置文件: 先找 Windows 目录,再找安装目录
+pub fn locate(windows_dir: &Path, install_dir: &Path) -> Option<PathBuf> {
+    [windows_dir.join(INI_FILE), install_dir.join(INI_FILE)]
+        .into_iter()
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &[u8] = include_bytes!("../tests/fixtures/Sandboxie.ini");
+
+    fn sample() -> SandboxieIni {
+        SandboxieIni::from_bytes(SAMPLE).unwrap()
+    }
+
+    fn change(op: &str, section: &str, key: &str, value: &str) -> IniChange {
+        IniChange {
+            op: op.to_string(),
+            section: section.to_string(),
+            key: key.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_round_trip_is_lossless() {
+        let ini = sample();
+        assert_eq!(ini.encoding(), Encoding::Utf16Le);
+        assert_eq!(ini.to_bytes(), SAMPLE);
+
+        for text in ["", "a=1", "; 注释\n[Box]\nKey = Value \n\n", "[Box]\r\nKey=1\r\n[Other]"] {
+            assert_eq!(SandboxieIni::parse(text).to_string(), text);
+            for encoding in [Encoding::Utf16Be, Encoding::Utf8Bom, Encoding::Utf8] {
+                let (decoded, back) = Encoding::decode(&encoding.encode(text)).unwrap();
+                assert_eq!((decoded, back.as_str()), (encoding, text));
+            }
+        }
+        assert!(Encoding::decode(&[0xFF, 0xFE, 0x41]).is_err());
+    }
+
+    #[test]
+    fn test_typed_settings() {
+        let ini = sample();
+        assert_eq!(ini.box_names(), ["DefaultBox", "WeCom_1"]);
+
+        let default_box = ini.section("defaultbox").unwrap();
+        assert_eq!(default_box.get_bool("Enabled"), Some(true));
+        assert_eq!(default_box.get_number::<u8>("ConfigLevel"), Some(9));
+        assert_eq!(default_box.get_all("RecoverFolder").len(), 3);
+        assert_eq!(default_box.get("borderColor"), Some("#00FFFF,ttl,6"));
+
+        let user = ini.section("UserSettings_054A02CE").unwrap();
+        assert_eq!(user.get("SbieCtrl_UserName"), Some("张三"));
+        assert_eq!(user.get("由 Sandboxie-Plus 管理"), None);
+    }
+
+    #[test]
+    fn test_edit_keeps_comments_and_order() {
+        let mut ini = sample();
+        let wecom = ini.section_mut("WeCom_1").unwrap();
+        wecom.set_number("ConfigLevel", 8);
+        wecom.set_bool("AutoDelete", true);
+        wecom.append("OpenFilePath", r"D:\Shared");
+        wecom.append("OpenFilePath", r"D:\Shared");
+        ini.section_or_insert("WeCom_2").set("Enabled", "y");
+        assert!(ini.remove_section("DefaultBox"));
+
+        let text = ini.to_string();
+        let wecom_1 = &text[text.find("[WeCom_1]").unwrap()..text.find("[WeCom_2]").unwrap()];
+        assert_eq!(
+            wecom_1,
+            "[WeCom_1]\r\n\r\nEnabled=y\r\nConfigLevel=8\r\n# 企业微信账号 1\r\nBoxNameTitle=企业微信账号 1\r\n\
+             OpenFilePath=C:\\Shared\r\nOpenFilePath=D:\\Shared\r\nAutoDelete=y\r\n\r\n"
+        );
+        assert!(text.ends_with("[WeCom_2]\r\n\r\nEnabled=y\r\n"));
+        assert!(text.contains("; 由 Sandboxie-Plus 管理\r\n"));
+        assert!(!text.contains("[DefaultBox]"));
+
+        // 修改后重新解析得到相同的内容
+        assert_eq!(SandboxieIni::from_bytes(&ini.to_bytes()).unwrap().to_string(), text);
+    }
+
+    #[test]
+    fn test_apply_and_diff() {
+        let before = sample();
+        let mut after = before.clone();
+        let changes = [
+            change("set", "WeCom_1", "ConfigLevel", "8"),
+            change("append", "WeCom_1", "OpenFilePath", r"D:\Shared"),
+            change("set", "WeCom_1", "BoxNameTitle", ""),
+            change("set", "WeCom_2", "Enabled", "y"),
+            change("append", "WeCom_2", "OpenKeyPath", r"HKEY_CURRENT_USER\Software\Tencent"),
+        ];
+        for c in &changes {
+            after.apply(c).unwrap();
+        }
+        assert!(after.apply(&change("insert", "WeCom_1", "A", "b")).is_err());
+
+        // 按键在文件中的顺序列出;新键用 set
+        let diff = before.diff(&after);
+        assert_eq!(
+            diff,
+            [
+                change("set", "WeCom_1", "ConfigLevel", "8"),
+                change("set", "WeCom_1", "BoxNameTitle", ""),
+                change("append", "WeCom_1", "OpenFilePath", r"D:\Shared"),
+                change("set", "WeCom_2", "Enabled", "y"),
+                change("set", "WeCom_2", "OpenKeyPath", r"HKEY_CURRENT_USER\Software\Tencent"),
+            ]
+        );
+
+        // 按差异修改后与目标一致,再次比较没有差异
+        let mut replay = before.clone();
+        for c in &diff {
+            replay.apply(c).unwrap();
+        }
+        assert!(replay.diff(&after).is_empty());
+        assert_eq!(replay.to_string(), after.to_string());
+
+        // 多值设置改变顺序时整体重写
+        let mut reordered = before.clone();
+        let section = reordered.section_mut("DefaultBox").unwrap();
+        section.remove_value("RecoverFolder", "%Personal%");
+        section.append("RecoverFolder", "%Personal%");
+        let diff = before.diff(&reordered);
+        assert_eq!(diff.iter().map(|c| c.op.as_str()).collect::<Vec<_>>(), ["set", "append", "append"]);
+    }
+
+    #[test]
+    fn test_locate() {
+        let dir = app_dirs::temp_test_dir("sandboxie-ini-locate");
+        let (windows, install) = (dir.join("Windows"), dir.join("Sandboxie-Plus"));
+        std::fs::create_dir_all(&windows).unwrap();
+        std::fs::create_dir_all(&install).unwrap();
+        assert_eq!(locate(&windows, &install), None);
+
+        std::fs::write(install.join(INI_FILE), SAMPLE).unwrap();
+        assert_eq!(locate(&windows, &install), Some(install.join(INI_FILE)));
+        std::fs::write(windows.join(INI_FILE), SAMPLE).unwrap();
+        assert_eq!(locate(&windows, &install), Some(windows.join(INI_FILE)));
+
+        let path = dir.join("saved.ini");
+        sample().save(&path).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), SAMPLE);
+    }
+}
@@ -0,0 +1,415 @@
+// 目录扫描 - 在指定目录下按通配符查找应用程序
+//
+// 多个线程并行遍历目录,限制遍历深度和总耗时,避免在大目录或网络盘上卡住
+// (到达截止时间后不等待仍卡在 read_dir 中的线程)。
+// 匹配不区分大小写,无需再列举 WXWork.exe / wxwork.exe / WXWORK.EXE 等写法。
+
+use crate::discovery::AppProfile;
+use crate::AppType;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// 扫描参数
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScanOptions {
+    /// 最大遍历深度 (搜索目录下的直接子项为 1)
+    pub max_depth: usize,
+    /// 超时时间
+    pub timeout_ms: u64,
+    /// 并行线程数
+    pub workers: usize,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: 4,
+            timeout_ms: 5000,
+            workers: 4,
+        }
+    }
+}
+
+/// 通配符 (相对于搜索目录的路径,以 / 分隔)
+///
+/// - `*` 匹配任意个字符 (不跨目录),`?` 匹配单个字符
+/// - `**` 匹配任意层目录
+/// - 不含 `/` 的模式只匹配文件名,可位于任意层
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    segments: Vec<Vec<char>>,
+}
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Self {
+        let normalized = pattern.replace('\\', "/").to_lowercase();
+        let normalized = if normalized.contains('/') {
+            normalized
+        } else {
+            format!("**/{}", normalized)
+        };
+
+        Self {
+            segments: normalized
+                .split('/')
+                .filter(|s| !s.is_empty())
+                .map(|s| s.chars().collect())
+                .collect(),
+        }
+    }
+
+    /// 是否匹配相对路径
+    pub fn matches(&self, relative: &Path) -> bool {
+        let parts: Vec<Vec<char>> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_lowercase().chars().collect())
+            .collect();
+        match_segments(&self.segments, &parts)
+    }
+}
+
+fn match_segments(pattern: &[Vec<char>], path: &[Vec<char>]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first.as_slice() == ['*', '*'] => {
+            (0..=path.len()).any(|skip| match_segments(rest, &path[skip..]))
+        }
+        Some((first, rest)) => match path.split_first() {
+            Some((name, path_rest)) => match_wildcard(first, name) && match_segments(rest, path_rest),
+            None => false,
+        },
+    }
+}
+
+fn match_wildcard(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|skip| match_wildcard(rest, &text[skip..])),
+        Some(('?', rest)) => !text.is_empty() && match_wildcard(rest, &text[1..]),
+        Some((c, rest)) => text.first() == Some(c) && match_wildcard(rest, &text[1..]),
+    }
+}
+
+/// 应用的默认文件名模式
+pub fn default_patterns(app_type: &AppType) -> Vec<String> {
+    AppProfile::for_type(app_type)
+        .exe_names
+        .iter()
+        .map(|name| {
+            // Linux 上为 Wine 前缀中的 Windows 程序
+            if cfg!(target_os = "macos") {
+                format!("{}.app", name)
+            } else {
+                format!("{}.exe", name)
+            }
+        })
+        .collect()
+}
+
+/// 扫描结果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScanResult {
+    /// 匹配的路径 (按搜索目录顺序、深度、路径排序)
+    pub matches: Vec<PathBuf>,
+    /// 是否因超时提前结束
+    pub timed_out: bool,
+    /// 遍历过的目录数
+    pub dirs_visited: usize,
+}
+
+struct Job {
+    root: usize,
+    dir: PathBuf,
+    depth: usize,
+}
+
+/// 遍历单个目录的函数 (测试中可替换)
+type Visit = fn(&Path, &Path, &[Pattern]) -> (Vec<PathBuf>, Vec<PathBuf>);
+
+/// 扫描线程共享的状态
+struct ScanState {
+    roots: Vec<PathBuf>,
+    patterns: Vec<Pattern>,
+    max_depth: usize,
+    deadline: Instant,
+    queue: Mutex<Vec<Job>>,
+    active: AtomicUsize,
+    visited: AtomicUsize,
+    timed_out: AtomicBool,
+    found: Mutex<Vec<(usize, usize, PathBuf)>>,
+}
+
+/// 并行扫描搜索目录
+pub fn scan(roots: &[PathBuf], patterns: &[Pattern], options: &ScanOptions) -> ScanResult {
+    scan_with(roots, patterns, options, visit)
+}
+
+fn scan_with(roots: &[PathBuf], patterns: &[Pattern], options: &ScanOptions, visit: Visit) -> ScanResult {
+    let deadline = Instant::now() + Duration::from_millis(options.timeout_ms);
+    let state = Arc::new(ScanState {
+        roots: roots.to_vec(),
+        patterns: patterns.to_vec(),
+        max_depth: options.max_depth,
+        deadline,
+        queue: Mutex::new(
+            roots
+                .iter()
+                .enumerate()
+                .rev()
+                .filter(|(_, dir)| dir.is_dir())
+                .map(|(root, dir)| Job {
+                    root,
+                    dir: dir.clone(),
+                    depth: 0,
+                })
+                .collect(),
+        ),
+        active: AtomicUsize::new(0),
+        visited: AtomicUsize::new(0),
+        timed_out: AtomicBool::new(false),
+        found: Mutex::new(Vec::new()),
+    });
+
+    // 线程不与调用方汇合: read_dir 在网络盘等位置可能一直不返回,
+    // 到达截止时间后直接返回已找到的结果,卡住的线程完成当前目录后自行退出
+    let workers = options.workers.max(1);
+    let (done_tx, done_rx) = mpsc::channel();
+    for _ in 0..workers {
+        let state = Arc::clone(&state);
+        let done = done_tx.clone();
+        std::thread::spawn(move || {
+            work(&state, visit);
+            let _ = done.send(());
+        });
+    }
+    drop(done_tx);
+
+    for _ in 0..workers {
+        match done_rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(()) => {}
+            Err(RecvTimeoutError::Timeout) => {
+                state.timed_out.store(true, Ordering::SeqCst);
+                break;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    let mut found = state.found.lock().unwrap().clone();
+    found.sort();
+    ScanResult {
+        matches: found.into_iter().map(|(_, _, path)| path).collect(),
+        timed_out: state.timed_out.load(Ordering::SeqCst),
+        dirs_visited: state.visited.load(Ordering::SeqCst),
+    }
+}
+
+/// 扫描线程: 不断从队列取目录遍历,直到队列为空且没有线程在遍历,或到达截止时间
+fn work(state: &ScanState, visit: Visit) {
+    loop {
+        if Instant::now() >= state.deadline {
+            state.timed_out.store(true, Ordering::SeqCst);
+            break;
+        }
+
+        // 取任务和增加计数在同一把锁内完成,其他线程才能正确判断是否全部结束
+        let job = {
+            let mut queue = state.queue.lock().unwrap();
+            let job = queue.pop();
+            if job.is_some() {
+                state.active.fetch_add(1, Ordering::SeqCst);
+            }
+            job
+        };
+
+        let job = match job {
+            Some(job) => job,
+            None if state.active.load(Ordering::SeqCst) == 0 => break,
+            None => {
+                std::thread::sleep(Duration::from_millis(1));
+                continue;
+            }
+        };
+
+        state.visited.fetch_add(1, Ordering::SeqCst);
+        let (matches, subdirs) = visit(&state.roots[job.root], &job.dir, &state.patterns);
+
+        if !matches.is_empty() {
+            let mut found = state.found.lock().unwrap();
+            found.extend(matches.into_iter().map(|p| (job.root, job.depth + 1, p)));
+        }
+        if job.depth + 1 < state.max_depth {
+            let mut queue = state.queue.lock().unwrap();
+            queue.extend(subdirs.into_iter().map(|dir| Job {
+                root: job.root,
+                dir,
+                depth: job.depth + 1,
+            }));
+        }
+
+        state.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// 遍历单个目录,返回匹配项和需要继续遍历的子目录
+fn visit(root: &Path, dir: &Path, patterns: &[Pattern]) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let mut matches = Vec::new();
+    let mut subdirs = Vec::new();
+
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        // 无权限等情况直接跳过
+        Err(_) => return (matches, subdirs),
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let relative = path.strip_prefix(root).unwrap_or(&path);
+
+        if patterns.iter().any(|p| p.matches(relative)) {
+            // 匹配到的 .app 包不再向下遍历
+            matches.push(path);
+            continue;
+        }
+
+        // 不跟随符号链接,避免循环
+        if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            subdirs.push(path);
+        }
+    }
+
+    (matches, subdirs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_dirs;
+
+    fn touch(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "").unwrap();
+    }
+
+    fn patterns(list: &[&str]) -> Vec<Pattern> {
+        list.iter().map(|p| Pattern::new(p)).collect()
+    }
+
+    #[test]
+    fn test_pattern_is_case_insensitive() {
+        let pattern = Pattern::new("wxwork.exe");
+        assert!(pattern.matches(Path::new("WXWork.exe")));
+        assert!(pattern.matches(Path::new("Tencent/WXWork/WXWORK.EXE")));
+        assert!(!pattern.matches(Path::new("WXWorkUpdate.exe")));
+    }
+
+    #[test]
+    fn test_pattern_wildcards() {
+        assert!(Pattern::new("Tencent/*/WeChat.exe").matches(Path::new("tencent/WeChat/wechat.exe")));
+        assert!(!Pattern::new("Tencent/*/WeChat.exe").matches(Path::new("Tencent/a/b/WeChat.exe")));
+        assert!(Pattern::new("Tencent/**/WeChat.exe").matches(Path::new("Tencent/a/b/WeChat.exe")));
+        assert!(Pattern::new("Tencent/**/WeChat.exe").matches(Path::new("Tencent/WeChat.exe")));
+        assert!(Pattern::new("WX?ork*.exe").matches(Path::new("x/wxwork_v4.exe")));
+        assert!(Pattern::new(r"Tencent\*.exe").matches(Path::new("Tencent/WeChat.exe")));
+    }
+
+    #[test]
+    fn test_scan_finds_matches_in_order() {
+        let dir = app_dirs::temp_test_dir("scanner-order");
+        let first = dir.join("first");
+        let second = dir.join("second");
+        touch(&second.join("WXWork.exe"));
+        touch(&first.join("Tencent/WXWork/wxwork.EXE"));
+        touch(&first.join("Tencent/WXWork/readme.txt"));
+        touch(&first.join("WXWORK.exe"));
+
+        let result = scan(
+            &[dir.join("missing"), first.clone(), second.clone()],
+            &patterns(&["wxwork.exe"]),
+            &ScanOptions::default(),
+        );
+
+        assert!(!result.timed_out);
+        assert_eq!(
+            result.matches,
+            vec![
+                first.join("WXWORK.exe"),
+                first.join("Tencent/WXWork/wxwork.EXE"),
+                second.join("WXWork.exe"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scan_respects_depth_limit() {
+        let dir = app_dirs::temp_test_dir("scanner-depth");
+        touch(&dir.join("a/b/c/d/WeChat.exe"));
+
+        let shallow = ScanOptions {
+            max_depth: 4,
+            ..Default::default()
+        };
+        assert!(scan(std::slice::from_ref(&dir), &patterns(&["wechat.exe"]), &shallow).matches.is_empty());
+
+        let deep = ScanOptions {
+            max_depth: 5,
+            ..Default::default()
+        };
+        assert_eq!(scan(std::slice::from_ref(&dir), &patterns(&["wechat.exe"]), &deep).matches.len(), 1);
+    }
+
+    #[test]
+    fn test_scan_stops_at_timeout() {
+        let dir = app_dirs::temp_test_dir("scanner-timeout");
+        touch(&dir.join("a/WeChat.exe"));
+
+        let options = ScanOptions {
+            timeout_ms: 0,
+            ..Default::default()
+        };
+        let result = scan(&[dir], &patterns(&["wechat.exe"]), &options);
+        assert!(result.timed_out);
+        assert!(result.matches.is_empty());
+    }
+
+    #[test]
+    fn test_scan_does_not_wait_for_hung_directory() {
+        let dir = app_dirs::temp_test_dir("scanner-hung");
+        let hung: Visit = |_, _, _| {
+            std::thread::sleep(Duration::from_secs(30));
+            (Vec::new(), Vec::new())
+        };
+
+        let options = ScanOptions {
+            timeout_ms: 100,
+            ..Default::default()
+        };
+        let started = Instant::now();
+        let result = scan_with(&[dir], &patterns(&["wechat.exe"]), &options, hung);
+        assert!(result.timed_out);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_scan_with_many_workers_visits_each_dir_once() {
+        let dir = app_dirs::temp_test_dir("scanner-parallel");
+        for i in 0..20 {
+            touch(&dir.join(format!("d{}/sub/file.txt", i)));
+        }
+        touch(&dir.join("d7/sub/WeChat.exe"));
+
+        let options = ScanOptions {
+            workers: 8,
+            ..Default::default()
+        };
+        let result = scan(std::slice::from_ref(&dir), &patterns(&["wechat.exe"]), &options);
+        assert_eq!(result.matches, vec![dir.join("d7/sub/WeChat.exe")]);
+        assert_eq!(result.dirs_visited, 1 + 20 + 20);
+    }
+}
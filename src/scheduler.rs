@@ -0,0 +1,733 @@
+// 定时启动 / 停止账号配置
+//
+// 规则按本地时间计算,精确到分钟,支持两种写法:
+// - 时间段: 在指定星期的 start 启动、stop 停止 (stop 早于 start 表示跨午夜)
+// - cron: 标准 5 字段表达式 (分 时 日 月 周),到点执行 start 或 stop
+//
+// 调度器只根据"上次检查以来最近的一次动作"决定是否执行,因此休眠 / 唤醒后
+// 只补执行最近的一次 (如错过了 18:00 停止和次日 09:00 启动,唤醒后只启动)。
+// 首次检查时只补启动,不会结束用户在计划外手动打开的实例。
+
+use crate::profiles::AccountProfile;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 检查间隔
+pub const TICK_INTERVAL: Duration = Duration::from_secs(30);
+/// 定时停止时等待实例正常退出的时间,超时后强制结束
+pub const STOP_GRACE: Duration = Duration::from_secs(30);
+
+const MINUTES_PER_DAY: i64 = 24 * 60;
+/// 查找上一次动作时最多回看的天数
+const LOOKBACK_DAYS: i64 = 7;
+/// 查找下一次动作时最多向后查找的天数
+const LOOKAHEAD_DAYS: i64 = 366;
+
+/// 星期
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+impl Weekday {
+    const ALL: [Weekday; 7] = [
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+        Weekday::Sat,
+        Weekday::Sun,
+    ];
+
+    /// 周一为 0
+    fn index(self) -> i64 {
+        self as i64
+    }
+
+    /// 某天 (1970-01-01 起的天数) 是星期几
+    fn of_day(day: i64) -> Self {
+        // 1970-01-01 是星期四
+        Self::ALL[(day + 3).rem_euclid(7) as usize]
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim().to_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|d| format!("{:?}", d).to_lowercase() == text)
+            .ok_or_else(|| format!("未知星期 '{}' (可选: mon, tue, wed, thu, fri, sat, sun)", text))
+    }
+}
+
+/// 一天中的时间 (序列化为 "HH:MM")
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeOfDay {
+    pub hour: u8,
+    pub minute: u8,
+}
+
+impl TimeOfDay {
+    fn minutes(self) -> i64 {
+        self.hour as i64 * 60 + self.minute as i64
+    }
+}
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, String> {
+        let invalid = || format!("时间格式应为 HH:MM: '{}'", text);
+        let (hour, minute) = text.trim().split_once(':').ok_or_else(invalid)?;
+        let hour: u8 = hour.parse().map_err(|_| invalid())?;
+        let minute: u8 = minute.parse().map_err(|_| invalid())?;
+        if hour > 23 || minute > 59 {
+            return Err(invalid());
+        }
+        Ok(Self { hour, minute })
+    }
+}
+
+impl From<TimeOfDay> for String {
+    fn from(time: TimeOfDay) -> Self {
+        format!("{:02}:{:02}", time.hour, time.minute)
+    }
+}
+
+/// 定时动作
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduleAction {
+    Start,
+    Stop,
+}
+
+/// 定时规则
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ScheduleRule {
+    /// 时间段 (days 为空表示每天)
+    Window {
+        #[serde(default)]
+        days: Vec<Weekday>,
+        start: TimeOfDay,
+        stop: TimeOfDay,
+    },
+    /// cron 表达式
+    Cron { expr: String, action: ScheduleAction },
+}
+
+impl ScheduleRule {
+    /// 解析时间段,如 "mon-fri 09:00-18:00"、"sat,sun 10:00-16:00"、"daily 22:00-06:00"
+    pub fn parse_window(text: &str) -> Result<Self, String> {
+        let invalid = || format!("时间段格式应为 <星期> <开始>-<结束> (如 mon-fri 09:00-18:00): '{}'", text);
+        let (days, times) = text.trim().split_once(' ').ok_or_else(invalid)?;
+        let (start, stop) = times.trim().split_once('-').ok_or_else(invalid)?;
+
+        let days = match days.to_lowercase().as_str() {
+            "daily" | "*" => Vec::new(),
+            days => {
+                let mut result = Vec::new();
+                for part in days.split(',') {
+                    match part.split_once('-') {
+                        Some((from, to)) => {
+                            let (from, to) = (Weekday::parse(from)?.index(), Weekday::parse(to)?.index());
+                            if from > to {
+                                return Err(invalid());
+                            }
+                            result.extend((from..=to).map(|i| Weekday::ALL[i as usize]));
+                        }
+                        None => result.push(Weekday::parse(part)?),
+                    }
+                }
+                result
+            }
+        };
+
+        let rule = ScheduleRule::Window {
+            days,
+            start: TimeOfDay::try_from(start.to_string())?,
+            stop: TimeOfDay::try_from(stop.to_string())?,
+        };
+        rule.validate()?;
+        Ok(rule)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            ScheduleRule::Window { start, stop, .. } if start == stop => {
+                Err(format!("时间段的开始和结束不能相同: {}", String::from(*start)))
+            }
+            ScheduleRule::Window { .. } => Ok(()),
+            ScheduleRule::Cron { expr, .. } => CronExpr::parse(expr).map(|_| ()),
+        }
+    }
+
+    /// 某天的动作 (当天的分钟数, 动作)
+    fn events_on(&self, day: i64) -> Vec<(i64, ScheduleAction)> {
+        match self {
+            ScheduleRule::Window { days, start, stop } => {
+                let active = |day: i64| days.is_empty() || days.contains(&Weekday::of_day(day));
+                let mut events = Vec::new();
+                if active(day) {
+                    events.push((start.minutes(), ScheduleAction::Start));
+                }
+                // 跨午夜的时间段在次日停止
+                let stop_day = if stop.minutes() > start.minutes() { day } else { day - 1 };
+                if active(stop_day) {
+                    events.push((stop.minutes(), ScheduleAction::Stop));
+                }
+                events
+            }
+            ScheduleRule::Cron { expr, action } => match CronExpr::parse(expr) {
+                Ok(cron) if cron.matches_day(day) => cron.minutes_of_day().map(|m| (m, *action)).collect(),
+                _ => Vec::new(),
+            },
+        }
+    }
+}
+
+/// 5 字段 cron 表达式
+#[derive(Debug, Clone, PartialEq)]
+struct CronExpr {
+    minutes: u64,
+    hours: u32,
+    days_of_month: u32,
+    months: u16,
+    /// 周日为 0
+    days_of_week: u8,
+    /// 日和周都有限制时满足其一即可 (与标准 cron 一致)
+    dom_restricted: bool,
+    dow_restricted: bool,
+}
+
+impl CronExpr {
+    fn parse(expr: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, dom, month, dow] = fields.as_slice() else {
+            return Err(format!("cron 表达式应为 5 个字段 (分 时 日 月 周): '{}'", expr));
+        };
+        let field = |text: &str, min: u32, max: u32| {
+            parse_cron_field(text, min, max).map_err(|e| format!("cron 表达式 '{}' 有误: {}", expr, e))
+        };
+
+        // 周字段中 7 也表示周日
+        let dow_bits = field(dow, 0, 7)?;
+        Ok(Self {
+            minutes: field(minute, 0, 59)?,
+            hours: field(hour, 0, 23)? as u32,
+            days_of_month: field(dom, 1, 31)? as u32,
+            months: field(month, 1, 12)? as u16,
+            days_of_week: ((dow_bits | (dow_bits >> 7)) & 0x7f) as u8,
+            dom_restricted: *dom != "*",
+            dow_restricted: *dow != "*",
+        })
+    }
+
+    fn matches_day(&self, day: i64) -> bool {
+        let (_, month, dom) = civil_from_days(day);
+        if self.months & (1 << month) == 0 {
+            return false;
+        }
+        let dom_ok = self.days_of_month & (1 << dom) != 0;
+        let dow_ok = self.days_of_week & (1 << ((Weekday::of_day(day).index() + 1) % 7)) != 0;
+        match (self.dom_restricted, self.dow_restricted) {
+            (true, true) => dom_ok || dow_ok,
+            _ => dom_ok && dow_ok,
+        }
+    }
+
+    fn minutes_of_day(&self) -> impl Iterator<Item = i64> + '_ {
+        (0..24)
+            .filter(|h| self.hours & (1 << h) != 0)
+            .flat_map(move |h| (0..60).filter(|m| self.minutes & (1 << m) != 0).map(move |m| h * 60 + m))
+    }
+}
+
+/// 解析 cron 字段 (支持 *、数字、a-b、逗号列表和 /步长),返回位集合
+fn parse_cron_field(text: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut bits = 0u64;
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| format!("无效的步长 '{}'", step))?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err("步长不能为 0".to_string());
+        }
+        let number = |s: &str| {
+            s.parse::<u32>()
+                .ok()
+                .filter(|n| (min..=max).contains(n))
+                .ok_or_else(|| format!("'{}' 超出范围 {}-{}", s, min, max))
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((from, to)) => (number(from)?, number(to)?),
+                // "5/15" 表示从 5 开始每 15
+                None if part.contains('/') => (number(range)?, max),
+                None => (number(range)?, number(range)?),
+            },
+        };
+        if from > to {
+            return Err(format!("无效的范围 '{}'", range));
+        }
+        for n in (from..=to).step_by(step as usize) {
+            bits |= 1 << n;
+        }
+    }
+    Ok(bits)
+}
+
+/// 1970-01-01 起的天数转换为 (年, 月, 日)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// 本地时间 (精确到秒)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalTime {
+    /// Unix 秒
+    pub unix: i64,
+    /// 时区偏移 (秒,东区为正)
+    pub offset: i32,
+}
+
+impl LocalTime {
+    /// 本地时间的分钟数 (自 1970-01-01 00:00 起)
+    pub fn minute(&self) -> i64 {
+        (self.unix + self.offset as i64).div_euclid(60)
+    }
+
+    /// 本地分钟数对应的 Unix 秒 (按当前时区偏移计算)
+    pub fn unix_of(&self, minute: i64) -> i64 {
+        minute * 60 - self.offset as i64
+    }
+}
+
+/// 本地分钟数格式化为 "YYYY-MM-DD HH:MM"
+pub fn format_minute(minute: i64) -> String {
+    let (year, month, day) = civil_from_days(minute.div_euclid(MINUTES_PER_DAY));
+    let of_day = minute.rem_euclid(MINUTES_PER_DAY);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, of_day / 60, of_day % 60)
+}
+
+/// 时钟 (测试中可替换)
+pub trait Clock {
+    fn now(&self) -> LocalTime;
+}
+
+/// 系统时钟
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> LocalTime {
+        let unix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        LocalTime {
+            unix,
+            offset: local_offset(unix),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn local_offset(unix: i64) -> i32 {
+    // 32 位 time_t 的平台上超出范围的时刻 (2038 年以后) 按截断处理
+    let time = unix as libc::time_t;
+    let mut tm = std::mem::MaybeUninit::<libc::tm>::zeroed();
+    // SAFETY: localtime_r 只写入调用方提供的缓冲区,失败时返回空指针
+    let result = unsafe { libc::localtime_r(&time, tm.as_mut_ptr()) };
+    if result.is_null() {
+        return 0;
+    }
+    unsafe { tm.assume_init() }.tm_gmtoff as i32
+}
+
+#[cfg(windows)]
+fn local_offset(unix: i64) -> i32 {
+    use windows::Win32::Foundation::{FILETIME, SYSTEMTIME};
+    use windows::Win32::System::Time::{FileTimeToSystemTime, SystemTimeToFileTime, SystemTimeToTzSpecificLocalTime};
+
+    // FILETIME: 自 1601-01-01 起的 100 纳秒数
+    const EPOCH_DIFF_SECS: i64 = 11_644_473_600;
+    const TICKS_PER_SEC: i64 = 10_000_000;
+
+    let Some(ticks) = unix
+        .checked_add(EPOCH_DIFF_SECS)
+        .and_then(|secs| secs.checked_mul(TICKS_PER_SEC))
+        .filter(|ticks| *ticks >= 0)
+    else {
+        return 0;
+    };
+    let to_filetime = |ticks: i64| FILETIME {
+        dwLowDateTime: ticks as u32,
+        dwHighDateTime: (ticks >> 32) as u32,
+    };
+    let from_filetime = |ft: FILETIME| ((ft.dwHighDateTime as i64) << 32) | ft.dwLowDateTime as i64;
+
+    // 按该时刻所在的标准时间 / 夏令时换算,而不是当前的夏令时状态
+    let utc_ft = to_filetime(ticks);
+    let mut utc = SYSTEMTIME::default();
+    let mut local = SYSTEMTIME::default();
+    let mut local_ft = FILETIME::default();
+    let converted = unsafe {
+        FileTimeToSystemTime(&utc_ft, &mut utc).is_ok()
+            && SystemTimeToTzSpecificLocalTime(None, &utc, &mut local).is_ok()
+            && SystemTimeToFileTime(&local, &mut local_ft).is_ok()
+    };
+    if !converted {
+        return 0;
+    }
+    ((from_filetime(local_ft) - ticks) / TICKS_PER_SEC) as i32
+}
+
+/// 指定分钟 (含) 之前最近的一次动作,同一分钟内停止优先
+pub fn last_event(rules: &[ScheduleRule], at: i64) -> Option<(i64, ScheduleAction)> {
+    let today = at.div_euclid(MINUTES_PER_DAY);
+    (0..=LOOKBACK_DAYS).find_map(|back| {
+        let day = today - back;
+        rules
+            .iter()
+            .flat_map(|rule| rule.events_on(day))
+            .map(|(m, action)| (day * MINUTES_PER_DAY + m, action))
+            .filter(|(minute, _)| *minute <= at)
+            .max_by_key(|(minute, action)| (*minute, *action == ScheduleAction::Stop))
+    })
+}
+
+/// 指定分钟之后的下一次动作
+pub fn next_event(rules: &[ScheduleRule], after: i64) -> Option<(i64, ScheduleAction)> {
+    let today = after.div_euclid(MINUTES_PER_DAY);
+    (0..=LOOKAHEAD_DAYS).find_map(|ahead| {
+        let day = today + ahead;
+        rules
+            .iter()
+            .flat_map(|rule| rule.events_on(day))
+            .map(|(m, action)| (day * MINUTES_PER_DAY + m, action))
+            .filter(|(minute, _)| *minute > after)
+            .min_by_key(|(minute, action)| (*minute, *action != ScheduleAction::Stop))
+    })
+}
+
+/// 到期的动作
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScheduledAction {
+    pub profile: String,
+    pub action: ScheduleAction,
+    /// 计划执行时间 (Unix 秒),补执行时早于当前时间
+    pub due_at: i64,
+}
+
+/// 账号配置的下一次定时动作
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NextFire {
+    pub profile: String,
+    pub action: ScheduleAction,
+    /// Unix 秒
+    pub at: i64,
+    /// 本地时间 "YYYY-MM-DD HH:MM"
+    pub local: String,
+}
+
+/// 所有设置了定时规则的账号配置的下一次动作
+pub fn next_fires(profiles: &[AccountProfile], now: LocalTime) -> Vec<NextFire> {
+    profiles
+        .iter()
+        .filter_map(|profile| {
+            let (minute, action) = next_event(&profile.schedule, now.minute())?;
+            Some(NextFire {
+                profile: profile.name.clone(),
+                action,
+                at: now.unix_of(minute),
+                local: format_minute(minute),
+            })
+        })
+        .collect()
+}
+
+/// 调度器
+///
+/// 不直接启动或停止实例: `due` 返回到期的动作,由调用方执行。
+pub struct Scheduler<C> {
+    clock: C,
+    /// 各账号配置已处理的最近一次动作 (本地分钟数)
+    handled: HashMap<String, i64>,
+}
+
+impl<C: Clock> Scheduler<C> {
+    pub fn new(clock: C) -> Self {
+        Self {
+            clock,
+            handled: HashMap::new(),
+        }
+    }
+
+    pub fn now(&self) -> LocalTime {
+        self.clock.now()
+    }
+
+    /// 忘记已处理的动作,下次检查视为首次检查
+    pub fn reset(&mut self) {
+        self.handled.clear();
+    }
+
+    /// 返回自上次检查以来到期的动作 (每个账号配置最多一个)
+    pub fn due(&mut self, profiles: &[AccountProfile]) -> Vec<ScheduledAction> {
+        let now = self.clock.now();
+        let minute = now.minute();
+
+        // 时钟被调回或账号配置被删除时丢弃记录
+        self.handled
+            .retain(|name, handled| *handled <= minute && profiles.iter().any(|p| &p.name == name));
+
+        let mut due = Vec::new();
+        for profile in profiles {
+            let Some((at, action)) = last_event(&profile.schedule, minute) else {
+                continue;
+            };
+            let previous = self.handled.insert(profile.name.clone(), at);
+            match previous {
+                Some(previous) if previous >= at => continue,
+                // 首次检查只补启动
+                None if action == ScheduleAction::Stop => continue,
+                _ => {}
+            }
+            due.push(ScheduledAction {
+                profile: profile.name.clone(),
+                action,
+                due_at: now.unix_of(at),
+            });
+        }
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AppType;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    /// 可手动拨动的时钟 (UTC+8)
+    #[derive(Clone)]
+    struct FakeClock(Rc<Cell<i64>>);
+
+    impl FakeClock {
+        /// 2026-10-19 (周一) 的本地时间
+        fn monday(hour: i64, minute: i64) -> Self {
+            let clock = Self(Rc::new(Cell::new(0)));
+            clock.set(0, hour, minute);
+            clock
+        }
+
+        /// 设为 2026-10-19 之后第 day 天的本地时间
+        fn set(&self, day: i64, hour: i64, minute: i64) {
+            const MONDAY: i64 = 20_745; // 2026-10-19
+            let local = ((MONDAY + day) * MINUTES_PER_DAY + hour * 60 + minute) * 60;
+            self.0.set(local - 8 * 3600);
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> LocalTime {
+            LocalTime {
+                unix: self.0.get(),
+                offset: 8 * 3600,
+            }
+        }
+    }
+
+    fn profile(name: &str, rules: Vec<ScheduleRule>) -> AccountProfile {
+        let mut profile = AccountProfile::new(name, AppType::WeCom);
+        profile.schedule = rules;
+        profile
+    }
+
+    fn office_hours() -> AccountProfile {
+        profile("客服", vec![ScheduleRule::parse_window("mon-fri 09:00-18:00").unwrap()])
+    }
+
+    fn actions(due: &[ScheduledAction]) -> Vec<(&str, ScheduleAction)> {
+        due.iter().map(|d| (d.profile.as_str(), d.action)).collect()
+    }
+
+    #[test]
+    fn test_calendar() {
+        assert_eq!(civil_from_days(20_745), (2026, 10, 19));
+        assert_eq!(Weekday::of_day(20_745), Weekday::Mon);
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(format_minute(20_745 * MINUTES_PER_DAY + 9 * 60 + 5), "2026-10-19 09:05");
+    }
+
+    #[test]
+    fn test_window_transitions() {
+        let clock = FakeClock::monday(8, 0);
+        let mut scheduler = Scheduler::new(clock.clone());
+        let profiles = [office_hours()];
+
+        // 首次检查在时间段外: 不补执行停止
+        assert!(scheduler.due(&profiles).is_empty());
+
+        clock.set(0, 9, 0);
+        assert_eq!(actions(&scheduler.due(&profiles)), [("客服", ScheduleAction::Start)]);
+        clock.set(0, 9, 1);
+        assert!(scheduler.due(&profiles).is_empty());
+
+        clock.set(0, 18, 0);
+        let due = scheduler.due(&profiles);
+        assert_eq!(actions(&due), [("客服", ScheduleAction::Stop)]);
+        assert_eq!(due[0].due_at, clock.now().unix);
+
+        // 周六不启动 (最近一次是周五的停止)
+        clock.set(5, 9, 30);
+        assert_eq!(actions(&scheduler.due(&profiles)), [("客服", ScheduleAction::Stop)]);
+        clock.set(5, 10, 0);
+        assert!(scheduler.due(&profiles).is_empty());
+    }
+
+    #[test]
+    fn test_first_check_inside_window_starts() {
+        let clock = FakeClock::monday(10, 30);
+        let mut scheduler = Scheduler::new(clock.clone());
+        let due = scheduler.due(&[office_hours()]);
+        assert_eq!(actions(&due), [("客服", ScheduleAction::Start)]);
+        // 补执行的动作带有原计划时间
+        assert_eq!(due[0].due_at, clock.now().unix - 90 * 60);
+    }
+
+    #[test]
+    fn test_catch_up_after_sleep() {
+        let clock = FakeClock::monday(17, 0);
+        let mut scheduler = Scheduler::new(clock.clone());
+        let profiles = [office_hours()];
+        scheduler.due(&profiles);
+
+        // 17:00 休眠,20:00 唤醒: 补执行错过的停止
+        clock.set(0, 20, 0);
+        assert_eq!(actions(&scheduler.due(&profiles)), [("客服", ScheduleAction::Stop)]);
+
+        // 休眠整晚,次日 10:00 唤醒: 只执行最近一次 (启动)
+        clock.set(1, 10, 0);
+        assert_eq!(actions(&scheduler.due(&profiles)), [("客服", ScheduleAction::Start)]);
+
+        // 周五休眠到下周一 08:00: 最近一次是周五的停止
+        clock.set(4, 12, 0);
+        scheduler.due(&profiles);
+        clock.set(7, 8, 0);
+        assert_eq!(actions(&scheduler.due(&profiles)), [("客服", ScheduleAction::Stop)]);
+    }
+
+    #[test]
+    fn test_clock_moved_back() {
+        let clock = FakeClock::monday(10, 0);
+        let mut scheduler = Scheduler::new(clock.clone());
+        let profiles = [office_hours()];
+        scheduler.due(&profiles);
+
+        // 时钟调回到 09:00 之前,再次到达 09:00 时重新启动
+        clock.set(0, 8, 0);
+        assert!(scheduler.due(&profiles).is_empty());
+        clock.set(0, 9, 0);
+        assert_eq!(actions(&scheduler.due(&profiles)), [("客服", ScheduleAction::Start)]);
+    }
+
+    #[test]
+    fn test_overnight_window() {
+        let rules = vec![ScheduleRule::parse_window("fri 22:00-06:00").unwrap()];
+        let friday = 20_745 + 4;
+        let at = |day: i64, h: i64| day * MINUTES_PER_DAY + h * 60;
+
+        assert_eq!(last_event(&rules, at(friday, 23)), Some((at(friday, 22), ScheduleAction::Start)));
+        // 周六 06:00 停止 (属于周五的时间段)
+        assert_eq!(next_event(&rules, at(friday, 23)), Some((at(friday + 1, 6), ScheduleAction::Stop)));
+        assert_eq!(last_event(&rules, at(friday + 2, 6)), Some((at(friday + 1, 6), ScheduleAction::Stop)));
+    }
+
+    #[test]
+    fn test_cron() {
+        let cron = CronExpr::parse("*/15 9-17 * * 1-5").unwrap();
+        assert_eq!(cron.minutes_of_day().next(), Some(9 * 60));
+        assert_eq!(cron.minutes_of_day().count(), 9 * 4);
+        assert!(cron.matches_day(20_745));
+        assert!(!cron.matches_day(20_745 + 6));
+
+        // 周字段 7 也表示周日;日和周都有限制时满足其一即可
+        assert!(CronExpr::parse("0 9 * * 7").unwrap().matches_day(20_745 + 6));
+        let either = CronExpr::parse("0 9 1 * 1").unwrap();
+        assert!(either.matches_day(20_745));
+        assert!(either.matches_day(20_745 + 13)); // 11 月 1 日,周日
+
+        assert!(CronExpr::parse("0 9 * *").is_err());
+        assert!(CronExpr::parse("60 9 * * *").is_err());
+        assert!(CronExpr::parse("*/0 9 * * *").is_err());
+
+        let clock = FakeClock::monday(8, 59);
+        let mut scheduler = Scheduler::new(clock.clone());
+        let profiles = [profile(
+            "值班",
+            vec![
+                ScheduleRule::Cron {
+                    expr: "0 9 * * 1-5".to_string(),
+                    action: ScheduleAction::Start,
+                },
+                ScheduleRule::Cron {
+                    expr: "30 12 * * *".to_string(),
+                    action: ScheduleAction::Stop,
+                },
+            ],
+        )];
+        scheduler.due(&profiles);
+        clock.set(0, 9, 0);
+        assert_eq!(actions(&scheduler.due(&profiles)), [("值班", ScheduleAction::Start)]);
+
+        let next = next_fires(&profiles, clock.now());
+        assert_eq!(next[0].action, ScheduleAction::Stop);
+        assert_eq!(next[0].local, "2026-10-19 12:30");
+        assert_eq!(next[0].at, clock.now().unix + 210 * 60);
+    }
+
+    #[test]
+    fn test_parse_window() {
+        let rule = ScheduleRule::parse_window("sat,sun 10:00-16:30").unwrap();
+        assert_eq!(
+            rule,
+            ScheduleRule::Window {
+                days: vec![Weekday::Sat, Weekday::Sun],
+                start: TimeOfDay { hour: 10, minute: 0 },
+                stop: TimeOfDay { hour: 16, minute: 30 },
+            }
+        );
+        let json = serde_json::to_value(&rule).unwrap();
+        assert_eq!(json["kind"], "window");
+        assert_eq!(json["stop"], "16:30");
+
+        assert!(ScheduleRule::parse_window("daily 09:00-09:00").is_err());
+        assert!(ScheduleRule::parse_window("fri-mon 09:00-18:00").is_err());
+        assert!(ScheduleRule::parse_window("mon 9-18").is_err());
+        assert!(ScheduleRule::parse_window("mon 24:00-18:00").is_err());
+    }
+}
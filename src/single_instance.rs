@@ -0,0 +1,365 @@
+// 工具自身的单实例锁
+//
+// - GUI 锁: 同一用户只运行一个 GUI,再次打开时把参数转发给已运行的 GUI 并激活其窗口
+// - 启动锁: CLI、GUI、后台服务启动实例时互斥,避免同时关闭 Mutex 导致重复启动
+//
+// 使用操作系统的文件锁,持有锁的进程退出 (包括崩溃) 后自动释放;锁文件中记录持有者的 PID。
+
+use crate::app_dirs;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+/// GUI 锁文件名 (位于数据目录)
+pub const GUI_LOCK_FILE: &str = "gui.lock";
+/// 启动锁文件名 (位于数据目录)
+pub const SPAWN_LOCK_FILE: &str = "spawn.lock";
+/// GUI 接收转发请求的 Unix socket 文件名 (位于数据目录)
+pub const GUI_SOCKET_FILE: &str = "gui.sock";
+/// 等待启动锁的最长时间
+pub const SPAWN_LOCK_TIMEOUT: Duration = Duration::from_secs(300);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 已持有的文件锁,drop 时释放
+#[derive(Debug)]
+pub struct LockFile {
+    _file: File,
+    path: PathBuf,
+}
+
+impl LockFile {
+    /// 尝试获取锁,已被其他进程 (或本进程的其他句柄) 持有时返回 None
+    pub fn try_acquire(path: &Path) -> Result<Option<Self>, String> {
+        let file = open(path)?;
+        match file.try_lock() {
+            Ok(()) => Self::locked(file, path).map(Some),
+            Err(TryLockError::WouldBlock) => Ok(None),
+            Err(TryLockError::Error(e)) => Err(format!("获取锁失败 {}: {}", path.display(), e)),
+        }
+    }
+
+    /// 等待获取锁,超时返回错误
+    pub async fn acquire(path: &Path, timeout: Duration) -> Result<Self, String> {
+        let started = Instant::now();
+        let mut waiting = false;
+        loop {
+            if let Some(lock) = Self::poll(path, started, timeout, &mut waiting)? {
+                return Ok(lock);
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// 阻塞等待获取锁,超时返回错误 (同步代码使用,异步任务中需放在 spawn_blocking 里调用)
+    pub fn acquire_blocking(path: &Path, timeout: Duration) -> Result<Self, String> {
+        let started = Instant::now();
+        let mut waiting = false;
+        loop {
+            if let Some(lock) = Self::poll(path, started, timeout, &mut waiting)? {
+                return Ok(lock);
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// 尝试获取一次锁,仍被占用时返回 None,超过等待时间时返回错误
+    fn poll(path: &Path, started: Instant, timeout: Duration, waiting: &mut bool) -> Result<Option<Self>, String> {
+        if let Some(lock) = Self::try_acquire(path)? {
+            return Ok(Some(lock));
+        }
+        if !*waiting {
+            eprintln!("等待其他进程{}完成启动...", describe_owner(path));
+            *waiting = true;
+        }
+        if started.elapsed() >= timeout {
+            return Err(format!(
+                "其他进程{}正在启动实例,等待 {} 秒后超时",
+                describe_owner(path),
+                timeout.as_secs()
+            ));
+        }
+        Ok(None)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn locked(mut file: File, path: &Path) -> Result<Self, String> {
+        // 记录持有者 (锁本身由操作系统维护,与文件内容无关)
+        file.set_len(0)
+            .and_then(|_| file.write_all(std::process::id().to_string().as_bytes()))
+            .map_err(|e| format!("写入锁文件失败 {}: {}", path.display(), e))?;
+        Ok(Self {
+            _file: file,
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+fn open(path: &Path) -> Result<File, String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("创建目录失败 {}: {}", parent.display(), e))?;
+    }
+    // 不截断: 文件可能正被其他进程持有
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .map_err(|e| format!("打开锁文件失败 {}: {}", path.display(), e))
+}
+
+/// 锁文件中记录的持有者 PID
+///
+/// Windows 上文件被锁定时其他进程无法读取,返回 None。
+pub fn owner(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// 锁是否正被持有 (如 GUI 是否在运行)
+pub fn is_locked(path: &Path) -> bool {
+    matches!(LockFile::try_acquire(path), Ok(None))
+}
+
+fn describe_owner(path: &Path) -> String {
+    owner(path).map(|pid| format!(" (PID {}) ", pid)).unwrap_or_default()
+}
+
+/// 获取启动锁,启动完成前持有
+pub async fn lock_spawn() -> Result<LockFile, String> {
+    LockFile::acquire(&app_dirs::data_dir().join(SPAWN_LOCK_FILE), SPAWN_LOCK_TIMEOUT).await
+}
+
+/// 获取启动锁 (同步代码使用)
+pub fn lock_spawn_blocking() -> Result<LockFile, String> {
+    LockFile::acquire_blocking(&app_dirs::data_dir().join(SPAWN_LOCK_FILE), SPAWN_LOCK_TIMEOUT)
+}
+
+/// GUI 锁文件路径
+pub fn gui_lock_path() -> PathBuf {
+    app_dirs::data_dir().join(GUI_LOCK_FILE)
+}
+
+/// GUI 接收转发请求的地址
+#[cfg(unix)]
+pub fn gui_endpoint() -> PathBuf {
+    app_dirs::data_dir().join(GUI_SOCKET_FILE)
+}
+
+/// GUI 接收转发请求的地址
+#[cfg(windows)]
+pub fn gui_endpoint() -> PathBuf {
+    let user = std::env::var("USERNAME").unwrap_or_default();
+    PathBuf::from(format!(r"\\.\pipe\wecom-multi-open-gui-{}", user))
+}
+
+/// 再次打开 GUI 时转发给已运行 GUI 的请求
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Activation {
+    /// 发起转发的进程
+    pub pid: u32,
+    /// 命令行参数 (不含程序名)
+    pub args: Vec<String>,
+}
+
+impl Activation {
+    /// 当前进程的请求
+    pub fn current() -> Self {
+        Self {
+            pid: std::process::id(),
+            args: std::env::args().skip(1).collect(),
+        }
+    }
+}
+
+/// 把请求转发给已运行的 GUI
+pub async fn forward(endpoint: &Path, activation: &Activation) -> Result<(), String> {
+    let mut text = serde_json::to_string(activation).map_err(|e| e.to_string())?;
+    text.push('\n');
+
+    let mut stream = connect(endpoint)
+        .await
+        .map_err(|e| format!("连接已运行的 GUI 失败 {}: {}", endpoint.display(), e))?;
+    stream
+        .write_all(text.as_bytes())
+        .await
+        .map_err(|e| format!("转发请求失败: {}", e))?;
+    // 等待对方确认,确保窗口已被激活
+    let mut reply = String::new();
+    BufReader::new(stream)
+        .read_line(&mut reply)
+        .await
+        .map_err(|e| format!("转发请求失败: {}", e))?;
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn connect(endpoint: &Path) -> std::io::Result<tokio::net::UnixStream> {
+    tokio::net::UnixStream::connect(endpoint).await
+}
+
+#[cfg(windows)]
+async fn connect(endpoint: &Path) -> std::io::Result<tokio::net::windows::named_pipe::NamedPipeClient> {
+    tokio::net::windows::named_pipe::ClientOptions::new().open(endpoint)
+}
+
+/// 处理一个转发连接: 读取一行请求,回复后关闭
+async fn handle_connection<S>(stream: S, on_activate: &(impl Fn(Activation) + Send + Sync + 'static))
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut line = String::new();
+    if BufReader::new(reader).read_line(&mut line).await.is_err() {
+        return;
+    }
+    match serde_json::from_str::<Activation>(&line) {
+        Ok(activation) => {
+            on_activate(activation);
+            let _ = writer.write_all(b"ok\n").await;
+        }
+        Err(e) => eprintln!("⚠ 无效的转发请求: {}", e),
+    }
+}
+
+/// 接收其他 GUI 进程转发的请求 (由持有 GUI 锁的进程调用,不会返回)
+#[cfg(unix)]
+pub async fn listen(endpoint: &Path, on_activate: impl Fn(Activation) + Send + Sync + 'static) -> Result<(), String> {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+    use tokio::net::UnixListener;
+
+    // 持有 GUI 锁时已存在的 socket 文件都是上次异常退出留下的
+    let _ = fs::remove_file(endpoint);
+    if let Some(parent) = endpoint.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("创建目录失败 {}: {}", parent.display(), e))?;
+    }
+    let listener = UnixListener::bind(endpoint).map_err(|e| format!("监听 {} 失败: {}", endpoint.display(), e))?;
+    fs::set_permissions(endpoint, fs::Permissions::from_mode(0o600)).map_err(|e| format!("设置 socket 权限失败: {}", e))?;
+    let owner = fs::metadata(endpoint).map(|m| m.uid()).map_err(|e| format!("读取 socket 信息失败: {}", e))?;
+
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                eprintln!("⚠ 接受连接失败: {}", e);
+                continue;
+            }
+        };
+        // 只接受当前用户的连接
+        match stream.peer_cred() {
+            Ok(cred) if cred.uid() == owner => handle_connection(stream, &on_activate).await,
+            _ => eprintln!("⚠ 拒绝其他用户的连接"),
+        }
+    }
+}
+
+/// 接收其他 GUI 进程转发的请求 (由持有 GUI 锁的进程调用,不会返回)
+#[cfg(windows)]
+pub async fn listen(endpoint: &Path, on_activate: impl Fn(Activation) + Send + Sync + 'static) -> Result<(), String> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let create = |first: bool| {
+        ServerOptions::new()
+            .first_pipe_instance(first)
+            .reject_remote_clients(true)
+            .create(endpoint)
+    };
+    let mut server = create(true).map_err(|e| format!("创建命名管道失败: {}", e))?;
+
+    loop {
+        if let Err(e) = server.connect().await {
+            eprintln!("⚠ 接受连接失败: {}", e);
+            continue;
+        }
+        let next = create(false).map_err(|e| format!("创建命名管道失败: {}", e))?;
+        let stream = std::mem::replace(&mut server, next);
+        handle_connection(stream, &on_activate).await;
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_lock_is_exclusive() {
+        let dir = app_dirs::temp_test_dir("single-instance-lock");
+        let path = dir.join(GUI_LOCK_FILE);
+
+        let lock = LockFile::try_acquire(&path).unwrap().unwrap();
+        assert_eq!(owner(&path), Some(std::process::id()));
+        assert!(LockFile::try_acquire(&path).unwrap().is_none());
+        assert!(is_locked(&path));
+
+        drop(lock);
+        assert!(!is_locked(&path));
+        assert!(LockFile::try_acquire(&path).unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_acquire_waits_for_release() {
+        let dir = app_dirs::temp_test_dir("single-instance-wait");
+        let path = dir.join(SPAWN_LOCK_FILE);
+
+        let lock = LockFile::try_acquire(&path).unwrap().unwrap();
+        let error = LockFile::acquire(&path, Duration::from_millis(300)).await.unwrap_err();
+        assert!(error.contains("超时"), "{}", error);
+
+        let release = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            drop(lock);
+        });
+        LockFile::acquire(&path, Duration::from_secs(5)).await.unwrap();
+        release.await.unwrap();
+    }
+
+    #[test]
+    fn test_acquire_blocking_times_out() {
+        let dir = app_dirs::temp_test_dir("single-instance-blocking");
+        let path = dir.join(SPAWN_LOCK_FILE);
+
+        let lock = LockFile::try_acquire(&path).unwrap().unwrap();
+        let error = LockFile::acquire_blocking(&path, Duration::from_millis(300)).unwrap_err();
+        assert!(error.contains("超时"), "{}", error);
+
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(300));
+            drop(lock);
+        });
+        LockFile::acquire_blocking(&path, Duration::from_secs(5)).unwrap();
+        release.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_forward_activation() {
+        let dir = app_dirs::temp_test_dir("single-instance-forward");
+        let endpoint = dir.join(GUI_SOCKET_FILE);
+        // 上次异常退出留下的 socket 文件
+        fs::write(&endpoint, "").unwrap();
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = received.clone();
+        let listen_endpoint = endpoint.clone();
+        let server = tokio::spawn(async move {
+            listen(&listen_endpoint, move |activation| sink.lock().unwrap().push(activation)).await
+        });
+        while forward(&endpoint, &Activation::current()).await.is_err() {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let activation = Activation {
+            pid: 1,
+            args: vec!["--minimized".to_string()],
+        };
+        forward(&endpoint, &activation).await.unwrap();
+        assert_eq!(received.lock().unwrap().last(), Some(&activation));
+        server.abort();
+    }
+}
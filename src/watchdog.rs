@@ -0,0 +1,669 @@
+// 实例守护 - 实例意外退出时按原配置重新启动
+// 可选功能: 需要调用方显式启用
+
+use crate::{platform, AppType, InstanceConfig, SpawnRequest};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// 守护策略
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct WatchdogPolicy {
+    /// 是否启用自动重启
+    pub enabled: bool,
+    /// 连续重启次数上限,超过后放弃
+    pub max_restarts: u32,
+    /// 首次重启前的等待时间
+    pub initial_backoff_ms: u64,
+    /// 退避等待时间上限 (每次连续崩溃翻倍)
+    pub max_backoff_ms: u64,
+    /// 实例稳定运行超过此时长后,连续重启计数清零
+    pub stable_after_secs: u64,
+    /// 检测间隔
+    pub poll_interval_ms: u64,
+}
+
+impl Default for WatchdogPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_restarts: 5,
+            initial_backoff_ms: 2_000,
+            max_backoff_ms: 60_000,
+            stable_after_secs: 300,
+            poll_interval_ms: 2_000,
+        }
+    }
+}
+
+impl WatchdogPolicy {
+    /// 第 n 次连续重启 (从 0 开始) 前的等待时间
+    pub fn backoff(&self, consecutive: u32) -> Duration {
+        let factor = 1u64.checked_shl(consecutive).unwrap_or(u64::MAX);
+        Duration::from_millis(self.initial_backoff_ms.saturating_mul(factor).min(self.max_backoff_ms))
+    }
+}
+
+/// 被守护的实例 (重启所需的全部信息)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WatchedInstance {
+    pub pid: u32,
+    pub app_type: AppType,
+    pub app_path: Option<PathBuf>,
+    /// 数据目录等实例配置,重启时原样复用
+    pub config: InstanceConfig,
+}
+
+/// 守护事件
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum WatchdogEvent {
+    /// 用户主动关闭 (工具发起的关闭或正常退出),不会重启
+    Stopped { pid: u32 },
+    /// 检测到异常退出,将在 delay_ms 后重启
+    Crashed { pid: u32, exit_code: Option<i32>, attempt: u32, delay_ms: u64 },
+    /// 重启成功
+    Restarted { old_pid: u32, new_pid: u32, attempt: u32 },
+    /// 重启失败,将继续退避重试
+    RestartFailed { pid: u32, attempt: u32, error: String },
+    /// 连续重启次数超过上限,放弃守护
+    GaveUp { pid: u32, restarts: u32 },
+}
+
+/// 进程退出观测结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitObservation {
+    Running,
+    /// 已退出 (能获取时附带退出码,被信号结束时为 128 + 信号值;无法获取时为 None)
+    Exited(Option<i32>),
+}
+
+/// 待执行的重启任务
+#[derive(Debug, Clone, PartialEq)]
+pub struct RestartJob {
+    pub pid: u32,
+    pub attempt: u32,
+    pub instance: WatchedInstance,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EntryState {
+    Running,
+    Backoff { until: Instant },
+    Restarting,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    instance: WatchedInstance,
+    started_at: Instant,
+    /// 连续重启次数
+    restarts: u32,
+    state: EntryState,
+}
+
+/// 守护状态机
+///
+/// 不直接启动进程: `poll` 返回到期的重启任务,由调用方执行后通过
+/// `complete_restart` 回报结果,便于在测试中使用虚拟时间驱动。
+#[derive(Debug, Default)]
+pub struct Supervisor {
+    policy: WatchdogPolicy,
+    entries: HashMap<u32, Entry>,
+    expected_exits: HashSet<u32>,
+}
+
+impl Supervisor {
+    pub fn new(policy: WatchdogPolicy) -> Self {
+        Self {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    pub fn policy(&self) -> &WatchdogPolicy {
+        &self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: WatchdogPolicy) {
+        self.policy = policy;
+    }
+
+    /// 开始守护实例
+    pub fn watch(&mut self, instance: WatchedInstance, now: Instant) {
+        self.expected_exits.remove(&instance.pid);
+        self.entries.insert(
+            instance.pid,
+            Entry {
+                instance,
+                started_at: now,
+                restarts: 0,
+                state: EntryState::Running,
+            },
+        );
+    }
+
+    /// 停止守护实例
+    pub fn unwatch(&mut self, pid: u32) -> Option<WatchedInstance> {
+        self.expected_exits.remove(&pid);
+        self.entries.remove(&pid).map(|e| e.instance)
+    }
+
+    /// 标记即将由用户关闭的实例,其退出不视为崩溃
+    pub fn expect_exit(&mut self, pid: u32) {
+        if self.entries.contains_key(&pid) {
+            self.expected_exits.insert(pid);
+        }
+    }
+
+    /// 当前守护的实例
+    pub fn watched(&self) -> Vec<WatchedInstance> {
+        self.entries.values().map(|e| e.instance.clone()).collect()
+    }
+
+    /// 检查所有实例,返回产生的事件和到期的重启任务
+    pub fn poll<F>(&mut self, now: Instant, mut observe: F) -> (Vec<WatchdogEvent>, Vec<RestartJob>)
+    where
+        F: FnMut(u32) -> ExitObservation,
+    {
+        let mut events = Vec::new();
+        let mut jobs = Vec::new();
+        let mut removed = Vec::new();
+
+        for (&pid, entry) in self.entries.iter_mut() {
+            match entry.state {
+                EntryState::Running => {
+                    let ExitObservation::Exited(exit_code) = observe(pid) else {
+                        continue;
+                    };
+
+                    // 工具发起的关闭、正常退出 (退出码 0)、无法确定是否崩溃 (如不是本进程启动的实例)
+                    // 或未启用守护: 不重启
+                    if self.expected_exits.remove(&pid) || matches!(exit_code, None | Some(0)) || !self.policy.enabled {
+                        events.push(WatchdogEvent::Stopped { pid });
+                        removed.push(pid);
+                        continue;
+                    }
+
+                    // 稳定运行一段时间后的崩溃重新计数
+                    if now.duration_since(entry.started_at) >= Duration::from_secs(self.policy.stable_after_secs) {
+                        entry.restarts = 0;
+                    }
+
+                    if entry.restarts >= self.policy.max_restarts {
+                        events.push(WatchdogEvent::GaveUp { pid, restarts: entry.restarts });
+                        removed.push(pid);
+                        continue;
+                    }
+
+                    let delay = self.policy.backoff(entry.restarts);
+                    entry.state = EntryState::Backoff { until: now + delay };
+                    events.push(WatchdogEvent::Crashed {
+                        pid,
+                        exit_code,
+                        attempt: entry.restarts + 1,
+                        delay_ms: delay.as_millis() as u64,
+                    });
+                }
+                EntryState::Backoff { until } if now >= until => {
+                    entry.state = EntryState::Restarting;
+                    jobs.push(RestartJob {
+                        pid,
+                        attempt: entry.restarts + 1,
+                        instance: entry.instance.clone(),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        for pid in removed {
+            self.entries.remove(&pid);
+        }
+
+        (events, jobs)
+    }
+
+    /// 回报重启结果
+    pub fn complete_restart(&mut self, pid: u32, result: Result<u32, String>, now: Instant) -> Option<WatchdogEvent> {
+        let mut entry = self.entries.remove(&pid)?;
+        entry.restarts += 1;
+        let attempt = entry.restarts;
+
+        match result {
+            Ok(new_pid) => {
+                entry.instance.pid = new_pid;
+                entry.started_at = now;
+                entry.state = EntryState::Running;
+                self.entries.insert(new_pid, entry);
+                Some(WatchdogEvent::Restarted { old_pid: pid, new_pid, attempt })
+            }
+            Err(error) if attempt < self.policy.max_restarts => {
+                entry.state = EntryState::Backoff { until: now + self.policy.backoff(attempt) };
+                self.entries.insert(pid, entry);
+                Some(WatchdogEvent::RestartFailed { pid, attempt, error })
+            }
+            Err(_) => Some(WatchdogEvent::GaveUp { pid, restarts: attempt }),
+        }
+    }
+}
+
+/// 守护进程句柄 (可在多个线程间共享)
+#[derive(Clone, Default)]
+pub struct Watchdog {
+    inner: Arc<Mutex<Supervisor>>,
+    probe: Arc<Mutex<ExitProbe>>,
+}
+
+impl Watchdog {
+    pub fn new(policy: WatchdogPolicy) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Supervisor::new(policy))),
+            probe: Arc::default(),
+        }
+    }
+
+    pub fn policy(&self) -> WatchdogPolicy {
+        self.inner.lock().unwrap().policy().clone()
+    }
+
+    pub fn set_policy(&self, policy: WatchdogPolicy) {
+        self.inner.lock().unwrap().set_policy(policy);
+    }
+
+    pub fn watch(&self, instance: WatchedInstance) {
+        self.probe.lock().unwrap().track(instance.pid);
+        self.inner.lock().unwrap().watch(instance, Instant::now());
+    }
+
+    pub fn unwatch(&self, pid: u32) -> Option<WatchedInstance> {
+        self.probe.lock().unwrap().forget(pid);
+        self.inner.lock().unwrap().unwatch(pid)
+    }
+
+    pub fn expect_exit(&self, pid: u32) {
+        self.inner.lock().unwrap().expect_exit(pid);
+    }
+
+    pub fn watched(&self) -> Vec<WatchedInstance> {
+        self.inner.lock().unwrap().watched()
+    }
+
+    /// 守护循环,由调用方在异步运行时中执行
+    pub async fn run<F>(self, on_event: F)
+    where
+        F: Fn(WatchdogEvent) + Send + Sync + 'static,
+    {
+        loop {
+            let interval = self.inner.lock().unwrap().policy().poll_interval_ms.max(100);
+            tokio::time::sleep(Duration::from_millis(interval)).await;
+
+            let (events, jobs) = {
+                let mut probe = self.probe.lock().unwrap();
+                self.inner
+                    .lock()
+                    .unwrap()
+                    .poll(Instant::now(), |pid| probe.observe(pid))
+            };
+
+            for event in events {
+                if let WatchdogEvent::Stopped { pid } | WatchdogEvent::GaveUp { pid, .. } = &event {
+                    self.probe.lock().unwrap().forget(*pid);
+                }
+                on_event(event);
+            }
+
+            for job in jobs {
+                let result = relaunch(&job.instance).await;
+
+                if let Ok(new_pid) = result {
+                    let mut probe = self.probe.lock().unwrap();
+                    probe.forget(job.pid);
+                    probe.track(new_pid);
+                }
+
+                let event = self.inner.lock().unwrap().complete_restart(job.pid, result, Instant::now());
+                if let Some(event) = event {
+                    on_event(event);
+                }
+            }
+        }
+    }
+}
+
+/// 按原配置启动一个新实例
+async fn relaunch(instance: &WatchedInstance) -> Result<u32, String> {
+    let req = SpawnRequest {
+        count: 1,
+        app_path: instance.app_path.clone(),
+        app_type: Some(instance.app_type.clone()),
+        instance_configs: Some(vec![instance.config.clone()]),
+        dry_run: false,
+    };
+
+    let response = platform::spawn_multiple(req).await?;
+    response
+        .pids
+        .first()
+        .copied()
+        .ok_or_else(|| "实例启动失败".to_string())
+}
+
+/// 进程退出探测 (Windows: 持有进程句柄以读取退出码)
+#[cfg(target_os = "windows")]
+#[derive(Default)]
+pub struct ExitProbe {
+    handles: HashMap<u32, windows::Win32::Foundation::HANDLE>,
+}
+
+#[cfg(target_os = "windows")]
+impl ExitProbe {
+    pub fn track(&mut self, pid: u32) {
+        use windows::Win32::System::Threading::{OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION};
+
+        if let Ok(handle) = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) } {
+            if let Some(old) = self.handles.insert(pid, handle) {
+                let _ = unsafe { windows::Win32::Foundation::CloseHandle(old) };
+            }
+        }
+    }
+
+    pub fn forget(&mut self, pid: u32) {
+        if let Some(handle) = self.handles.remove(&pid) {
+            let _ = unsafe { windows::Win32::Foundation::CloseHandle(handle) };
+        }
+    }
+
+    pub fn observe(&mut self, pid: u32) -> ExitObservation {
+        use windows::Win32::Foundation::STILL_ACTIVE;
+        use windows::Win32::System::Threading::GetExitCodeProcess;
+
+        let Some(handle) = self.handles.get(&pid) else {
+            return if platform::process_exists(pid) {
+                ExitObservation::Running
+            } else {
+                ExitObservation::Exited(None)
+            };
+        };
+
+        let mut code = 0u32;
+        match unsafe { GetExitCodeProcess(*handle, &mut code) } {
+            Ok(_) if code == STILL_ACTIVE.0 as u32 => ExitObservation::Running,
+            Ok(_) => ExitObservation::Exited(Some(code as i32)),
+            Err(_) => ExitObservation::Exited(None),
+        }
+    }
+}
+
+/// 进程退出探测 (Unix: 通过 waitpid 回收本进程启动的实例并读取退出状态)
+///
+/// 不是本进程子进程的实例 (如 GUI 重启后恢复的实例) 无法获取退出状态,只判断是否存活。
+#[cfg(unix)]
+#[derive(Default)]
+pub struct ExitProbe;
+
+#[cfg(unix)]
+impl ExitProbe {
+    pub fn track(&mut self, _pid: u32) {}
+
+    pub fn forget(&mut self, _pid: u32) {}
+
+    pub fn observe(&mut self, pid: u32) -> ExitObservation {
+        let mut status = 0;
+        match unsafe { libc::waitpid(pid as libc::pid_t, &mut status, libc::WNOHANG) } {
+            0 => ExitObservation::Running,
+            reaped if reaped == pid as libc::pid_t => ExitObservation::Exited(exit_code(status)),
+            // 不是子进程 (ECHILD)
+            _ if platform::process_exists(pid) => ExitObservation::Running,
+            _ => ExitObservation::Exited(None),
+        }
+    }
+}
+
+/// waitpid 返回的状态对应的退出码
+#[cfg(unix)]
+fn exit_code(status: libc::c_int) -> Option<i32> {
+    if libc::WIFEXITED(status) {
+        Some(libc::WEXITSTATUS(status))
+    } else if libc::WIFSIGNALED(status) {
+        Some(128 + libc::WTERMSIG(status))
+    } else {
+        None
+    }
+}
+
+/// 进程退出探测 (无法获取退出码,仅判断存活)
+#[cfg(not(any(unix, target_os = "windows")))]
+#[derive(Default)]
+pub struct ExitProbe;
+
+#[cfg(not(any(unix, target_os = "windows")))]
+impl ExitProbe {
+    pub fn track(&mut self, _pid: u32) {}
+
+    pub fn forget(&mut self, _pid: u32) {}
+
+    pub fn observe(&mut self, pid: u32) -> ExitObservation {
+        if platform::process_exists(pid) {
+            ExitObservation::Running
+        } else {
+            ExitObservation::Exited(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> WatchdogPolicy {
+        WatchdogPolicy {
+            enabled: true,
+            max_restarts: 3,
+            initial_backoff_ms: 1_000,
+            max_backoff_ms: 3_000,
+            stable_after_secs: 60,
+            poll_interval_ms: 100,
+        }
+    }
+
+    fn instance(pid: u32) -> WatchedInstance {
+        WatchedInstance {
+            pid,
+            app_type: AppType::WeCom,
+            app_path: None,
+            config: InstanceConfig {
+                data_dir: Some(PathBuf::from("/data/1")),
+                proxy: None,
+                instance_id: Some(1),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// 异常退出 (退出码 1)
+    fn dead(_pid: u32) -> ExitObservation {
+        ExitObservation::Exited(Some(1))
+    }
+
+    fn alive(_pid: u32) -> ExitObservation {
+        ExitObservation::Running
+    }
+
+    #[test]
+    fn test_backoff_doubles_and_caps() {
+        let p = policy();
+        assert_eq!(p.backoff(0), Duration::from_secs(1));
+        assert_eq!(p.backoff(1), Duration::from_secs(2));
+        assert_eq!(p.backoff(2), Duration::from_secs(3));
+        assert_eq!(p.backoff(80), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn test_crash_restarts_with_same_config() {
+        let t0 = Instant::now();
+        let mut sup = Supervisor::new(policy());
+        sup.watch(instance(100), t0);
+
+        let (events, jobs) = sup.poll(t0 + Duration::from_secs(1), alive);
+        assert!(events.is_empty() && jobs.is_empty());
+
+        let (events, jobs) = sup.poll(t0 + Duration::from_secs(2), dead);
+        assert_eq!(
+            events,
+            vec![WatchdogEvent::Crashed { pid: 100, exit_code: Some(1), attempt: 1, delay_ms: 1_000 }]
+        );
+        assert!(jobs.is_empty(), "退避期间不应重启");
+
+        let (_, jobs) = sup.poll(t0 + Duration::from_secs(3), dead);
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].instance.config.data_dir, Some(PathBuf::from("/data/1")));
+
+        let event = sup.complete_restart(100, Ok(200), t0 + Duration::from_secs(3));
+        assert_eq!(event, Some(WatchdogEvent::Restarted { old_pid: 100, new_pid: 200, attempt: 1 }));
+        assert_eq!(sup.watched()[0].pid, 200);
+    }
+
+    #[test]
+    fn test_user_close_is_not_restarted() {
+        let t0 = Instant::now();
+        let mut sup = Supervisor::new(policy());
+        sup.watch(instance(100), t0);
+        sup.watch(instance(101), t0);
+
+        sup.expect_exit(100);
+        let (events, jobs) = sup.poll(t0, |pid| {
+            if pid == 100 {
+                ExitObservation::Exited(None)
+            } else {
+                ExitObservation::Exited(Some(0))
+            }
+        });
+
+        assert!(jobs.is_empty());
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|e| matches!(e, WatchdogEvent::Stopped { .. })));
+        assert!(sup.watched().is_empty());
+    }
+
+    #[test]
+    fn test_unknown_exit_status_is_not_restarted() {
+        // 无法获取退出状态 (如 macOS 上用户按 Cmd+Q 退出了不是本进程启动的实例) 时不视为崩溃
+        let t0 = Instant::now();
+        let mut sup = Supervisor::new(policy());
+        sup.watch(instance(100), t0);
+
+        let (events, jobs) = sup.poll(t0, |_| ExitObservation::Exited(None));
+        assert_eq!(events, vec![WatchdogEvent::Stopped { pid: 100 }]);
+        assert!(jobs.is_empty());
+        assert!(sup.watched().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exit_probe_reads_child_status() {
+        use std::process::Command;
+
+        let wait_exit = |pid: u32| {
+            let mut probe = ExitProbe;
+            for _ in 0..100 {
+                if let ExitObservation::Exited(code) = probe.observe(pid) {
+                    return code;
+                }
+                std::thread::sleep(Duration::from_millis(20));
+            }
+            panic!("进程 {} 未退出", pid);
+        };
+
+        // 与实际启动实例一样不保留 Child,由探测器回收
+        let spawn = |program: &str, args: &[&str]| Command::new(program).args(args).spawn().unwrap().id();
+
+        assert_eq!(wait_exit(spawn("sh", &["-c", "exit 0"])), Some(0));
+        assert_eq!(wait_exit(spawn("sh", &["-c", "exit 3"])), Some(3));
+
+        let killed = spawn("sleep", &["30"]);
+        assert_eq!(ExitProbe.observe(killed), ExitObservation::Running);
+        platform::kill_process(killed).unwrap();
+        assert_eq!(wait_exit(killed), Some(128 + libc::SIGKILL));
+    }
+
+    #[test]
+    fn test_disabled_policy_only_reports_stop() {
+        let t0 = Instant::now();
+        let mut sup = Supervisor::new(WatchdogPolicy { enabled: false, ..policy() });
+        sup.watch(instance(100), t0);
+
+        let (events, jobs) = sup.poll(t0, dead);
+        assert_eq!(events, vec![WatchdogEvent::Stopped { pid: 100 }]);
+        assert!(jobs.is_empty());
+    }
+
+    #[test]
+    fn test_crash_loop_gives_up() {
+        let t0 = Instant::now();
+        let mut sup = Supervisor::new(policy());
+        sup.watch(instance(1), t0);
+
+        let mut now = t0;
+        let mut pid = 1;
+        for round in 1..=3 {
+            now += Duration::from_secs(1);
+            let (events, _) = sup.poll(now, dead);
+            assert!(matches!(events[0], WatchdogEvent::Crashed { attempt, .. } if attempt == round));
+
+            now += Duration::from_secs(10);
+            let (_, jobs) = sup.poll(now, dead);
+            assert_eq!(jobs.len(), 1);
+            sup.complete_restart(pid, Ok(pid + 1), now);
+            pid += 1;
+        }
+
+        // 第 4 次崩溃超过上限
+        let (events, jobs) = sup.poll(now + Duration::from_secs(1), dead);
+        assert_eq!(events, vec![WatchdogEvent::GaveUp { pid, restarts: 3 }]);
+        assert!(jobs.is_empty());
+        assert!(sup.watched().is_empty());
+    }
+
+    #[test]
+    fn test_stable_run_resets_counter() {
+        let t0 = Instant::now();
+        let mut sup = Supervisor::new(WatchdogPolicy { max_restarts: 1, ..policy() });
+        sup.watch(instance(1), t0);
+
+        sup.poll(t0, dead);
+        sup.poll(t0 + Duration::from_secs(5), dead);
+        sup.complete_restart(1, Ok(2), t0 + Duration::from_secs(5));
+
+        // 稳定运行超过 60 秒后再次崩溃,仍然会重启
+        let (events, _) = sup.poll(t0 + Duration::from_secs(120), dead);
+        assert!(matches!(events[0], WatchdogEvent::Crashed { attempt: 1, .. }));
+    }
+
+    #[test]
+    fn test_failed_restart_backs_off_then_gives_up() {
+        let t0 = Instant::now();
+        let mut sup = Supervisor::new(WatchdogPolicy { max_restarts: 2, ..policy() });
+        sup.watch(instance(1), t0);
+
+        sup.poll(t0, dead);
+        let (_, jobs) = sup.poll(t0 + Duration::from_secs(1), dead);
+        assert_eq!(jobs.len(), 1);
+
+        let event = sup.complete_restart(1, Err("boom".into()), t0 + Duration::from_secs(1));
+        assert!(matches!(event, Some(WatchdogEvent::RestartFailed { attempt: 1, .. })));
+
+        // 第二次退避 2 秒
+        let (_, jobs) = sup.poll(t0 + Duration::from_secs(2), dead);
+        assert!(jobs.is_empty());
+        let (_, jobs) = sup.poll(t0 + Duration::from_secs(3), dead);
+        assert_eq!(jobs.len(), 1);
+
+        let event = sup.complete_restart(1, Err("boom".into()), t0 + Duration::from_secs(3));
+        assert_eq!(event, Some(WatchdogEvent::GaveUp { pid: 1, restarts: 2 }));
+        assert!(sup.watched().is_empty());
+    }
+}
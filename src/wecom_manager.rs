@@ -2,20 +2,19 @@
 // 使用 Sandboxie 实现真正的多实例隔离
 
 #[cfg(target_os = "windows")]
-use crate::windows_sandbox::{SandboxConfig, SandboxieManager};
+use crate::plan::SpawnPlan;
 #[cfg(target_os = "windows")]
-use serde::{Deserialize, Serialize};
+use crate::profiles::AccountProfile;
 #[cfg(target_os = "windows")]
-use std::path::PathBuf;
-
-/// 企业微信默认安装路径
+use crate::registry::InstanceRecord;
+#[cfg(target_os = "windows")]
+use crate::windows_sandbox::{BorderColor, BoxNameTitle, SandboxConfig, SandboxieManager};
 #[cfg(target_os = "windows")]
-const WECOM_DEFAULT_PATHS: &[&str] = &[
-    r"C:\Program Files (x86)\WXWork\WXWork.exe",
-    r"C:\Program Files\WXWork\WXWork.exe",
-    r"D:\Program Files (x86)\WXWork\WXWork.exe",
-    r"D:\Program Files\WXWork\WXWork.exe",
-];
+use crate::{AppType, IsolationMode};
+#[cfg(target_os = "windows")]
+use serde::{Deserialize, Serialize};
+#[cfg(target_os = "windows")]
+use std::path::{Path, PathBuf};
 
 /// 实例信息
 #[cfg(target_os = "windows")]
@@ -28,6 +27,24 @@ pub struct WeComInstance {
     pub running: bool,
 }
 
+#[cfg(target_os = "windows")]
+impl WeComInstance {
+    /// 应记录到实例注册表的实例信息 (未取得 PID 时返回 None)
+    pub fn record(&self) -> Option<InstanceRecord> {
+        let mut record = InstanceRecord::new(self.pid?, AppType::WeCom, IsolationMode::Sandboxie);
+        record.sandbox_name = Some(self.sandbox_name.clone());
+        Some(record)
+    }
+}
+
+/// 在沙盒中启动的程序
+#[cfg(target_os = "windows")]
+struct BoxLaunch<'a> {
+    exe: &'a Path,
+    args: &'a [String],
+    env: &'a [(String, String)],
+}
+
 /// 企业微信管理器
 #[cfg(target_os = "windows")]
 pub struct WeComManager {
@@ -38,9 +55,11 @@ pub struct WeComManager {
 #[cfg(target_os = "windows")]
 impl WeComManager {
     /// 创建管理器
+    ///
+    /// 企业微信路径与简单模式和账号配置一样通过路径检测获取 (配置、缓存、注册表、搜索目录等)。
     pub fn new() -> Result<Self, String> {
         let sandboxie = SandboxieManager::new()?;
-        let wecom_exe = Self::find_wecom_exe()?;
+        let wecom_exe = crate::path_resolver::resolve_app_path(&AppType::WeCom)?;
 
         Ok(Self {
             sandboxie,
@@ -48,50 +67,95 @@ impl WeComManager {
         })
     }
 
-    /// 查找企业微信安装路径
-    fn find_wecom_exe() -> Result<PathBuf, String> {
-        for path in WECOM_DEFAULT_PATHS {
-            let p = PathBuf::from(path);
-            if p.exists() {
-                return Ok(p);
-            }
-        }
-        Err("未找到企业微信安装路径".to_string())
+    /// 第 id 个匿名实例的沙盒名、标题和边框颜色
+    fn instance_box(id: u8) -> (String, String, &'static str) {
+        // 生成不同的边框颜色
+        let colors = ["#FF0000", "#00FF00", "#0000FF", "#FFFF00", "#FF00FF", "#00FFFF"];
+        let color = colors[id as usize % colors.len()];
+        (format!("WeCom_{}", id), format!("企业微信账号 {}", id), color)
     }
 
     /// 创建并启动实例
     pub fn create_instance(&self, id: u8) -> Result<WeComInstance, String> {
-        let sandbox_name = format!("WeCom_{}", id);
-        let title = format!("企业微信账号 {}", id);
+        let (sandbox_name, title, color) = Self::instance_box(id);
+        let config = Self::box_config(&sandbox_name, color)?;
+        let launch = BoxLaunch {
+            exe: &self.wecom_exe,
+            args: &[],
+            env: &[],
+        };
+        Self::launch_in_box(&self.sandboxie, id, title, &config, &launch)
+    }
 
-        // 生成不同的边框颜色
-        let colors = ["#FF0000", "#00FF00", "#0000FF", "#FFFF00", "#FF00FF", "#00FFFF"];
-        let _border_color = format!("{},ttl,6", colors[id as usize % colors.len()]);
-
-        // 创建沙盒配置
-        let config = SandboxConfig {
-            name: sandbox_name.clone(),
-            enabled: true,
-            config_level: 7,
-            auto_delete: false,
-            closed_file_path: vec![],
-            closed_key_path: vec![],
-            open_file_path: vec![],
-            open_key_path: vec![],
+    /// 实例沙盒的配置: 各自颜色的边框,窗口标题显示沙盒名
+    fn box_config(sandbox_name: &str, color: &str) -> Result<SandboxConfig, String> {
+        let mut config = SandboxConfig::new(sandbox_name);
+        config.border_color = Some(BorderColor::new(color)?);
+        config.box_name_title = Some(BoxNameTitle::Show);
+        Ok(config)
+    }
+
+    /// 批量启动的计划 (与 spawn_multiple 使用相同的沙盒配置,只列出与现有沙盒不一致的设置)
+    pub fn plan(&self, count: u8) -> Result<SpawnPlan, String> {
+        let boxes = (1..=count)
+            .map(|id| {
+                let (sandbox_name, _, color) = Self::instance_box(id);
+                self.sandboxie.preview_sandbox(&Self::box_config(&sandbox_name, color)?)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(SpawnPlan::sandboxie(&self.wecom_exe, &boxes))
+    }
+
+    /// 按账号配置创建沙盒并启动实例
+    ///
+    /// 应用取自配置的 app_type / app_path (不要求安装企业微信),启动参数和代理通过 Start.exe 传入,
+    /// 数据目录作为沙盒的 FileRootPath。沙盒名取自实例编号,标题取自配置名称。
+    pub fn launch_profile(profile: &AccountProfile) -> Result<WeComInstance, String> {
+        let sandbox_name = profile
+            .sandbox_name()
+            .ok_or_else(|| format!("账号配置 {} 没有实例编号,请先保存", profile.name))?;
+        let exe = match &profile.app_path {
+            Some(path) => path.clone(),
+            None => crate::path_resolver::resolve_app_path(&profile.app_type)?,
         };
+        if !exe.exists() {
+            return Err(format!("应用程序不存在: {}", exe.display()));
+        }
+
+        let mut config = Self::box_config(&sandbox_name, profile.color.as_deref().unwrap_or("#00FFFF"))?;
+        config.file_root_path = Some(profile.resolved_data_dir().display().to_string());
+
+        let env = profile.instance_config().proxy_env();
+        let launch = BoxLaunch {
+            exe: &exe,
+            args: &profile.launch_args,
+            env: &env,
+        };
+        let sandboxie = SandboxieManager::new()?;
+        Self::launch_in_box(&sandboxie, profile.instance_id.unwrap_or(0), profile.name.clone(), &config, &launch)
+    }
+
+    /// 创建沙盒并在其中启动程序
+    fn launch_in_box(
+        sandboxie: &SandboxieManager,
+        id: u8,
+        title: String,
+        config: &SandboxConfig,
+        launch: &BoxLaunch,
+    ) -> Result<WeComInstance, String> {
+        // 与其他进程的启动互斥,避免同时修改 Sandboxie.ini
+        let _spawn_lock = crate::single_instance::lock_spawn_blocking()?;
 
         // 创建沙盒
-        self.sandboxie.create_sandbox(&config)?;
+        sandboxie.create_sandbox(config)?;
 
-        // 在沙盒中启动企业微信
-        let pid = self.sandboxie.start_in_sandbox(
-            &sandbox_name,
-            self.wecom_exe.to_str().unwrap(),
-        )?;
+        // 在沙盒中启动
+        let exe = launch.exe.to_str().ok_or_else(|| format!("应用路径无效: {}", launch.exe.display()))?;
+        let pid = sandboxie.start_in_sandbox(&config.name, exe, launch.args, launch.env)?;
 
         Ok(WeComInstance {
             id,
-            sandbox_name,
+            sandbox_name: config.name.clone(),
             title,
             pid: Some(pid),
             running: true,
@@ -105,7 +169,7 @@ impl WeComManager {
         for i in 1..=count {
             match self.create_instance(i) {
                 Ok(instance) => {
-                    println!("✓ 实例 {} 启动成功 (PID: {:?})", i, instance.pid);
+                    eprintln!("✓ 实例 {} 启动成功 (PID: {:?})", i, instance.pid);
                     instances.push(instance);
                 }
                 Err(e) => {
@@ -142,6 +206,18 @@ impl WeComManager {
     }
 }
 
+/// 在各自的沙盒中批量启动企业微信,返回应记录的实例
+///
+/// 创建沙盒和等待启动锁会阻塞,放到阻塞线程池执行。
+#[cfg(target_os = "windows")]
+pub async fn spawn_sandboxie(count: u8) -> Result<Vec<InstanceRecord>, String> {
+    let manager = WeComManager::new()?;
+    let instances = tokio::task::spawn_blocking(move || manager.spawn_multiple(count))
+        .await
+        .map_err(|e| e.to_string())??;
+    Ok(instances.iter().filter_map(WeComInstance::record).collect())
+}
+
 // 非 Windows 平台的空实现
 #[cfg(not(target_os = "windows"))]
 pub struct WeComManager;
@@ -166,7 +242,7 @@ mod tests {
 
     #[test]
     fn test_find_wecom_path() {
-        let path = WeComManager::find_wecom_exe();
-        println!("检测到的企业微信路径: {:?}", path);
+        let path = crate::path_resolver::resolve_app_path(&AppType::WeCom);
+        eprintln!("检测到的企业微信路径: {:?}", path);
     }
 }
@@ -1,15 +1,17 @@
 // Windows 平台 - Sandboxie 集成模块
-// 仅在 Windows 平台编译
+// 沙盒配置和管理器跨平台编译 (用于启动计划和测试),只在 Windows 上检测 Sandboxie 安装
 
 #[cfg(target_os = "windows")]
-use std::path::PathBuf;
-#[cfg(target_os = "windows")]
-use std::process::Command;
-#[cfg(target_os = "windows")]
+use crate::sandboxie_ini;
+use crate::sandboxie_ini::{SandboxieIni, Section};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
 
-#[cfg(target_os = "windows")]
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SandboxConfig {
     pub name: String,
     pub enabled: bool,
@@ -19,9 +21,26 @@ pub struct SandboxConfig {
     pub closed_key_path: Vec<String>,
     pub open_file_path: Vec<String>,
     pub open_key_path: Vec<String>,
+    /// 沙盒窗口边框 (BorderColor)
+    pub border_color: Option<BorderColor>,
+    /// 窗口标题中显示沙盒名 (BoxNameTitle)
+    pub box_name_title: Option<BoxNameTitle>,
+    /// 启用的模板 (Template)
+    pub templates: Option<Vec<String>>,
+    /// 进程组 (ProcessGroup,不含 Start/Run 限制使用的组)
+    pub process_groups: Option<Vec<ProcessGroup>>,
+    /// 只允许启动这些程序,为空表示不限制 (Start/Run 限制)
+    pub start_run_access: Option<Vec<String>>,
+    /// 禁止访问的网络地址和端口 (ClosedIpPort)
+    pub closed_ip_port: Option<Vec<String>>,
+    /// 快速恢复时复制文件的大小上限,单位 KB (CopyLimitKb)
+    pub copy_limit_kb: Option<u64>,
+    /// 超过复制上限时不提示 (CopyLimitSilent)
+    pub copy_limit_silent: Option<bool>,
+    /// 沙盒内容的存放目录 (FileRootPath)
+    pub file_root_path: Option<String>,
 }
 
-#[cfg(target_os = "windows")]
 impl Default for SandboxConfig {
     fn default() -> Self {
         Self {
@@ -33,132 +52,647 @@ impl Default for SandboxConfig {
             closed_key_path: vec![],
             open_file_path: vec![],
             open_key_path: vec![],
+            border_color: None,
+            box_name_title: None,
+            templates: None,
+            process_groups: None,
+            start_run_access: None,
+            closed_ip_port: None,
+            copy_limit_kb: None,
+            copy_limit_silent: None,
+            file_root_path: None,
+        }
+    }
+}
+
+/// 边框显示方式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BorderMode {
+    /// 始终显示
+    On,
+    /// 鼠标位于标题栏时显示
+    #[default]
+    Ttl,
+    /// 不显示
+    Off,
+}
+
+/// 沙盒窗口边框 (序列化为 Sandboxie 的 "#RRGGBB,ttl,6" 格式)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct BorderColor {
+    /// #RRGGBB
+    pub color: String,
+    pub mode: BorderMode,
+    /// 边框宽度 (像素)
+    pub width: u8,
+}
+
+impl BorderColor {
+    /// 指定颜色,鼠标位于标题栏时显示 6 像素宽的边框
+    pub fn new(color: &str) -> Result<Self, String> {
+        Self::try_from(color.to_string())
+    }
+}
+
+impl TryFrom<String> for BorderColor {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, String> {
+        let mut parts = text.split(',').map(str::trim);
+        let color = parts.next().unwrap_or_default();
+        let hex = color.strip_prefix('#').unwrap_or("");
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("颜色格式应为 #RRGGBB: '{}'", text));
+        }
+        let mode = match parts.next() {
+            None | Some("ttl") => BorderMode::Ttl,
+            Some("on") => BorderMode::On,
+            Some("off") => BorderMode::Off,
+            Some(other) => return Err(format!("未知边框模式 '{}' (可选: on, ttl, off)", other)),
+        };
+        let width = match parts.next() {
+            None => 6,
+            Some(width) => width.parse().map_err(|_| format!("边框宽度无效: '{}'", text))?,
+        };
+        Ok(Self {
+            color: color.to_uppercase(),
+            mode,
+            width,
+        })
+    }
+}
+
+impl From<BorderColor> for String {
+    fn from(border: BorderColor) -> Self {
+        border.to_string()
+    }
+}
+
+impl fmt::Display for BorderColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self.mode {
+            BorderMode::On => "on",
+            BorderMode::Ttl => "ttl",
+            BorderMode::Off => "off",
+        };
+        write!(f, "{},{},{}", self.color, mode, self.width)
+    }
+}
+
+/// 窗口标题中显示的沙盒信息
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BoxNameTitle {
+    /// 显示沙盒名 (y)
+    Show,
+    /// 不显示 (n)
+    Hide,
+    /// 显示沙盒名和进程名 (process)
+    Process,
+}
+
+impl BoxNameTitle {
+    fn ini_value(self) -> &'static str {
+        match self {
+            Self::Show => "y",
+            Self::Hide => "n",
+            Self::Process => "process",
+        }
+    }
+}
+
+/// 进程组,可在其他设置中以 <名称> 引用
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProcessGroup {
+    pub name: String,
+    pub programs: Vec<String>,
+}
+
+impl ProcessGroup {
+    /// Sandboxie.ini 中的写法: <名称>,程序1,程序2
+    fn ini_value(&self) -> String {
+        std::iter::once(format!("<{}>", self.name))
+            .chain(self.programs.iter().cloned())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+/// Start/Run 限制使用的进程组
+const START_RUN_GROUP: &str = "StartRunAccess";
+const START_RUN_GROUP_PREFIX: &str = "<StartRunAccess>,";
+const START_RUN_IPC_PREFIX: &str = "!<StartRunAccess>,";
+
+/// 一条 SbieIni 配置修改
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IniChange {
+    /// 操作: set (空值表示删除整个设置)、append 或 delete (删除一个值)
+    pub op: String,
+    pub section: String,
+    pub key: String,
+    pub value: String,
+}
+
+impl fmt::Display for IniChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} [{}] {}={}", self.op, self.section, self.key, self.value)
+    }
+}
+
+/// 沙盒对账结果
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct ReconcileReport {
+    pub name: String,
+    /// 沙盒原先不存在
+    pub created: bool,
+    /// 需要执行 (或已执行) 的修改
+    pub changes: Vec<IniChange>,
+}
+
+impl ReconcileReport {
+    /// 沙盒已符合配置
+    pub fn is_up_to_date(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// 一项由配置管理的设置
+struct Desired {
+    key: &'static str,
+    /// 期望的值,空表示不应存在
+    values: Vec<String>,
+    /// 单值设置 (其余为多值列表)
+    scalar: bool,
+    /// 由本项管理的现有值,其余的值保持不变
+    owns: fn(&str) -> bool,
+}
+
+impl Desired {
+    fn scalar(key: &'static str, value: Option<String>) -> Self {
+        Self {
+            key,
+            values: value.into_iter().collect(),
+            scalar: true,
+            owns: |_| true,
+        }
+    }
+
+    fn list(key: &'static str, values: Vec<String>) -> Self {
+        Self {
+            key,
+            values,
+            scalar: false,
+            owns: |_| true,
+        }
+    }
+}
+
+impl SandboxConfig {
+    /// 使用默认设置创建指定名称的沙盒配置
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Self::default()
+        }
+    }
+
+    /// 创建沙盒时要写入 Sandboxie.ini 的修改 (按执行顺序)
+    pub fn ini_changes(&self) -> Vec<IniChange> {
+        self.reconcile(None).changes
+    }
+
+    /// 由本配置管理的设置 (为 None 的字段不管理,保留用户或 Sandboxie 的设置)
+    fn desired_settings(&self) -> Vec<Desired> {
+        let flag = |on: bool| if on { "y" } else { "n" }.to_string();
+
+        let mut desired = vec![
+            Desired::scalar("ConfigLevel", Some(self.config_level.to_string())),
+            Desired::scalar("Enabled", Some(flag(self.enabled))),
+            Desired::scalar("AutoDelete", self.auto_delete.then(|| flag(true))),
+        ];
+        if let Some(root) = &self.file_root_path {
+            desired.push(Desired::scalar("FileRootPath", Some(root.clone())));
+        }
+        if let Some(border) = &self.border_color {
+            desired.push(Desired::scalar("BorderColor", Some(border.to_string())));
+        }
+        if let Some(title) = self.box_name_title {
+            desired.push(Desired::scalar("BoxNameTitle", Some(title.ini_value().to_string())));
+        }
+        if let Some(kb) = self.copy_limit_kb {
+            desired.push(Desired::scalar("CopyLimitKb", Some(kb.to_string())));
+        }
+        if let Some(silent) = self.copy_limit_silent {
+            desired.push(Desired::scalar("CopyLimitSilent", silent.then(|| flag(true))));
+        }
+        if let Some(templates) = &self.templates {
+            desired.push(Desired::list("Template", templates.clone()));
+        }
+        if let Some(groups) = &self.process_groups {
+            desired.push(Desired {
+                owns: |value| !value.starts_with(START_RUN_GROUP_PREFIX),
+                ..Desired::list("ProcessGroup", groups.iter().map(ProcessGroup::ini_value).collect())
+            });
+        }
+        // Start/Run 限制: 只有组内的程序可以启动
+        if let Some(programs) = &self.start_run_access {
+            let group = ProcessGroup {
+                name: START_RUN_GROUP.to_string(),
+                programs: programs.clone(),
+            };
+            let restricted = !programs.is_empty();
+            desired.push(Desired {
+                owns: |value| value.starts_with(START_RUN_GROUP_PREFIX),
+                ..Desired::list("ProcessGroup", restricted.then(|| group.ini_value()).into_iter().collect())
+            });
+            let closed_ipc = restricted.then(|| format!("{}*", START_RUN_IPC_PREFIX));
+            desired.push(Desired {
+                owns: |value| value.starts_with(START_RUN_IPC_PREFIX),
+                ..Desired::list("ClosedIpcPath", closed_ipc.into_iter().collect())
+            });
+        }
+
+        desired.extend([
+            Desired::list("ClosedFilePath", self.closed_file_path.clone()),
+            Desired::list("OpenFilePath", self.open_file_path.clone()),
+            Desired::list("ClosedKeyPath", self.closed_key_path.clone()),
+            Desired::list("OpenKeyPath", self.open_key_path.clone()),
+        ]);
+        if let Some(ports) = &self.closed_ip_port {
+            desired.push(Desired::list("ClosedIpPort", ports.clone()));
+        }
+        desired
+    }
+
+    /// 与沙盒当前设置对账: 只修改不一致的设置,列表只追加缺少的值、删除多余的值
+    pub fn reconcile(&self, current: Option<&Section>) -> ReconcileReport {
+        let change = |op: &str, key: &str, value: &str| IniChange {
+            op: op.to_string(),
+            section: self.name.clone(),
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+
+        let mut changes = Vec::new();
+        for Desired { key, values: want, scalar, owns } in self.desired_settings() {
+            let all = current.map(|s| s.get_all(key)).unwrap_or_default();
+            let have: Vec<&str> = all.iter().copied().filter(|v| owns(v)).collect();
+            if have == want {
+                continue;
+            }
+            // 该键的值全部由本项管理时可以整体重写,否则只能逐个删除
+            let owns_all = have.len() == all.len();
+            let mut unique: Vec<&str> = Vec::new();
+            for value in &have {
+                if !unique.contains(value) {
+                    unique.push(value);
+                }
+            }
+
+            let Some((first, rest)) = want.split_first() else {
+                if owns_all {
+                    changes.push(change("set", key, ""));
+                } else {
+                    changes.extend(unique.iter().map(|value| change("delete", key, value)));
+                }
+                continue;
+            };
+
+            let duplicated = unique.len() != have.len();
+            if scalar || (duplicated && owns_all) {
+                // 整体重写
+                changes.push(change("set", key, first));
+                changes.extend(rest.iter().map(|value| change("append", key, value)));
+                continue;
+            }
+            for value in unique.iter().filter(|v| !want.iter().any(|w| w == *v)) {
+                changes.push(change("delete", key, value));
+            }
+            for value in &want {
+                let count = have.iter().filter(|v| **v == value.as_str()).count();
+                if count > 1 {
+                    // 删除重复的值后重新追加一次
+                    changes.push(change("delete", key, value));
+                }
+                if count != 1 {
+                    changes.push(change("append", key, value));
+                }
+            }
+        }
+
+        ReconcileReport {
+            name: self.name.clone(),
+            created: current.is_none_or(|s| s.settings().next().is_none()),
+            changes,
         }
     }
 }
 
+/// 用 SbieIni query 读取沙盒中由配置管理的设置
+fn query_sandbox(
+    config: &SandboxConfig,
+    mut sbieini: impl FnMut(&[&str]) -> Result<String, String>,
+) -> Result<SandboxieIni, String> {
+    let mut text = format!("[{}]\n", config.name);
+    let mut keys: Vec<&str> = config.desired_settings().iter().map(|d| d.key).collect();
+    keys.dedup();
+    for key in keys {
+        let output = sbieini(&["query", &config.name, key])?;
+        for value in output.lines().map(str::trim).filter(|v| !v.is_empty()) {
+            text.push_str(&format!("{}={}\n", key, value));
+        }
+    }
+    Ok(SandboxieIni::parse(&text))
+}
+
+/// 通过 SbieIni 对账: 用 query 读取当前设置,逐条执行修改,有修改时重载一次
+///
+/// `sbieini` 执行一次 SbieIni 命令并返回标准输出,测试中可替换为模拟实现。
+pub fn reconcile_with_sbieini(
+    config: &SandboxConfig,
+    mut sbieini: impl FnMut(&[&str]) -> Result<String, String>,
+) -> Result<ReconcileReport, String> {
+    let current = query_sandbox(config, &mut sbieini)?;
+    let report = config.reconcile(current.section(&config.name));
+    for change in &report.changes {
+        sbieini(&[&change.op, &change.section, &change.key, &change.value])?;
+    }
+    if !report.is_up_to_date() {
+        sbieini(&["reload"])?;
+    }
+    Ok(report)
+}
+
+/// Sandboxie-Plus 常见安装目录
 #[cfg(target_os = "windows")]
-pub struct SandboxieManager {
+pub const SANDBOXIE_DIRS: &[&str] = &[
+    r"C:\Program Files\Sandboxie-Plus",
+    r"C:\Program Files (x86)\Sandboxie-Plus",
+];
+
+/// 外部命令的执行结果
+#[derive(Debug, Clone, Default)]
+pub struct CommandOutput {
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// 执行 SbieIni / Start.exe 等外部命令,测试中可替换为记录调用的模拟实现
+pub trait CommandRunner {
+    /// 执行命令并等待结束
+    fn output(&self, program: &Path, args: &[&str]) -> Result<CommandOutput, String>;
+    /// 当前所有进程的 PID 和可执行文件路径
+    fn process_images(&self) -> Vec<(u32, PathBuf)>;
+}
+
+/// 直接启动系统进程
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn output(&self, program: &Path, args: &[&str]) -> Result<CommandOutput, String> {
+        let output = Command::new(program).args(args).output().map_err(|e| e.to_string())?;
+        Ok(CommandOutput {
+            success: output.status.success(),
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        })
+    }
+
+    fn process_images(&self) -> Vec<(u32, PathBuf)> {
+        #[cfg(target_os = "windows")]
+        return crate::platform::list_process_images();
+        #[cfg(not(target_os = "windows"))]
+        return Vec::new();
+    }
+}
+
+/// 等待沙盒中的程序出现的最长时间
+const START_PID_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 路径中的文件名 (同时支持 \ 和 /)
+fn image_name(path: &str) -> &str {
+    path.rsplit(['\\', '/']).next().unwrap_or(path)
+}
+
+/// 解析 Start.exe /listpids 的输出 (第一行为进程数,之后每行一个 PID)
+fn parse_pids(output: &str) -> Vec<u32> {
+    let numbers: Vec<u32> = output.lines().filter_map(|line| line.trim().parse().ok()).collect();
+    match numbers.split_first() {
+        Some((&count, rest)) if count as usize == rest.len() => rest.to_vec(),
+        _ => numbers,
+    }
+}
+
+pub struct SandboxieManager<R: CommandRunner = SystemRunner> {
+    runner: R,
     sbieini_path: PathBuf,
     start_path: PathBuf,
+    /// Sandboxie.ini 路径 (未找到时只能通过 SbieIni 修改)
+    ini_path: Option<PathBuf>,
 }
 
-#[cfg(target_os = "windows")]
 impl SandboxieManager {
     /// 创建 Sandboxie 管理器
+    #[cfg(target_os = "windows")]
     pub fn new() -> Result<Self, String> {
         // 检测 Sandboxie-Plus 安装路径
-        let possible_paths = vec![
-            r"C:\Program Files\Sandboxie-Plus\SbieIni.exe",
-            r"C:\Program Files (x86)\Sandboxie-Plus\SbieIni.exe",
-        ];
+        let install_dir = SANDBOXIE_DIRS
+            .iter()
+            .map(PathBuf::from)
+            .find(|dir| dir.join("SbieIni.exe").exists())
+            .ok_or_else(|| "未找到 Sandboxie-Plus 安装,请先安装 Sandboxie-Plus".to_string())?;
 
-        let mut sbieini_path = None;
-        for path in possible_paths {
-            let p = PathBuf::from(path);
-            if p.exists() {
-                sbieini_path = Some(p);
-                break;
-            }
+        if !install_dir.join("Start.exe").exists() {
+            return Err("未找到 Start.exe".to_string());
         }
 
-        let sbieini_path = sbieini_path
-            .ok_or_else(|| "未找到 Sandboxie-Plus 安装,请先安装 Sandboxie-Plus".to_string())?;
+        let windows_dir = std::env::var_os("SystemRoot")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(r"C:\Windows"));
+        let ini_path = sandboxie_ini::locate(&windows_dir, &install_dir);
 
-        let start_path = sbieini_path
-            .parent()
-            .ok_or_else(|| "无法获取 Sandboxie 目录".to_string())?
-            .join("Start.exe");
+        Ok(Self::with_runner(&install_dir, ini_path, SystemRunner))
+    }
 
-        if !start_path.exists() {
-            return Err("未找到 Start.exe".to_string());
-        }
+    #[cfg(not(target_os = "windows"))]
+    pub fn new() -> Result<Self, String> {
+        Err("Sandboxie 仅支持 Windows 平台".to_string())
+    }
+}
 
-        Ok(Self {
-            sbieini_path,
-            start_path,
-        })
+impl<R: CommandRunner> SandboxieManager<R> {
+    /// 使用指定的安装目录、配置文件和命令执行器
+    pub fn with_runner(install_dir: &Path, ini_path: Option<PathBuf>, runner: R) -> Self {
+        Self {
+            runner,
+            sbieini_path: install_dir.join("SbieIni.exe"),
+            start_path: install_dir.join("Start.exe"),
+            ini_path,
+        }
     }
 
     /// 执行 SbieIni 命令
     fn run_sbieini(&self, args: &[&str]) -> Result<String, String> {
-        let output = Command::new(&self.sbieini_path)
-            .args(args)
-            .output()
+        let output = self
+            .runner
+            .output(&self.sbieini_path, args)
             .map_err(|e| format!("执行 SbieIni 失败: {}", e))?;
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(format!("SbieIni 命令失败: {}", stderr));
+        if !output.success {
+            return Err(format!("SbieIni 命令失败: {}", output.stderr));
         }
 
-        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        Ok(output.stdout)
     }
 
-    /// 创建沙盒
-    pub fn create_sandbox(&self, config: &SandboxConfig) -> Result<(), String> {
-        println!("正在创建沙盒: {}", config.name);
-
-        // 设置基本配置
-        self.run_sbieini(&["set", &config.name, "ConfigLevel", &config.config_level.to_string()])?;
-        self.run_sbieini(&["set", &config.name, "Enabled", if config.enabled { "y" } else { "n" }])?;
-
-        // 设置自动删除
-        if config.auto_delete {
-            self.run_sbieini(&["set", &config.name, "AutoDelete", "y"])?;
+    /// 直接修改 Sandboxie.ini (需要写入权限),返回是否有改动
+    fn edit_ini(&self, edit: impl FnOnce(&mut SandboxieIni) -> Result<(), String>) -> Result<bool, String> {
+        let path = self.ini_path.as_ref().ok_or_else(|| "未找到 Sandboxie.ini".to_string())?;
+        let before = SandboxieIni::load(path)?;
+        let mut after = before.clone();
+        edit(&mut after)?;
+        if before == after {
+            return Ok(false);
         }
+        after.save(path)?;
+        Ok(true)
+    }
 
-        // 设置文件路径隔离
-        for path in &config.closed_file_path {
-            self.run_sbieini(&["append", &config.name, "ClosedFilePath", path])?;
+    /// 创建沙盒时将要执行的修改 (dry-run 使用,不修改配置)
+    pub fn preview_sandbox(&self, config: &SandboxConfig) -> Result<ReconcileReport, String> {
+        if let Some(ini) = self.ini_path.as_deref().and_then(|path| SandboxieIni::load(path).ok()) {
+            return Ok(config.reconcile(ini.section(&config.name)));
         }
+        let current = query_sandbox(config, |args| self.run_sbieini(args))?;
+        Ok(config.reconcile(current.section(&config.name)))
+    }
 
-        for path in &config.open_file_path {
-            self.run_sbieini(&["append", &config.name, "OpenFilePath", path])?;
-        }
+    /// 创建沙盒或使已有沙盒符合配置,只执行必要的修改
+    ///
+    /// 优先直接修改 Sandboxie.ini;无写入权限时通过 SbieIni 逐条修改。
+    pub fn create_sandbox(&self, config: &SandboxConfig) -> Result<ReconcileReport, String> {
+        eprintln!("正在配置沙盒: {}", config.name);
 
-        // 设置注册表隔离
-        for path in &config.closed_key_path {
-            self.run_sbieini(&["append", &config.name, "ClosedKeyPath", path])?;
+        let mut report = ReconcileReport::default();
+        let edited = self.edit_ini(|ini| {
+            report = config.reconcile(ini.section(&config.name));
+            report.changes.iter().try_for_each(|change| ini.apply(change))
+        });
+        match edited {
+            Ok(changed) => {
+                if changed {
+                    self.run_sbieini(&["reload"])?;
+                }
+            }
+            Err(e) => {
+                eprintln!("⚠ 无法直接修改 Sandboxie.ini,改用 SbieIni: {}", e);
+                report = reconcile_with_sbieini(config, |args| self.run_sbieini(args))?;
+            }
         }
 
-        for path in &config.open_key_path {
-            self.run_sbieini(&["append", &config.name, "OpenKeyPath", path])?;
+        for change in &report.changes {
+            eprintln!("  {}", change);
         }
-
-        // 重载配置
-        self.run_sbieini(&["reload"])?;
-
-        println!("✓ 沙盒 {} 创建成功", config.name);
-        Ok(())
+        if report.created {
+            eprintln!("✓ 沙盒 {} 创建成功", config.name);
+        } else if report.is_up_to_date() {
+            eprintln!("✓ 沙盒 {} 已是最新", config.name);
+        } else {
+            eprintln!("✓ 沙盒 {} 已更新 ({} 项修改)", config.name, report.changes.len());
+        }
+        Ok(report)
     }
 
     /// 删除沙盒
     pub fn delete_sandbox(&self, sandbox_name: &str) -> Result<(), String> {
-        println!("正在删除沙盒: {}", sandbox_name);
-        self.run_sbieini(&["delete_sandbox", sandbox_name])?;
+        eprintln!("正在删除沙盒: {}", sandbox_name);
+        let edited = self.edit_ini(|ini| {
+            ini.remove_section(sandbox_name);
+            Ok(())
+        });
+        if let Err(e) = edited {
+            eprintln!("⚠ 无法直接修改 Sandboxie.ini,改用 SbieIni: {}", e);
+            self.run_sbieini(&["delete_sandbox", sandbox_name])?;
+        }
         self.run_sbieini(&["reload"])?;
-        println!("✓ 沙盒 {} 删除成功", sandbox_name);
+        eprintln!("✓ 沙盒 {} 删除成功", sandbox_name);
         Ok(())
     }
 
-    /// 在沙盒中启动程序
-    pub fn start_in_sandbox(&self, sandbox_name: &str, exe_path: &str) -> Result<u32, String> {
-        println!("正在沙盒 {} 中启动: {}", sandbox_name, exe_path);
+    /// 沙盒中正在运行的进程
+    pub fn list_pids(&self, sandbox_name: &str) -> Result<Vec<u32>, String> {
+        let output = self
+            .runner
+            .output(&self.start_path, &[&format!("/box:{}", sandbox_name), "/listpids"])
+            .map_err(|e| format!("执行 Start.exe 失败: {}", e))?;
+        Ok(parse_pids(&output.stdout))
+    }
+
+    /// 在沙盒中启动程序,返回沙盒内该程序的 PID (而不是很快退出的 Start.exe)
+    ///
+    /// 环境变量通过 Start.exe 的 /env: 参数传入。程序已在沙盒中运行时 (如单实例应用被激活),
+    /// 返回已有进程的 PID。
+    pub fn start_in_sandbox(
+        &self,
+        sandbox_name: &str,
+        exe_path: &str,
+        args: &[String],
+        env: &[(String, String)],
+    ) -> Result<u32, String> {
+        eprintln!("正在沙盒 {} 中启动: {}", sandbox_name, exe_path);
+
+        let mut start_args = vec![format!("/box:{}", sandbox_name)];
+        start_args.extend(env.iter().map(|(key, value)| format!("/env:{}={}", key, value)));
+        start_args.push(exe_path.to_string());
+        start_args.extend(args.iter().cloned());
+        let start_args: Vec<&str> = start_args.iter().map(String::as_str).collect();
 
-        let child = Command::new(&self.start_path)
-            .arg(format!("/box:{}", sandbox_name))
-            .arg(exe_path)
-            .spawn()
+        let before = self.list_pids(sandbox_name)?;
+        let output = self
+            .runner
+            .output(&self.start_path, &start_args)
             .map_err(|e| format!("启动程序失败: {}", e))?;
+        if !output.success {
+            return Err(format!("启动程序失败: {}", output.stderr.trim()));
+        }
+
+        let image = image_name(exe_path);
+        let deadline = Instant::now() + START_PID_TIMEOUT;
+        loop {
+            let images = self.runner.process_images();
+            let mut matching = self.list_pids(sandbox_name)?.into_iter().filter(|pid| {
+                images
+                    .iter()
+                    .any(|(p, path)| p == pid && image_name(&path.to_string_lossy()).eq_ignore_ascii_case(image))
+            });
+            let (started, existing): (Vec<u32>, Vec<u32>) = matching.by_ref().partition(|pid| !before.contains(pid));
 
-        let pid = child.id();
-        println!("✓ 程序已在沙盒中启动 (PID: {})", pid);
-        Ok(pid)
+            let pid = match (started.first(), existing.first()) {
+                (Some(&pid), _) => Some(pid),
+                (None, Some(&pid)) if Instant::now() >= deadline => Some(pid),
+                _ => None,
+            };
+            if let Some(pid) = pid {
+                eprintln!("✓ 程序已在沙盒中启动 (PID: {})", pid);
+                return Ok(pid);
+            }
+            if Instant::now() >= deadline {
+                return Err(format!("沙盒 {} 中未找到已启动的 {}", sandbox_name, image));
+            }
+            std::thread::sleep(Duration::from_millis(200));
+        }
     }
 
     /// 列出所有沙盒
     pub fn list_sandboxes(&self) -> Result<Vec<String>, String> {
+        if let Some(ini) = self.ini_path.as_deref().and_then(|path| SandboxieIni::load(path).ok()) {
+            return Ok(ini.box_names().into_iter().map(String::from).collect());
+        }
+
         let output = self.run_sbieini(&["enum_sandboxes"])?;
         let sandboxes: Vec<String> = output
             .lines()
@@ -176,28 +710,28 @@ impl SandboxieManager {
 
     /// 清理沙盒内容(但保留沙盒配置)
     pub fn cleanup_sandbox(&self, sandbox_name: &str) -> Result<(), String> {
-        println!("正在清理沙盒: {}", sandbox_name);
+        eprintln!("正在清理沙盒: {}", sandbox_name);
 
         // 终止沙盒中的所有进程
-        let _ = Command::new(&self.start_path)
-            .arg(format!("/box:{}", sandbox_name))
-            .arg("/terminate")
-            .output();
+        let _ = self
+            .runner
+            .output(&self.start_path, &[&format!("/box:{}", sandbox_name), "/terminate"]);
 
         // 删除沙盒内容
         self.run_sbieini(&["delete_sandbox_silent", sandbox_name])?;
 
-        println!("✓ 沙盒 {} 清理成功", sandbox_name);
+        eprintln!("✓ 沙盒 {} 清理成功", sandbox_name);
         Ok(())
     }
 }
 
 #[cfg(test)]
-#[cfg(target_os = "windows")]
 mod tests {
     use super::*;
+    use std::cell::RefCell;
 
     #[test]
+    #[cfg(target_os = "windows")]
     fn test_sandboxie_manager_creation() {
         let manager = SandboxieManager::new();
         assert!(manager.is_ok(), "应该能找到 Sandboxie-Plus");
@@ -207,18 +741,410 @@ mod tests {
     fn test_sandbox_config_default() {
         let config = SandboxConfig::default();
         assert_eq!(config.name, "DefaultBox");
-        assert_eq!(config.enabled, true);
+        assert!(config.enabled);
         assert_eq!(config.config_level, 7);
     }
-}
 
-// 非 Windows 平台的空实现
-#[cfg(not(target_os = "windows"))]
-pub struct SandboxieManager;
+    #[test]
+    fn test_ini_changes_order() {
+        let mut config = SandboxConfig::new("WeCom_1");
+        config.auto_delete = true;
+        config.open_file_path = vec![r"C:\Shared".to_string()];
 
-#[cfg(not(target_os = "windows"))]
-impl SandboxieManager {
-    pub fn new() -> Result<Self, String> {
-        Err("Sandboxie 仅支持 Windows 平台".to_string())
+        let changes: Vec<String> = config
+            .ini_changes()
+            .iter()
+            .map(|c| format!("{} {} {}={}", c.op, c.section, c.key, c.value))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                "set WeCom_1 ConfigLevel=7",
+                "set WeCom_1 Enabled=y",
+                "set WeCom_1 AutoDelete=y",
+                r"append WeCom_1 OpenFilePath=C:\Shared",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_border_color() {
+        let border = BorderColor::new("#ff8800").unwrap();
+        assert_eq!(border.to_string(), "#FF8800,ttl,6");
+        assert_eq!(BorderColor::new("#00FF00,on,3").unwrap().mode, BorderMode::On);
+        assert!(BorderColor::new("red").is_err());
+        assert!(BorderColor::new("#00FF00,blink").is_err());
+
+        let json = serde_json::to_value(&border).unwrap();
+        assert_eq!(json, "#FF8800,ttl,6");
+        assert_eq!(serde_json::from_value::<BorderColor>(json).unwrap(), border);
+    }
+
+    #[test]
+    fn test_full_config_changes() {
+        let mut config = SandboxConfig::new("WeCom_1");
+        config.border_color = Some(BorderColor::new("#00FF00").unwrap());
+        config.box_name_title = Some(BoxNameTitle::Show);
+        config.templates = Some(vec!["OpenBluetooth".to_string()]);
+        config.process_groups = Some(vec![ProcessGroup {
+            name: "Browsers".to_string(),
+            programs: vec!["chrome.exe".to_string(), "msedge.exe".to_string()],
+        }]);
+        config.start_run_access = Some(vec!["WXWork.exe".to_string(), "WXWorkWeb.exe".to_string()]);
+        config.closed_ip_port = Some(vec!["*:25".to_string()]);
+        config.copy_limit_kb = Some(80 * 1024);
+        config.copy_limit_silent = Some(true);
+        config.file_root_path = Some(r"D:\Data\Sales".to_string());
+
+        let changes: Vec<String> = config.ini_changes().iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            changes,
+            [
+                "set [WeCom_1] ConfigLevel=7",
+                "set [WeCom_1] Enabled=y",
+                r"set [WeCom_1] FileRootPath=D:\Data\Sales",
+                "set [WeCom_1] BorderColor=#00FF00,ttl,6",
+                "set [WeCom_1] BoxNameTitle=y",
+                "set [WeCom_1] CopyLimitKb=81920",
+                "set [WeCom_1] CopyLimitSilent=y",
+                "append [WeCom_1] Template=OpenBluetooth",
+                "append [WeCom_1] ProcessGroup=<Browsers>,chrome.exe,msedge.exe",
+                "append [WeCom_1] ProcessGroup=<StartRunAccess>,WXWork.exe,WXWorkWeb.exe",
+                "append [WeCom_1] ClosedIpcPath=!<StartRunAccess>,*",
+                "append [WeCom_1] ClosedIpPort=*:25",
+            ]
+        );
+
+        // 换颜色、取消 Start/Run 限制时只修改对应设置
+        let mut ini = SandboxieIni::parse("");
+        config.ini_changes().iter().for_each(|c| ini.apply(c).unwrap());
+        config.border_color = Some(BorderColor::new("#0000FF").unwrap());
+        config.start_run_access = Some(Vec::new());
+        let report = config.reconcile(ini.section("WeCom_1"));
+        let changes: Vec<String> = report.changes.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            changes,
+            [
+                "set [WeCom_1] BorderColor=#0000FF,ttl,6",
+                "delete [WeCom_1] ProcessGroup=<StartRunAccess>,WXWork.exe,WXWorkWeb.exe",
+                "set [WeCom_1] ClosedIpcPath=",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reconcile_keeps_unmanaged_settings() {
+        let ini = SandboxieIni::parse(
+            "[WeCom_1]\nEnabled=y\nConfigLevel=7\nTemplate=OpenBluetooth\nTemplate=SkipHook\n\
+             ProcessGroup=<Custom>,a.exe\nClosedIpcPath=*\\BaseNamedObjects*\\Foo\nClosedIpPort=*:25\n\
+             ProcessGroup=<StartRunAccess>,WXWork.exe\nClosedIpcPath=!<StartRunAccess>,*\n",
+        );
+        let section = ini.section("WeCom_1");
+
+        // 未设置的字段不修改沙盒中已有的设置
+        let mut config = SandboxConfig::new("WeCom_1");
+        config.border_color = Some(BorderColor::new("#00FF00").unwrap());
+        let changes: Vec<String> = config.reconcile(section).changes.iter().map(|c| c.to_string()).collect();
+        assert_eq!(changes, ["set [WeCom_1] BorderColor=#00FF00,ttl,6"]);
+
+        // 只修改本工具管理的进程组和 ClosedIpcPath,保留用户添加的值
+        config.border_color = None;
+        config.start_run_access = Some(Vec::new());
+        let changes: Vec<String> = config.reconcile(section).changes.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            changes,
+            [
+                "delete [WeCom_1] ProcessGroup=<StartRunAccess>,WXWork.exe",
+                "delete [WeCom_1] ClosedIpcPath=!<StartRunAccess>,*",
+            ]
+        );
+
+        config.start_run_access = Some(vec!["WXWork.exe".to_string()]);
+        assert!(config.reconcile(section).is_up_to_date());
+    }
+
+    /// 记录调用的命令执行器,SbieIni 命令在内存中的 Sandboxie.ini 上执行
+    struct RecordingRunner {
+        ini: RefCell<SandboxieIni>,
+        calls: RefCell<Vec<String>>,
+        fail: Option<&'static str>,
+        /// 沙盒中的进程: (沙盒名, PID, 可执行文件)
+        processes: RefCell<Vec<(String, u32, PathBuf)>>,
+    }
+
+    impl RecordingRunner {
+        fn new(text: &str) -> Self {
+            Self {
+                ini: RefCell::new(SandboxieIni::parse(text)),
+                calls: RefCell::new(Vec::new()),
+                fail: None,
+                processes: RefCell::new(Vec::new()),
+            }
+        }
+
+        /// 模拟 Start.exe: 启动时在沙盒中留下 Start.exe 和目标程序两个进程
+        fn start(&self, args: &[&str]) -> String {
+            let sandbox = args[0].trim_start_matches("/box:").to_string();
+            let mut processes = self.processes.borrow_mut();
+            match args[1] {
+                "/listpids" => {
+                    let pids: Vec<String> = processes
+                        .iter()
+                        .filter(|(name, _, _)| *name == sandbox)
+                        .map(|(_, pid, _)| format!("{}\n", pid))
+                        .collect();
+                    format!("{}\n{}", pids.len(), pids.concat())
+                }
+                "/terminate" => {
+                    processes.retain(|(name, _, _)| *name != sandbox);
+                    String::new()
+                }
+                _ => {
+                    let exe = args[1..].iter().find(|arg| !arg.starts_with('/')).unwrap();
+                    let pid = 4241 + processes.len() as u32;
+                    processes.push((sandbox.clone(), pid, PathBuf::from("Start.exe")));
+                    processes.push((sandbox, pid + 1, PathBuf::from(exe)));
+                    String::new()
+                }
+            }
+        }
+
+        fn sbieini(&self, args: &[&str]) -> Result<String, String> {
+            let mut ini = self.ini.borrow_mut();
+            match args {
+                ["query", section, key] => Ok(ini
+                    .section(section)
+                    .map(|s| s.get_all(key).iter().map(|v| format!("{}\n", v)).collect())
+                    .unwrap_or_default()),
+                ["reload"] | ["delete_sandbox_silent", _] => Ok(String::new()),
+                ["delete_sandbox", section] => {
+                    ini.remove_section(section);
+                    Ok(String::new())
+                }
+                [op, section, key, value] => {
+                    let change = IniChange {
+                        op: op.to_string(),
+                        section: section.to_string(),
+                        key: key.to_string(),
+                        value: value.to_string(),
+                    };
+                    ini.apply(&change).map(|_| String::new())
+                }
+                _ => Err(format!("未知命令: {}", args.join(" "))),
+            }
+        }
+    }
+
+    impl CommandRunner for RecordingRunner {
+        fn output(&self, program: &Path, args: &[&str]) -> Result<CommandOutput, String> {
+            let program = program.file_name().unwrap().to_string_lossy();
+            let call = format!("{} {}", program, args.join(" "));
+            self.calls.borrow_mut().push(call.clone());
+
+            if self.fail.is_some_and(|prefix| call.starts_with(prefix)) {
+                return Ok(CommandOutput {
+                    stderr: "拒绝访问".to_string(),
+                    ..Default::default()
+                });
+            }
+            let stdout = match program.as_ref() {
+                "SbieIni.exe" => self.sbieini(args)?,
+                "Start.exe" => self.start(args),
+                _ => String::new(),
+            };
+            Ok(CommandOutput {
+                success: true,
+                stdout,
+                stderr: String::new(),
+            })
+        }
+
+        fn process_images(&self) -> Vec<(u32, PathBuf)> {
+            self.processes.borrow().iter().map(|(_, pid, path)| (*pid, path.clone())).collect()
+        }
+    }
+
+    fn manager(text: &str, ini_path: Option<PathBuf>) -> SandboxieManager<RecordingRunner> {
+        SandboxieManager::with_runner(Path::new("Sandboxie-Plus"), ini_path, RecordingRunner::new(text))
+    }
+
+    /// 取出并清空记录的调用 (不含 query 和 /listpids)
+    fn take_writes(manager: &SandboxieManager<RecordingRunner>) -> Vec<String> {
+        let calls = manager.runner.calls.take();
+        calls
+            .into_iter()
+            .filter(|c| !c.starts_with("SbieIni.exe query ") && !c.ends_with(" /listpids"))
+            .collect()
+    }
+
+    fn wecom_box() -> SandboxConfig {
+        let mut config = SandboxConfig::new("WeCom_1");
+        config.closed_file_path = vec![r"C:\Users\Public".to_string()];
+        config.open_file_path = vec![r"C:\Shared".to_string(), r"D:\Files".to_string()];
+        config
+    }
+
+    #[test]
+    fn test_create_sandbox_is_idempotent() {
+        let manager = manager("[GlobalSettings]\nFileRootPath=C:\\Sandbox\n", None);
+        let config = wecom_box();
+
+        let report = manager.create_sandbox(&config).unwrap();
+        assert!(report.created);
+        assert_eq!(
+            take_writes(&manager),
+            [
+                "SbieIni.exe set WeCom_1 ConfigLevel 7",
+                "SbieIni.exe set WeCom_1 Enabled y",
+                r"SbieIni.exe append WeCom_1 ClosedFilePath C:\Users\Public",
+                r"SbieIni.exe append WeCom_1 OpenFilePath C:\Shared",
+                r"SbieIni.exe append WeCom_1 OpenFilePath D:\Files",
+                "SbieIni.exe reload",
+            ]
+        );
+
+        // 再次对账不产生任何修改,也不重载
+        let report = manager.create_sandbox(&config).unwrap();
+        assert!(!report.created);
+        assert!(report.is_up_to_date());
+        assert!(take_writes(&manager).is_empty());
+    }
+
+    #[test]
+    fn test_create_sandbox_adds_and_removes() {
+        let manager = manager(
+            "[WeCom_1]\nEnabled=y\nConfigLevel=5\nAutoDelete=y\n\
+             OpenFilePath=C:\\Shared\nOpenFilePath=C:\\Old\n\
+             ClosedFilePath=C:\\Users\\Public\nClosedFilePath=C:\\Users\\Public\n",
+            None,
+        );
+
+        let report = manager.create_sandbox(&wecom_box()).unwrap();
+        assert!(!report.created);
+        assert_eq!(
+            take_writes(&manager),
+            [
+                "SbieIni.exe set WeCom_1 ConfigLevel 7",
+                "SbieIni.exe set WeCom_1 AutoDelete ",
+                r"SbieIni.exe set WeCom_1 ClosedFilePath C:\Users\Public",
+                r"SbieIni.exe delete WeCom_1 OpenFilePath C:\Old",
+                r"SbieIni.exe append WeCom_1 OpenFilePath D:\Files",
+                "SbieIni.exe reload",
+            ]
+        );
+
+        let ini = manager.runner.ini.borrow();
+        let section = ini.section("WeCom_1").unwrap();
+        assert_eq!(section.get("AutoDelete"), None);
+        assert_eq!(section.get_all("ClosedFilePath"), [r"C:\Users\Public"]);
+        assert_eq!(section.get_all("OpenFilePath"), [r"C:\Shared", r"D:\Files"]);
+        assert!(wecom_box().reconcile(Some(section)).is_up_to_date());
+    }
+
+    #[test]
+    fn test_create_sandbox_writes_ini_directly() {
+        let path = crate::app_dirs::temp_test_dir("sandboxie-ini").join(crate::sandboxie_ini::INI_FILE);
+        std::fs::write(&path, include_bytes!("../tests/fixtures/Sandboxie.ini")).unwrap();
+        let manager = manager("", Some(path.clone()));
+
+        let mut config = SandboxConfig::new("WeCom_2");
+        config.open_file_path = vec![r"C:\Shared".to_string()];
+        assert!(manager.create_sandbox(&config).unwrap().created);
+        assert_eq!(take_writes(&manager), ["SbieIni.exe reload"]);
+
+        let ini = SandboxieIni::load(&path).unwrap();
+        assert!(config.reconcile(ini.section("WeCom_2")).is_up_to_date());
+        assert!(ini.section("WeCom_1").is_some());
+
+        // 已符合配置时不写文件也不重载
+        assert!(manager.create_sandbox(&config).unwrap().is_up_to_date());
+        assert!(take_writes(&manager).is_empty());
+
+        manager.delete_sandbox("WeCom_2").unwrap();
+        assert_eq!(take_writes(&manager), ["SbieIni.exe reload"]);
+        assert!(SandboxieIni::load(&path).unwrap().section("WeCom_2").is_none());
+        assert_eq!(manager.list_sandboxes().unwrap(), ["DefaultBox", "WeCom_1"]);
+    }
+
+    #[test]
+    fn test_preview_sandbox() {
+        let manager = manager("[WeCom_1]\nEnabled=y\nConfigLevel=5\n", None);
+
+        let report = manager.preview_sandbox(&SandboxConfig::new("WeCom_1")).unwrap();
+        assert!(!report.created);
+        assert_eq!(report.changes.len(), 1);
+        assert_eq!(report.changes[0].to_string(), "set [WeCom_1] ConfigLevel=7");
+        // 只查询,不修改配置
+        assert!(take_writes(&manager).is_empty());
+
+        let report = manager.preview_sandbox(&SandboxConfig::new("WeCom_2")).unwrap();
+        assert!(report.created);
+        assert_eq!(report.changes.len(), 2);
+    }
+
+    #[test]
+    fn test_delete_and_cleanup_sandbox() {
+        let manager = manager("[WeCom_1]\nEnabled=y\n", None);
+
+        manager.delete_sandbox("WeCom_1").unwrap();
+        assert_eq!(take_writes(&manager), ["SbieIni.exe delete_sandbox WeCom_1", "SbieIni.exe reload"]);
+        assert!(manager.runner.ini.borrow().section("WeCom_1").is_none());
+
+        manager.cleanup_sandbox("WeCom_2").unwrap();
+        assert_eq!(
+            take_writes(&manager),
+            ["Start.exe /box:WeCom_2 /terminate", "SbieIni.exe delete_sandbox_silent WeCom_2"]
+        );
+    }
+
+    #[test]
+    fn test_start_in_sandbox() {
+        let manager = manager("", None);
+        let pid = manager.start_in_sandbox("WeCom_1", r"C:\WXWork\WXWork.exe", &[], &[]).unwrap();
+        assert_eq!(pid, 4242);
+        assert_eq!(take_writes(&manager), [r"Start.exe /box:WeCom_1 C:\WXWork\WXWork.exe"]);
+
+        let args = ["--debug".to_string()];
+        let env = [("HTTPS_PROXY".to_string(), "http://127.0.0.1:7890".to_string())];
+        let pid = manager.start_in_sandbox("WeComP_101", r"C:\WeChat\WeChat.exe", &args, &env).unwrap();
+        assert_eq!(pid, 4244);
+        assert_eq!(
+            take_writes(&manager),
+            [r"Start.exe /box:WeComP_101 /env:HTTPS_PROXY=http://127.0.0.1:7890 C:\WeChat\WeChat.exe --debug"]
+        );
+
+        // 同一沙盒中再次启动时返回新进程,而不是已有的实例
+        let pid = manager.start_in_sandbox("WeCom_1", r"C:\WXWork\WXWork.exe", &[], &[]).unwrap();
+        assert_eq!(pid, 4246);
+        assert_eq!(manager.list_pids("WeCom_1").unwrap(), [4241, 4242, 4245, 4246]);
+    }
+
+    #[test]
+    fn test_start_in_sandbox_failure() {
+        let mut manager = manager("", None);
+        manager.runner.fail = Some("Start.exe /box:WeCom_1 C:");
+
+        let err = manager.start_in_sandbox("WeCom_1", r"C:\WXWork\WXWork.exe", &[], &[]).unwrap_err();
+        assert!(err.contains("拒绝访问"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_pids() {
+        assert_eq!(parse_pids("2\r\n1234\r\n5678\r\n"), [1234, 5678]);
+        assert_eq!(parse_pids("0\n"), Vec::<u32>::new());
+        assert_eq!(parse_pids("1234\n5678\n"), [1234, 5678]);
+        assert_eq!(image_name(r"C:\WXWork\WXWork.exe"), "WXWork.exe");
+        assert_eq!(image_name("/opt/wecom/wecom"), "wecom");
+    }
+
+    #[test]
+    fn test_sbieini_failure() {
+        let mut manager = manager("", None);
+        manager.runner.fail = Some("SbieIni.exe set");
+
+        let err = manager.create_sandbox(&wecom_box()).unwrap_err();
+        assert!(err.contains("SbieIni 命令失败: 拒绝访问"), "{}", err);
+        // 第一条修改失败后不再继续,也不重载
+        assert_eq!(take_writes(&manager), ["SbieIni.exe set WeCom_1 ConfigLevel 7"]);
     }
 }
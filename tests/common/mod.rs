@@ -0,0 +1,73 @@
+// 集成测试共用: 不启动进程的后端和临时目录
+
+use std::path::PathBuf;
+use std::sync::Mutex;
+use wecom_multi_open::daemon::Backend;
+use wecom_multi_open::monitor::InstanceStats;
+use wecom_multi_open::profiles::AccountProfile;
+use wecom_multi_open::registry::{InstanceRecord, InstanceRegistry, ReconcileReport};
+use wecom_multi_open::{IsolationMode, SpawnRequest, SpawnResponse};
+
+/// 不启动进程的后端: 实例 PID 从 pid_base 起依次分配,账号配置实例的 PID 为 pid_base + 1000
+pub struct FakeBackend {
+    pid_base: u32,
+    spawned: Mutex<u32>,
+}
+
+impl FakeBackend {
+    pub fn new(pid_base: u32) -> Self {
+        Self {
+            pid_base,
+            spawned: Mutex::new(0),
+        }
+    }
+}
+
+impl Backend for FakeBackend {
+    async fn spawn(&self, req: SpawnRequest) -> Result<SpawnResponse, String> {
+        let mut spawned = self.spawned.lock().unwrap();
+        let pids: Vec<u32> = (0..req.count as u32).map(|i| self.pid_base + *spawned + i).collect();
+        *spawned += req.count as u32;
+        Ok(SpawnResponse {
+            success: pids.len(),
+            failed: 0,
+            pids,
+            plan: None,
+        })
+    }
+
+    async fn launch_profile(&self, profile: AccountProfile) -> Result<InstanceRecord, String> {
+        let mut record = InstanceRecord::new(self.pid_base + 1000, profile.app_type.clone(), IsolationMode::Simple);
+        record.profile = Some(profile.name);
+        Ok(record)
+    }
+
+    fn kill(&self, _pid: u32) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn reconcile(&self, _registry: &mut InstanceRegistry) -> ReconcileReport {
+        ReconcileReport::default()
+    }
+
+    fn sample(&self, pids: &[u32]) -> Vec<InstanceStats> {
+        pids.iter()
+            .map(|&pid| InstanceStats {
+                pid,
+                cpu_percent: 0.0,
+                rss_bytes: 1024 * pid as u64,
+                threads: 1,
+                uptime_secs: 1,
+                children: Vec::new(),
+            })
+            .collect()
+    }
+}
+
+/// 清空并创建临时目录
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("wecom-multi-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
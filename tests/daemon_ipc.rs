@@ -0,0 +1,96 @@
+// 后台服务集成测试: 通过真实的 Unix socket 与后台服务通信 (使用不启动进程的后端)
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::{temp_dir, FakeBackend};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use wecom_multi_open::daemon::{Client, Daemon};
+use wecom_multi_open::registry::InstanceRegistry;
+use wecom_multi_open::{AppType, SpawnRequest};
+
+fn start(dir: &Path) -> (PathBuf, tokio::task::JoinHandle<Result<(), String>>) {
+    let endpoint = dir.join("daemon.sock");
+    let daemon = Daemon::new(FakeBackend::new(5000), InstanceRegistry::new(dir.join("instances.json")));
+    let path = endpoint.clone();
+    (endpoint, tokio::spawn(async move { daemon.serve(&path).await }))
+}
+
+async fn connect(endpoint: &Path) -> Client {
+    for _ in 0..100 {
+        if let Ok(client) = Client::connect(endpoint).await {
+            return client;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("无法连接后台服务: {}", endpoint.display());
+}
+
+fn spawn_request(count: u8) -> SpawnRequest {
+    SpawnRequest {
+        count,
+        app_path: None,
+        app_type: Some(AppType::WeCom),
+        instance_configs: None,
+        dry_run: false,
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_clients_share_instances() {
+    let dir = temp_dir("daemon-share");
+    let (endpoint, server) = start(&dir);
+
+    let mut cli = connect(&endpoint).await;
+    let mut gui = connect(&endpoint).await;
+
+    // socket 仅当前用户可读写
+    let mode = std::fs::metadata(&endpoint).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    // 一个客户端启动的实例,另一个客户端可以看到并结束
+    let response = cli.spawn(&spawn_request(2)).await.unwrap();
+    assert_eq!(response.pids, [5000, 5001]);
+    let pids: Vec<u32> = gui.list().await.unwrap().iter().map(|r| r.pid).collect();
+    assert_eq!(pids, [5000, 5001]);
+
+    gui.kill(5000).await.unwrap();
+    assert_eq!(cli.list().await.unwrap().len(), 1);
+    assert!(cli.kill(5000).await.unwrap_err().contains("未找到实例"));
+    assert_eq!(cli.ping().await.unwrap().instances, 1);
+
+    // 状态持久化到注册表文件
+    let registry = InstanceRegistry::load(&dir.join("instances.json")).unwrap();
+    assert_eq!(registry.pids(), [5001]);
+
+    // 同一地址不能启动第二个后台服务
+    let (_, second) = start(&dir);
+    assert!(second.await.unwrap().unwrap_err().contains("已在运行"));
+
+    cli.shutdown().await.unwrap();
+    server.await.unwrap().unwrap();
+    assert!(!endpoint.exists());
+    assert!(Client::connect(&endpoint).await.is_err());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_stale_socket_is_replaced() {
+    let dir = temp_dir("daemon-stale");
+    let endpoint = dir.join("daemon.sock");
+
+    // 上次异常退出留下的 socket 文件
+    drop(std::os::unix::net::UnixListener::bind(&endpoint).unwrap());
+    assert!(endpoint.exists());
+
+    let (_, server) = start(&dir);
+    let mut client = connect(&endpoint).await;
+    assert_eq!(client.register(&[]).await.unwrap(), 0);
+    client.shutdown().await.unwrap();
+    server.await.unwrap().unwrap();
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
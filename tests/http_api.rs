@@ -0,0 +1,128 @@
+// REST API 端到端测试: 通过真实的 TCP 连接访问 (使用不启动进程的后端)
+
+mod common;
+
+use common::{temp_dir, FakeBackend};
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use wecom_multi_open::daemon::Daemon;
+use wecom_multi_open::http_api::HttpApi;
+use wecom_multi_open::profiles::{AccountProfile, ProfileStore};
+use wecom_multi_open::registry::InstanceRegistry;
+use wecom_multi_open::{AppType, SpawnResponse};
+
+const TOKEN: &str = "test-token-0123456789";
+
+/// 发送一个请求,返回状态码和原始响应体
+async fn request_text(addr: std::net::SocketAddr, method: &str, path: &str, token: Option<&str>, body: Option<Value>) -> (u16, String) {
+    let body = body.map(|b| b.to_string()).unwrap_or_default();
+    let auth = token.map(|t| format!("Authorization: Bearer {}\r\n", t)).unwrap_or_default();
+    let raw = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\n{}Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        addr,
+        auth,
+        body.len(),
+        body
+    );
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(raw.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+    (status, body.to_string())
+}
+
+/// 发送一个请求,返回状态码和 JSON 响应体
+async fn request(addr: std::net::SocketAddr, method: &str, path: &str, token: Option<&str>, body: Option<Value>) -> (u16, Value) {
+    let (status, body) = request_text(addr, method, path, token, body).await;
+    (status, serde_json::from_str(&body).unwrap())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_rest_api_end_to_end() {
+    let dir = temp_dir("http-e2e");
+    let mut store = ProfileStore::load(&dir.join("profiles.json")).unwrap();
+    store.create(AccountProfile::new("销售 1", AppType::WeChat)).unwrap();
+    store.save().unwrap();
+
+    let daemon = Daemon::with_profiles(
+        FakeBackend::new(7000),
+        InstanceRegistry::new(dir.join("instances.json")),
+        dir.join("profiles.json"),
+    );
+    let api = HttpApi::bind(0, TOKEN).await.unwrap();
+    let addr = api.local_addr().unwrap();
+    assert!(addr.ip().is_loopback());
+    let server = tokio::spawn(api.serve(daemon.clone()));
+
+    // 无需认证
+    let (status, health) = request(addr, "GET", "/health", None, None).await;
+    assert_eq!(status, 200);
+    assert_eq!(health["status"], "ok");
+    let (status, spec) = request(addr, "GET", "/openapi.json", None, None).await;
+    assert_eq!(status, 200);
+    assert!(spec["paths"]["/instances"]["post"].is_object());
+
+    // 缺少或错误的令牌
+    assert_eq!(request(addr, "GET", "/instances", None, None).await.0, 401);
+    assert_eq!(request(addr, "GET", "/instances", Some("wrong-token-0123456789"), None).await.0, 401);
+
+    // 启动、列出、结束
+    let spawn = json!({ "count": 2, "app_type": "WeCom" });
+    let (status, response) = request(addr, "POST", "/instances", Some(TOKEN), Some(spawn)).await;
+    assert_eq!(status, 200);
+    let response: SpawnResponse = serde_json::from_value(response).unwrap();
+    assert_eq!(response.pids, [7000, 7001]);
+
+    let (_, list) = request(addr, "GET", "/instances", Some(TOKEN), None).await;
+    assert_eq!(list.as_array().unwrap().len(), 2);
+
+    assert_eq!(request(addr, "DELETE", "/instances/7000", Some(TOKEN), None).await.0, 200);
+    let (status, error) = request(addr, "DELETE", "/instances/7000", Some(TOKEN), None).await;
+    assert_eq!(status, 404);
+    assert!(error["error"]["message"].as_str().unwrap().contains("未找到实例"));
+    assert_eq!(request(addr, "DELETE", "/instances/abc", Some(TOKEN), None).await.0, 400);
+    assert_eq!(request(addr, "POST", "/instances", Some(TOKEN), Some(json!({ "count": "two" }))).await.0, 400);
+
+    // 账号配置
+    let (_, profiles) = request(addr, "GET", "/profiles", Some(TOKEN), None).await;
+    assert_eq!(profiles[0]["name"], "销售 1");
+    let (status, record) = request(addr, "POST", "/profiles/%E9%94%80%E5%94%AE%201/launch", Some(TOKEN), None).await;
+    assert_eq!(status, 200);
+    assert_eq!(record["pid"], 8000);
+    assert_eq!(record["profile"], "销售 1");
+    assert_eq!(request(addr, "POST", "/profiles/none/launch", Some(TOKEN), None).await.0, 404);
+
+    // 指标: GUI 守护进程重启实例后同步到后台服务
+    let restarted = daemon.call("restarted", json!({ "old_pid": 7001, "new_pid": 7002 })).await.unwrap();
+    assert_eq!(restarted["restarts"], 1);
+    assert_eq!(request_text(addr, "GET", "/metrics", None, None).await.0, 401);
+    let (status, metrics) = request_text(addr, "GET", "/metrics", Some(TOKEN), None).await;
+    assert_eq!(status, 200);
+    let lines: Vec<&str> = metrics.lines().collect();
+    assert!(lines.contains(&"wecom_multi_instances{app=\"wecom\",backend=\"simple\"} 1"), "{}", metrics);
+    assert!(lines.contains(&"wecom_multi_instances{app=\"wechat\",backend=\"simple\"} 1"));
+    assert!(lines.contains(&"wecom_multi_instance_restarts{pid=\"7002\",app=\"wecom\",profile=\"\"} 1"));
+    assert!(lines.contains(&"wecom_multi_instance_memory_bytes{pid=\"8000\",app=\"wechat\",profile=\"销售 1\"} 8192000"));
+    assert!(lines.iter().any(|l| l.starts_with("wecom_multi_spawn_duration_seconds_count ")));
+
+    let (_, killed) = request(addr, "DELETE", "/instances", Some(TOKEN), None).await;
+    assert_eq!(killed["killed"], json!([7002, 8000]));
+
+    // 未知路由
+    assert_eq!(request(addr, "GET", "/nothing", Some(TOKEN), None).await.0, 404);
+    assert_eq!(request(addr, "PUT", "/instances", Some(TOKEN), None).await.0, 405);
+
+    // 后台服务停止时 API 随之停止
+    daemon.call("shutdown", Value::Null).await.unwrap();
+    server.await.unwrap().unwrap();
+    assert!(TcpStream::connect(addr).await.is_err());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}